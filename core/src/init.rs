@@ -1,3 +1,4 @@
+use crate::kpk::init_kpk;
 use crate::precalc::boards::init_boards;
 use crate::precalc::magic::init_magics;
 use crate::precalc::zobrist::init_zobrist;
@@ -9,7 +10,8 @@ static INITALIZED: Once = Once::new();
 /// board representation, such as:
 /// - magic bitboard tables
 /// - precalculated piece movements
-/// - zobrist hash keys.
+/// - zobrist hash keys
+/// - the king and pawn versus king bitbase.
 ///
 /// Any subsequent calls to this function after the first have no
 /// effect and should return instantly.
@@ -20,5 +22,6 @@ pub fn init_globals() {
         init_magics();
         init_boards();
         init_zobrist();
+        init_kpk();
     })
 }
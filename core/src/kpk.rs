@@ -0,0 +1,305 @@
+//! A bitbase for king and pawn versus king endings.
+//!
+//! Every position with a white king, white pawn and black king is classified as a win for white or
+//! a draw, for either side to move. Positions are normalised so that the pawn is on files a-d,
+//! which leaves 2 * 24 * 64 * 64 positions, stored as one bit each. The bitbase is generated by
+//! retrograde iteration when the globals are initialised, and takes a few milliseconds.
+
+use crate::position::{PieceType, Player, Position, Square};
+use crate::precalc::boards::{king_moves, pawn_attacks_from};
+
+/// The number of normalised positions: side to move, black king square, white king square and
+/// pawn square (24 squares on files a-d, ranks 2-7).
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+
+/// One bit per position, set if white wins.
+static mut KPK_BITBASE: [u32; MAX_INDEX / 32] = [0; MAX_INDEX / 32];
+
+/// The outcome of a king and pawn versus king position, with best play.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The side with the pawn wins.
+    Win,
+    Draw,
+}
+
+/// Look up the outcome of `pos`. Returns `None` if `pos` isn't a king and pawn versus king
+/// position.
+pub fn probe(pos: &Position) -> Option<Outcome> {
+    if pos.occupied().popcnt() != 3 {
+        return None;
+    }
+
+    let strong = if pos.piece_bb(Player::WHITE, PieceType::Pawn).popcnt() == 1 {
+        Player::WHITE
+    } else if pos.piece_bb(Player::BLACK, PieceType::Pawn).popcnt() == 1 {
+        Player::BLACK
+    } else {
+        return None;
+    };
+
+    let win = probe_squares(
+        normalise(pos.king_sq(strong), strong),
+        normalise(pos.piece_bb(strong, PieceType::Pawn).to_square(), strong),
+        normalise(pos.king_sq(!strong), strong),
+        pos.turn() == strong,
+    );
+
+    Some(if win { Outcome::Win } else { Outcome::Draw })
+}
+
+/// Look up whether the side with the pawn wins, given the squares of the pieces as if the side
+/// with the pawn were white.
+pub fn probe_squares(wksq: Square, psq: Square, bksq: Square, white_to_move: bool) -> bool {
+    debug_assert!(psq.rank() >= 1 && psq.rank() <= 6);
+
+    // Mirror the position so that the pawn is on files a-d.
+    let (wksq, psq, bksq) = if psq.file() > 3 {
+        (Square(wksq.0 ^ 7), Square(psq.0 ^ 7), Square(bksq.0 ^ 7))
+    } else {
+        (wksq, psq, bksq)
+    };
+
+    let stm = if white_to_move {
+        Player::WHITE
+    } else {
+        Player::BLACK
+    };
+    let idx = index(stm, bksq, wksq, psq);
+
+    unsafe { KPK_BITBASE.get_unchecked(idx / 32) & (1 << (idx % 32)) != 0 }
+}
+
+/// Flip the square vertically if `strong` is black, so that the pawn moves up the board.
+fn normalise(sq: Square, strong: Player) -> Square {
+    match strong {
+        Player::WHITE => sq,
+        Player::BLACK => Square(sq.0 ^ 56),
+    }
+}
+
+/// The index of a normalised position. Bit 0 is the side to move, bits 1-6 the black king,
+/// bits 7-12 the white king, bits 13-14 the pawn file and bits 15-17 the pawn rank counted from
+/// the 7th.
+fn index(stm: Player, bksq: Square, wksq: Square, psq: Square) -> usize {
+    stm.is_black() as usize
+        | (bksq.0 as usize) << 1
+        | (wksq.0 as usize) << 7
+        | (psq.file() as usize) << 13
+        | (6 - psq.rank() as usize) << 15
+}
+
+/// The classification of a position during generation. The values are bit flags so that the
+/// results of all the moves from a position can be combined with bitwise or.
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+#[derive(Copy, Clone)]
+struct KpkPosition {
+    stm: Player,
+    wksq: Square,
+    bksq: Square,
+    psq: Square,
+    result: u8,
+}
+
+impl KpkPosition {
+    fn new(idx: usize) -> Self {
+        let stm = if idx & 1 == 0 {
+            Player::WHITE
+        } else {
+            Player::BLACK
+        };
+        let bksq = Square(((idx >> 1) & 63) as u8);
+        let wksq = Square(((idx >> 7) & 63) as u8);
+        let psq = Square::from_rank_file(6 - ((idx >> 15) & 7), (idx >> 13) & 3);
+
+        let push = Square(psq.0 + 8);
+        let pawn_attacks = pawn_attacks_from(psq, Player::WHITE);
+
+        let result = if wksq.distance(bksq) <= 1
+            || wksq == psq
+            || bksq == psq
+            || (stm == Player::WHITE && pawn_attacks & bksq.to_bb().0 != 0)
+        {
+            INVALID
+        } else if stm == Player::WHITE
+            && psq.rank() == 6
+            && wksq != push
+            && bksq != push
+            && (bksq.distance(push) > 1 || wksq.distance(push) == 1)
+        {
+            // The pawn promotes and the new queen can't be captured.
+            WIN
+        } else if stm == Player::BLACK
+            && ((king_moves(bksq).0 & !(king_moves(wksq).0 | pawn_attacks) == 0)
+                || (bksq.distance(psq) == 1 && wksq.distance(psq) > 1))
+        {
+            // Stalemate, or the pawn can be captured.
+            DRAW
+        } else {
+            UNKNOWN
+        };
+
+        Self {
+            stm,
+            wksq,
+            bksq,
+            psq,
+            result,
+        }
+    }
+
+    /// Classify the position from the results of its successors. White to move wins if any move
+    /// wins; black to move draws if any move draws.
+    fn classify(&self, db: &[KpkPosition]) -> u8 {
+        let mut r = INVALID;
+
+        if self.stm == Player::WHITE {
+            for sq in king_moves(self.wksq) {
+                r |= db[index(Player::BLACK, self.bksq, sq, self.psq)].result;
+            }
+
+            let push = Square(self.psq.0 + 8);
+            if self.psq.rank() < 6 {
+                r |= db[index(Player::BLACK, self.bksq, self.wksq, push)].result;
+            }
+            if self.psq.rank() == 1 && push != self.wksq && push != self.bksq {
+                let double_push = Square(self.psq.0 + 16);
+                r |= db[index(Player::BLACK, self.bksq, self.wksq, double_push)].result;
+            }
+
+            if r & WIN != 0 {
+                WIN
+            } else if r & UNKNOWN != 0 {
+                UNKNOWN
+            } else {
+                DRAW
+            }
+        } else {
+            for sq in king_moves(self.bksq) {
+                r |= db[index(Player::WHITE, sq, self.wksq, self.psq)].result;
+            }
+
+            if r & DRAW != 0 {
+                DRAW
+            } else if r & UNKNOWN != 0 {
+                UNKNOWN
+            } else {
+                WIN
+            }
+        }
+    }
+}
+
+#[cold]
+pub fn init_kpk() {
+    let mut db: Vec<KpkPosition> = (0..MAX_INDEX).map(KpkPosition::new).collect();
+
+    // Iterate until no unknown position can be resolved. Any position still unknown at the end
+    // can't be forced into a win, so is a draw.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..MAX_INDEX {
+            if db[idx].result == UNKNOWN {
+                let result = db[idx].classify(&db);
+                if result != UNKNOWN {
+                    db[idx].result = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    unsafe {
+        for (idx, pos) in db.iter().enumerate() {
+            if pos.result == WIN {
+                KPK_BITBASE[idx / 32] |= 1 << (idx % 32);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::init_globals;
+
+    fn outcome(fen: &str) -> Option<Outcome> {
+        init_globals();
+        probe(&Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn kpk_outcomes() {
+        // King in front of the pawn on the 6th rank wins, whoever is to move.
+        assert_eq!(
+            outcome("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(Outcome::Win)
+        );
+        assert_eq!(
+            outcome("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Win)
+        );
+
+        // With the king on the 5th, the opposition decides.
+        assert_eq!(
+            outcome("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(
+            outcome("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"),
+            Some(Outcome::Win)
+        );
+
+        // Rook pawns are drawn once the defending king reaches the corner.
+        assert_eq!(
+            outcome("k7/8/P7/8/8/8/8/4K3 w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+
+        // Stalemate.
+        assert_eq!(
+            outcome("k7/P7/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Draw)
+        );
+
+        // The defending king is outside the square of the pawn.
+        assert_eq!(outcome("7k/8/8/8/P7/8/8/7K b - - 0 1"), Some(Outcome::Win));
+
+        // The pawn is lost.
+        assert_eq!(
+            outcome("8/8/8/8/8/1k6/P7/7K w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+    }
+
+    #[test]
+    fn kpk_is_colour_symmetric() {
+        assert_eq!(
+            outcome("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"),
+            Some(Outcome::Win)
+        );
+        assert_eq!(
+            outcome("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"),
+            Some(Outcome::Win)
+        );
+        assert_eq!(
+            outcome("8/8/8/3p4/3k4/8/3K4/8 b - - 0 1"),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(
+            outcome("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1"),
+            Some(Outcome::Win)
+        );
+    }
+
+    #[test]
+    fn kpk_ignores_other_material() {
+        assert_eq!(outcome("4k3/8/4K3/4P3/8/8/8/7N w - - 0 1"), None);
+        assert_eq!(outcome("4k3/8/4K3/8/8/8/8/8 w - - 0 1"), None);
+    }
+}
@@ -1,6 +1,4 @@
 #![feature(stmt_expr_attributes)]
-#![feature(maybe_uninit_slice)]
-#![feature(maybe_uninit_uninit_array)]
 
 #[macro_use]
 mod macros;
@@ -11,6 +9,7 @@ mod precalc;
 
 pub mod bb;
 pub mod init;
+pub mod kpk;
 pub mod mono_traits;
 pub mod mov;
 pub mod movegen;
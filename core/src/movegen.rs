@@ -9,6 +9,8 @@ use crate::position::{CastleType, PieceType, Player, Position, Square, PROMO_PIE
 use crate::precalc::boards::{between_bb, king_moves, knight_moves, line_bb, pawn_attacks_from};
 use crate::precalc::magic;
 
+use std::marker::PhantomData;
+
 /// Types of move generating options.
 ///
 /// `Generation::All` -> All available moves.
@@ -69,6 +71,26 @@ impl MoveGen {
         InnerMoveGen::<ML>::generate::<G, L>(position, movelist);
     }
 
+    /// Generate the moves of the piece on `orig` only, according to the parameters specified by
+    /// the dummy passed as generic types. The piece must belong to the side to move.
+    #[inline]
+    pub fn generate_from<ML: MoveList, G: Generate, L: Legality>(
+        position: &Position,
+        orig: Square,
+        movelist: &mut ML,
+    ) {
+        InnerMoveGen::<ML>::generate_from::<G, L>(position, orig, movelist);
+    }
+
+    /// Count the moves in the passed position according to the parameters specified by the dummy
+    /// passed as generic types, without storing them.
+    #[inline]
+    pub fn count<G: Generate, L: Legality>(position: &Position) -> usize {
+        let mut counter = MoveCounter(0);
+        InnerMoveGen::<MoveCounter>::generate::<G, L>(position, &mut counter);
+        counter.0
+    }
+
     /// Determine whether the passed move is a valid pseudolegal move in the given position. This
     /// means that the move may leave the king in check. Use this to determine if a move retrieved
     /// from transposition table or killer tables etc. are actually valid for the position.
@@ -102,6 +124,29 @@ impl MoveList for DummyMoveList {
     fn clear(&mut self) {}
 }
 
+/// A `MoveList` which only counts the moves pushed to it.
+#[derive(Debug)]
+struct MoveCounter(usize);
+impl MoveList for MoveCounter {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0
+    }
+
+    #[inline(always)]
+    fn push(&mut self, _mv: Move) {
+        self.0 += 1;
+    }
+
+    fn empty() -> Self {
+        Self(0)
+    }
+
+    fn clear(&mut self) {
+        self.0 = 0;
+    }
+}
+
 impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
     /// Determine whether the passed move is a valid pseudolegal move in the given position.
     #[inline]
@@ -209,6 +254,80 @@ impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
         }
     }
 
+    /// Generate the moves of the piece on `orig` only.
+    #[inline(always)]
+    fn generate_from<G: Generate, L: Legality>(
+        position: &'a Position,
+        orig: Square,
+        movelist: &'a mut MP,
+    ) -> &'a mut MP {
+        match position.turn() {
+            Player::WHITE => {
+                InnerMoveGen::<MP>::generate_from_helper::<G, L, White>(position, orig, movelist)
+            }
+            Player::BLACK => {
+                InnerMoveGen::<MP>::generate_from_helper::<G, L, Black>(position, orig, movelist)
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn generate_from_helper<G: Generate, L: Legality, PL: Side>(
+        position: &'a Position,
+        orig: Square,
+        movelist: &'a mut MP,
+    ) -> &'a mut MP {
+        let mut movegen = InnerMoveGen::<MP>::get_self::<PL>(position, movelist);
+        let piece = position.piece_at_sq(orig);
+        debug_assert_eq!(piece.player(), PL::player());
+
+        if movegen.position.in_check() {
+            let target_sqs = movegen.evasion_target_sqs::<G>();
+
+            if piece.type_of() == PieceType::King {
+                movegen.generate_king_evasions::<G, PL, L>(target_sqs);
+            } else if let Some(target) = movegen.block_or_capture_target::<PL>(target_sqs) {
+                movegen.moves_for_piece::<G, PL, L>(piece.type_of(), orig, target);
+            }
+
+            return movegen.movelist;
+        }
+
+        let target = if G::kind() == Generation::Captures {
+            movegen.them_occ
+        } else {
+            Bitboard::ALL
+        };
+        movegen.moves_for_piece::<G, PL, L>(piece.type_of(), orig, target);
+
+        if piece.type_of() == PieceType::King
+            && (G::kind() == Generation::All || G::kind() == Generation::Quiets)
+        {
+            movegen.generate_castling::<PL, L>();
+        }
+
+        movegen.movelist
+    }
+
+    /// Generate the moves of a piece of the given type on `orig`, to squares in `target`.
+    #[inline(always)]
+    fn moves_for_piece<G: Generate, PL: Side, L: Legality>(
+        &mut self,
+        piece: PieceType,
+        orig: Square,
+        target: Bitboard,
+    ) {
+        match piece {
+            PieceType::None => {}
+            PieceType::Pawn => self.generate_pawn_moves_from::<G, PL, L>(orig.to_bb(), target),
+            PieceType::Knight => self.moves_from_square::<G, Knight, L>(orig, target),
+            PieceType::Bishop => self.moves_from_square::<G, Bishop, L>(orig, target),
+            PieceType::Rook => self.moves_from_square::<G, Rook, L>(orig, target),
+            PieceType::Queen => self.moves_from_square::<G, Queen, L>(orig, target),
+            PieceType::King => self.moves_from_square::<G, King, L>(orig, target),
+        }
+    }
+
     #[inline(always)]
     fn generate_helper<G: Generate, L: Legality, PL: Side>(
         position: &'a Position,
@@ -290,13 +409,39 @@ impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
     fn generate_evasions<G: Generate, P: Side, L: Legality>(&mut self) {
         debug_assert!(self.position.in_check());
 
-        let target_sqs = if G::kind() == Generation::Captures {
+        let target_sqs = self.evasion_target_sqs::<G>();
+        self.generate_king_evasions::<G, P, L>(target_sqs);
+
+        // If there is only one checking square, we can block or capture the piece
+        if let Some(target) = self.block_or_capture_target::<P>(target_sqs) {
+            self.generate_pawn_moves::<G, P, L>(target);
+
+            if G::kind() != Generation::Promotions && G::kind() != Generation::QueenPromotions {
+                self.moves_per_piece::<G, P, Knight, L>(target);
+                self.moves_per_piece::<G, P, Bishop, L>(target);
+                self.moves_per_piece::<G, P, Rook, L>(target);
+                self.moves_per_piece::<G, P, Queen, L>(target);
+            }
+        }
+    }
+
+    /// The squares evasions may move to for the given generation type, before accounting for the
+    /// position of the checking pieces.
+    #[inline(always)]
+    fn evasion_target_sqs<G: Generate>(&self) -> Bitboard {
+        if G::kind() == Generation::Captures {
             self.them_occ
         } else if G::kind() == Generation::Quiets {
             !self.them_occ
         } else {
             Bitboard::ALL
-        };
+        }
+    }
+
+    /// Generate the moves of the king out of check.
+    #[inline(always)]
+    fn generate_king_evasions<G: Generate, P: Side, L: Legality>(&mut self, target_sqs: Bitboard) {
+        debug_assert!(self.position.in_check());
 
         let ksq = self.position.king_sq(P::player());
 
@@ -329,23 +474,21 @@ impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
                 self.move_append_from_bb_flag::<L>(&mut non_captures_bb, ksq, MoveType::QUIET);
             }
         }
+    }
 
-        // If there is only one checking square, we can block or capture the piece
-        if !(self.position.checkers().more_than_one()) {
-            let checking_sq = Square(self.position.checkers().bsf() as u8);
+    /// The squares a piece other than the king can move to in order to block or capture the
+    /// checking piece. Returns `None` in double check, when only the king can move.
+    #[inline(always)]
+    fn block_or_capture_target<P: Side>(&self, target_sqs: Bitboard) -> Option<Bitboard> {
+        if self.position.checkers().more_than_one() {
+            return None;
+        }
 
-            // Squares that allow a block or captures of the sliding piece
-            let target =
-                target_sqs & (Bitboard(between_bb(checking_sq, ksq)) | checking_sq.to_bb());
-            self.generate_pawn_moves::<G, P, L>(target);
+        let ksq = self.position.king_sq(P::player());
+        let checking_sq = Square(self.position.checkers().bsf() as u8);
 
-            if G::kind() != Generation::Promotions && G::kind() != Generation::QueenPromotions {
-                self.moves_per_piece::<G, P, Knight, L>(target);
-                self.moves_per_piece::<G, P, Bishop, L>(target);
-                self.moves_per_piece::<G, P, Rook, L>(target);
-                self.moves_per_piece::<G, P, Queen, L>(target);
-            }
-        }
+        // Squares that allow a block or captures of the sliding piece
+        Some(target_sqs & (Bitboard(between_bb(checking_sq, ksq)) | checking_sq.to_bb()))
     }
 
     #[inline(always)]
@@ -423,17 +566,28 @@ impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
     ) {
         let piece_bb: Bitboard = self.position.piece_bb(PL::player(), P::kind());
         for orig in piece_bb {
-            let moves_bb: Bitboard = self.moves_bb::<P>(orig) & !self.us_occ & target;
+            self.moves_from_square::<G, P, L>(orig, target);
+        }
+    }
 
-            if G::kind() == Generation::All || G::kind() == Generation::Captures {
-                let mut captures_bb: Bitboard = moves_bb & self.them_occ;
-                self.move_append_from_bb_flag::<L>(&mut captures_bb, orig, MoveType::CAPTURE);
-            }
+    /// Generate the moves for the single `Knight`, `King`, `Rook`, `Bishop` or `Queen` on `orig`.
+    /// See `moves_per_piece`.
+    #[inline(always)]
+    fn moves_from_square<G: Generate, P: PieceTrait, L: Legality>(
+        &mut self,
+        orig: Square,
+        target: Bitboard,
+    ) {
+        let moves_bb: Bitboard = self.moves_bb::<P>(orig) & !self.us_occ & target;
 
-            if G::kind() == Generation::All || G::kind() == Generation::Quiets {
-                let mut non_captures_bb: Bitboard = moves_bb & !self.them_occ;
-                self.move_append_from_bb_flag::<L>(&mut non_captures_bb, orig, MoveType::QUIET);
-            }
+        if G::kind() == Generation::All || G::kind() == Generation::Captures {
+            let mut captures_bb: Bitboard = moves_bb & self.them_occ;
+            self.move_append_from_bb_flag::<L>(&mut captures_bb, orig, MoveType::CAPTURE);
+        }
+
+        if G::kind() == Generation::All || G::kind() == Generation::Quiets {
+            let mut non_captures_bb: Bitboard = moves_bb & !self.them_occ;
+            self.move_append_from_bb_flag::<L>(&mut non_captures_bb, orig, MoveType::QUIET);
         }
     }
 
@@ -462,13 +616,23 @@ impl<'a, MP: MoveList> InnerMoveGen<'a, MP> {
 
     #[inline(always)]
     fn generate_pawn_moves<G: Generate, PL: Side, L: Legality>(&mut self, target: Bitboard) {
+        self.generate_pawn_moves_from::<G, PL, L>(Bitboard::ALL, target);
+    }
+
+    /// Generate the moves of the pawns on the squares in `from`, to squares in `target`.
+    #[inline(always)]
+    fn generate_pawn_moves_from<G: Generate, PL: Side, L: Legality>(
+        &mut self,
+        from: Bitboard,
+        target: Bitboard,
+    ) {
         let (rank_7, rank_3): (Bitboard, Bitboard) = if PL::player() == Player::WHITE {
             (Bitboard::RANK_7, Bitboard::RANK_3)
         } else {
             (Bitboard::RANK_2, Bitboard::RANK_6)
         };
 
-        let all_pawns = self.position.piece_bb(PL::player(), PieceType::Pawn);
+        let all_pawns = self.position.piece_bb(PL::player(), PieceType::Pawn) & from;
 
         // Separated out for promotion moves
         let pawns_rank_7: Bitboard = all_pawns & rank_7;
@@ -816,6 +980,98 @@ pub fn queen_moves(occupied: Bitboard, sq: Square) -> Bitboard {
     Bitboard(magic::rook_attacks(occupied.0, sq.0) | magic::bishop_attacks(occupied.0, sq.0))
 }
 
+/// The most moves a single piece can have: a queen in the centre of an empty board.
+const MAX_PIECE_MOVES: usize = 27;
+
+/// A `MoveList` holding the moves of a single piece, used as the buffer for `MoveIter`.
+#[derive(Debug)]
+struct PieceMoves {
+    moves: [Move; MAX_PIECE_MOVES],
+    len: usize,
+}
+
+impl MoveList for PieceMoves {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    fn push(&mut self, mv: Move) {
+        debug_assert!(self.len < MAX_PIECE_MOVES);
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    fn empty() -> Self {
+        Self {
+            moves: [Move::null(); MAX_PIECE_MOVES],
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// A lazy iterator over the legal moves of a position, created by `Position::legal_moves`.
+///
+/// Moves are generated one piece at a time into a small buffer on the stack, so nothing is
+/// allocated and callers which stop early (e.g. to find out whether there is any legal move at
+/// all) skip the work for the remaining pieces. Moves are not yielded in the same order as
+/// `MoveGen::generate`.
+#[derive(Debug)]
+pub struct MoveIter<'a, G: Generate> {
+    position: &'a Position,
+    /// Squares of the pieces whose moves are still to be generated.
+    pieces: Bitboard,
+    buffer: PieceMoves,
+    cursor: usize,
+    _generate: PhantomData<G>,
+}
+
+impl<'a, G: Generate> MoveIter<'a, G> {
+    pub fn new(position: &'a Position) -> Self {
+        let us = position.turn();
+        let pieces = if position.checkers().more_than_one() {
+            // Only the king can move in double check.
+            position.king_sq(us).to_bb()
+        } else if G::kind() == Generation::Promotions || G::kind() == Generation::QueenPromotions {
+            position.piece_bb(us, PieceType::Pawn)
+        } else {
+            position.get_occupied_player_runtime(us)
+        };
+
+        Self {
+            position,
+            pieces,
+            buffer: PieceMoves::empty(),
+            cursor: 0,
+            _generate: PhantomData,
+        }
+    }
+}
+
+impl<'a, G: Generate> Iterator for MoveIter<'a, G> {
+    type Item = Move;
+
+    #[inline]
+    fn next(&mut self) -> Option<Move> {
+        while self.cursor == self.buffer.len() {
+            let orig = self.pieces.next()?;
+            self.buffer.clear();
+            self.cursor = 0;
+            MoveGen::generate_from::<_, G, Legal>(self.position, orig, &mut self.buffer);
+        }
+
+        let mov = self.buffer.moves[self.cursor];
+        self.cursor += 1;
+        Some(mov)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -916,4 +1172,105 @@ mod tests {
         // of the capture phase.
         assert_eq!(res, 4_224_543);
     }
+
+    /// Check that the lazy iterator yields exactly the moves of `generate` for every generation
+    /// type, in every position of the tree below `pos` to the given depth.
+    fn compare_lazy(pos: &mut Position, depth: usize) {
+        fn same<G: Generate>(pos: &Position) {
+            let mut eager = pos.generate::<BasicMoveList, G, Legal>().to_vec();
+            let mut lazy = pos.legal_moves::<G>().collect::<Vec<_>>();
+            eager.sort_by_key(|m| m.to_uci_string());
+            lazy.sort_by_key(|m| m.to_uci_string());
+            assert_eq!(
+                eager,
+                lazy,
+                "{:?} moves differ in {}",
+                G::kind(),
+                pos.to_fen()
+            );
+        }
+
+        same::<All>(pos);
+        same::<Captures>(pos);
+        same::<Quiets>(pos);
+        same::<Promotions>(pos);
+        same::<QueenPromotions>(pos);
+        assert_eq!(pos.count_legal_moves(), pos.legal_moves::<All>().count());
+
+        if depth == 0 {
+            return;
+        }
+
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            pos.make_move(mov);
+            compare_lazy(pos, depth - 1);
+            pos.unmake_move();
+        }
+    }
+
+    #[test]
+    fn lazy_moves_match_generate() {
+        init_globals();
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            compare_lazy(&mut pos, 2);
+        }
+    }
+
+    /// Check that `gives_check` agrees with making each move, in every position of the tree below
+    /// `pos` to the given depth.
+    fn compare_gives_check(pos: &mut Position, depth: usize) {
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            let gives_check = pos.gives_check(mov);
+            pos.make_move(mov);
+            assert_eq!(gives_check, pos.in_check(), "{} in {}", mov, pos.to_fen());
+            if depth > 0 {
+                compare_gives_check(pos, depth - 1);
+            }
+            pos.unmake_move();
+        }
+    }
+
+    #[test]
+    fn gives_check_matches_make_move() {
+        init_globals();
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/8/8/k1pP3Q/8/8/8/7K w - c6 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            compare_gives_check(&mut pos, 2);
+        }
+    }
+
+    #[test]
+    fn has_legal_move() {
+        init_globals();
+
+        let mated =
+            Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+                .unwrap();
+        assert!(!mated.has_legal_move());
+        assert!(mated.in_checkmate());
+
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!stalemate.has_legal_move());
+        assert!(!stalemate.in_checkmate());
+        assert_eq!(stalemate.count_legal_moves(), 0);
+
+        let double_check = Position::from_fen("4k3/8/8/8/8/8/4r3/r3K3 w - - 0 1").unwrap();
+        assert!(double_check.has_legal_move());
+        assert_eq!(double_check.count_legal_moves(), 1);
+    }
 }
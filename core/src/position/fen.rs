@@ -1,6 +1,10 @@
-use super::{Board, CastlingRights, Piece, Player, Position, Square, State, Zobrist};
+use super::{
+    Board, CastlingRights, Piece, Player, Position, Square, State, Variant, Zobrist,
+    THREE_CHECK_LIMIT,
+};
 
 use crate::bb::Bitboard;
+use crate::masks::PLAYER_CNT;
 
 pub const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
@@ -30,6 +34,7 @@ pub enum FenErrorType {
     PiecePositionsInvalidNumber,
     PiecePositionsRowTooLong,
     PiecePositionsRowTooShort,
+    ChecksFieldInvalid,
 }
 
 impl std::fmt::Display for FenErrorType {
@@ -61,14 +66,25 @@ impl std::fmt::Display for FenErrorType {
             FenErrorType::PiecePositionsRowTooShort => {
                 write!(f, "piece positions row is too short")
             }
+            FenErrorType::ChecksFieldInvalid => write!(f, "remaining checks field invalid"),
         }
     }
 }
 
 impl Position {
     pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Self::from_fen_variant(fen, Variant::Standard)
+    }
+
+    /// Parse a fen string into a `Position` played under the rules of the given variant.
+    ///
+    /// For Three-check, the fen may include an extra field after the en passant square giving the
+    /// number of checks each player still needs to give, e.g. `3+3`. If omitted, no checks have
+    /// been given.
+    pub fn from_fen_variant(fen: &str, variant: Variant) -> Result<Self, FenError> {
+        let (fen, checks_given) = Self::split_checks_field(fen, variant)?;
         let [piece_positions, side_to_move, castling_rights, ep_square, half_move_clock, move_number] =
-            Self::split_fen_fields(fen)?;
+            Self::split_fen_fields(&fen)?;
 
         let (bbs, player_occ, board) = Self::parse_piece_position_string(piece_positions)?;
         let turn = Self::parse_side_to_move(side_to_move)?;
@@ -84,11 +100,15 @@ impl Position {
             ep_square,
             half_move_clock,
             move_number,
+            variant,
+            checks_given,
             bbs,
             player_occ,
             state: State::blank(), // Temporary. The real `State` is generated below.
             history: Vec::new(),
             zobrist: Zobrist(0),
+            pawn_key: Zobrist(0),
+            material_key: Zobrist(0),
         };
 
         pos.set_state();
@@ -101,6 +121,37 @@ impl Position {
         Self::from_fen(START_POSITION).unwrap()
     }
 
+    /// Removes the Three-check remaining checks field from a fen string, if present, returning the
+    /// remaining fen and the number of checks given so far by each player.
+    fn split_checks_field(
+        fen: &str,
+        variant: Variant,
+    ) -> Result<(String, [u8; PLAYER_CNT]), FenError> {
+        let mut fields: Vec<&str> = fen.split(' ').collect();
+        if variant != Variant::ThreeCheck || fields.len() != 7 {
+            return Ok((fen.to_string(), [0; PLAYER_CNT]));
+        }
+
+        let checks = fields.remove(4);
+        let remaining = checks
+            .split_once('+')
+            .and_then(|(w, b)| Some((w.parse::<u8>().ok()?, b.parse::<u8>().ok()?)));
+
+        match remaining {
+            Some((w, b)) if w <= THREE_CHECK_LIMIT && b <= THREE_CHECK_LIMIT => Ok((
+                fields.join(" "),
+                [THREE_CHECK_LIMIT - w, THREE_CHECK_LIMIT - b],
+            )),
+            _ => Err(FenError {
+                ty: FenErrorType::ChecksFieldInvalid,
+                msg: format!(
+                    "`{}` is not a valid remaining checks field; expected e.g. `3+3`",
+                    checks
+                ),
+            }),
+        }
+    }
+
     pub fn split_fen_fields(fen: &str) -> Result<[&str; 6], FenError> {
         let fields: Vec<&str> = fen.split(' ').collect();
         if fields.len() != 6 {
@@ -548,6 +599,15 @@ impl Position {
         }
         s.push(' ');
 
+        // Three-check only: remaining checks
+        if self.variant == Variant::ThreeCheck {
+            s.push_str(&format!(
+                "{}+{} ",
+                THREE_CHECK_LIMIT.saturating_sub(self.checks_given(Player::WHITE)),
+                THREE_CHECK_LIMIT.saturating_sub(self.checks_given(Player::BLACK))
+            ));
+        }
+
         // 5. Halfmove clock
         s.push_str(&format!("{}", self.half_move_clock));
         s.push(' ');
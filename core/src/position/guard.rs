@@ -0,0 +1,170 @@
+//! Scoped make/unmake of moves.
+//!
+//! `Position::make_move` must always be paired with a matching `Position::unmake_move`, and an
+//! early return or `?` in between leaves the position corrupted. `Position::play` returns a
+//! `MoveGuard` which unmakes the move when it goes out of scope, so the pairing is enforced by the
+//! compiler.
+//!
+//! For hot recursive code, such as search, where the position is borrowed through `&mut self` and
+//! a guard can't be held across the recursive call, `BalanceCheck` verifies in debug builds that
+//! a section of code leaves the position exactly as it found it.
+
+use super::Position;
+#[cfg(debug_assertions)]
+use super::Zobrist;
+use crate::mov::Move;
+
+use std::ops::{Deref, DerefMut};
+
+/// A move played on a `Position`, which is unmade when the guard is dropped.
+///
+/// Derefs to the `Position`, so it can be used to inspect the position after the move or to play
+/// further moves.
+pub struct MoveGuard<'a> {
+    pos: &'a mut Position,
+    /// The history length immediately after the move was made.
+    #[cfg(debug_assertions)]
+    depth: usize,
+}
+
+impl<'a> Deref for MoveGuard<'a> {
+    type Target = Position;
+
+    fn deref(&self) -> &Position {
+        self.pos
+    }
+}
+
+impl<'a> DerefMut for MoveGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Position {
+        self.pos
+    }
+}
+
+impl<'a> Drop for MoveGuard<'a> {
+    fn drop(&mut self) {
+        // Anything made through the guard must have been unmade before we unmake our own move.
+        #[cfg(debug_assertions)]
+        debug_assert_eq!(
+            self.pos.history.len(),
+            self.depth,
+            "unbalanced make/unmake while a `MoveGuard` was held"
+        );
+
+        self.pos.unmake_move();
+    }
+}
+
+impl Position {
+    /// Make a move on the board, returning a guard which unmakes it when dropped.
+    ///
+    /// The supplied `Move` must be legal in the current position, as for `make_move`.
+    pub fn play(&mut self, mov: &Move) -> MoveGuard<'_> {
+        self.make_move(mov);
+
+        MoveGuard {
+            #[cfg(debug_assertions)]
+            depth: self.history.len(),
+            pos: self,
+        }
+    }
+}
+
+/// Checks that make and unmake calls are balanced over a section of code.
+///
+/// Records the length of the move history and the Zobrist key of a position when created, and
+/// `verify` asserts that both have been restored. Only active in debug builds; in release builds
+/// this does nothing.
+#[derive(Debug)]
+pub struct BalanceCheck {
+    #[cfg(debug_assertions)]
+    depth: usize,
+    #[cfg(debug_assertions)]
+    zobrist: Zobrist,
+}
+
+impl BalanceCheck {
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn new(pos: &Position) -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            depth: pos.history.len(),
+            #[cfg(debug_assertions)]
+            zobrist: pos.zobrist(),
+        }
+    }
+
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub fn verify(&self, pos: &Position) {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert_eq!(
+                pos.history.len(),
+                self.depth,
+                "unbalanced make/unmake: history length changed"
+            );
+            debug_assert_eq!(
+                pos.zobrist(),
+                self.zobrist,
+                "unbalanced make/unmake: zobrist key changed"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::init_globals;
+    use crate::mono_traits::{All, Legal};
+    use crate::movelist::BasicMoveList;
+
+    #[test]
+    fn guard_unmakes_on_drop() {
+        init_globals();
+
+        let mut pos = Position::start_pos();
+        let start = pos.clone();
+        let check = BalanceCheck::new(&pos);
+
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            let mut after = pos.play(mov);
+            assert_ne!(after.zobrist(), start.zobrist());
+
+            for reply in &after.generate::<BasicMoveList, All, Legal>() {
+                let _ = after.play(reply);
+            }
+        }
+
+        check.verify(&pos);
+        assert_eq!(pos, start);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced make/unmake")]
+    fn guard_detects_imbalance() {
+        init_globals();
+
+        let mut pos = Position::start_pos();
+        let first = pos.generate::<BasicMoveList, All, Legal>()[0];
+        let mut after = pos.play(&first);
+        let reply = after.generate::<BasicMoveList, All, Legal>()[0];
+        after.make_move(&reply);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced make/unmake")]
+    fn balance_check_detects_imbalance() {
+        init_globals();
+
+        let mut pos = Position::start_pos();
+        let check = BalanceCheck::new(&pos);
+        let mov = pos.generate::<BasicMoveList, All, Legal>()[0];
+        pos.make_move(&mov);
+        check.verify(&pos);
+    }
+}
@@ -1,26 +1,32 @@
 mod board;
 mod castling;
 mod fen;
+mod guard;
 mod notation;
 mod piece;
 mod square;
 mod state;
+mod unmove;
+mod variant;
 mod zobrist;
 
 use crate::bb::Bitboard;
 use crate::masks::{CASTLING_PATH, CASTLING_ROOK_START, FILE_BB, PLAYER_CNT, RANK_BB};
 use crate::mono_traits::{All, Generate, Legal, Legality, Side};
 use crate::mov::{Move, MoveType, UndoableMove};
-use crate::movegen::{bishop_moves, queen_moves, rook_moves, MoveGen};
+use crate::movegen::{bishop_moves, queen_moves, rook_moves, MoveGen, MoveIter};
 use crate::movelist::{BasicMoveList, Frame, MoveList, MoveStack};
 use crate::precalc::boards::{aligned, between_bb, king_moves, knight_moves, pawn_attacks_from};
 
 pub use board::Board;
 pub use castling::{CastleType, CastlingRights};
 pub use fen::{FenError, START_POSITION};
+pub use guard::{BalanceCheck, MoveGuard};
 pub use piece::{Piece, PieceType, PIECE_TYPES, PROMO_PIECES};
 pub use square::Square;
 pub use state::State;
+pub use unmove::Unmove;
+pub use variant::{Variant, HILL, THREE_CHECK_LIMIT};
 pub use zobrist::Zobrist;
 
 use std::fmt;
@@ -130,6 +136,11 @@ pub struct Position {
     /// Full move number of the current position in the game being played.
     pub(crate) move_number: u32,
 
+    /// The variant whose rules this position is played under.
+    pub(crate) variant: Variant,
+    /// The number of checks given by each player so far. Only tracked in Three-check.
+    pub(crate) checks_given: [u8; PLAYER_CNT],
+
     /// `State` struct stores other useful information for fast access
     // TODO: Pleco wraps this in an Arc for quick copying of states without
     // copying memory. Do we need that?
@@ -144,6 +155,12 @@ pub struct Position {
     /// The Zobrist key of the current position. Incrementally updated in `makemove()`
     /// and `unmakemove()`.
     pub(crate) zobrist: Zobrist,
+    /// A Zobrist key of the pawns alone. Incrementally updated whenever a pawn is placed,
+    /// removed or moved.
+    pub(crate) pawn_key: Zobrist,
+    /// A key of the number of pieces of each type and colour, ignoring where they are.
+    /// Incrementally updated whenever a piece is placed or removed.
+    pub(crate) material_key: Zobrist,
 }
 
 impl Position {
@@ -159,10 +176,35 @@ impl Position {
             ep_square: None,
             half_move_clock: 0,
             move_number: 1,
+            variant: Variant::Standard,
+            checks_given: [0; PLAYER_CNT],
             state: State::blank(),
             history: Vec::with_capacity(16),
             zobrist: Zobrist::empty(),
+            pawn_key: Zobrist::empty(),
+            material_key: Zobrist::empty(),
+        }
+    }
+
+    /// Creates a `Position` with `pieces` on the board and `turn` to move, without castling
+    /// rights and with the given en passant square. The position isn't checked for legality.
+    pub fn from_pieces(
+        pieces: &[(Piece, Square)],
+        turn: Player,
+        ep_square: Option<Square>,
+    ) -> Self {
+        let mut pos = Self::blank();
+        pos.bbs[Piece::None as usize] = Bitboard::ALL;
+
+        for &(piece, sq) in pieces {
+            pos.put_piece_c(piece, sq);
         }
+        pos.turn = turn;
+        pos.ep_square = ep_square;
+
+        pos.set_state();
+        pos.set_zobrist();
+        pos
     }
 
     /// Pretty print the board to stdout.
@@ -176,11 +218,13 @@ impl Position {
         self.state = State::from_position(&self);
     }
 
-    /// Set the `Zobrist` key for the current position based on the other data in
+    /// Set the `Zobrist` keys for the current position based on the other data in
     /// the `Position` struct. Should only be called when initialising a new `Position`
-    /// as the zobrist key is kept incrementally updated thereafter.
+    /// as the zobrist keys are kept incrementally updated thereafter.
     pub fn set_zobrist(&mut self) {
         self.zobrist = Zobrist::from_position(&self);
+        self.pawn_key = Zobrist::pawns_from_position(self);
+        self.material_key = Zobrist::material_from_position(self);
     }
 
     pub fn history(&self) -> &Vec<UndoableMove> {
@@ -196,6 +240,26 @@ impl Position {
         string
     }
 
+    /// The variant whose rules this position is played under.
+    #[inline(always)]
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Set the variant whose rules this position is played under. Any checks counted so far are
+    /// discarded.
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.checks_given = [0; PLAYER_CNT];
+        self.set_zobrist();
+    }
+
+    /// The number of checks given by `player` so far. Always zero outside of Three-check.
+    #[inline(always)]
+    pub fn checks_given(&self, player: Player) -> u8 {
+        self.checks_given[player.inner() as usize]
+    }
+
     pub fn half_move_clock(&self) -> u32 {
         self.half_move_clock
     }
@@ -204,10 +268,25 @@ impl Position {
         self.zobrist
     }
 
+    /// The Zobrist key of the pawns of both players. Positions with the same pawn structure have
+    /// the same key, whatever the other pieces are doing.
+    #[inline(always)]
+    pub fn pawn_key(&self) -> Zobrist {
+        self.pawn_key
+    }
+
+    /// The key of the material on the board: positions with the same number of pieces of each
+    /// type and colour have the same key.
+    #[inline(always)]
+    pub fn material_key(&self) -> Zobrist {
+        self.material_key
+    }
+
     /// Make a move on the Board and update the `Position`.
     ///
     /// The supplied `Move` must be legal in the current position, otherwise undefined behaviour
-    /// will occur.
+    /// will occur. Every call must be paired with a call to `unmake_move`; prefer `play` where the
+    /// borrow allows it, which does this automatically.
     pub fn make_move(&mut self, mov: &Move) {
         // In debug mode, check the move isn't somehow null
         debug_assert_ne!(mov.orig(), mov.dest());
@@ -316,6 +395,48 @@ impl Position {
         // Update "invisible" state
         self.turn = them;
         self.state = State::from_position(&self);
+
+        // In Three-check, count the check we have just given (if any)
+        if self.variant == Variant::ThreeCheck && self.in_check() {
+            let given = self.checks_given[us.inner() as usize];
+            self.zobrist.update_checks_given(us, given, given + 1);
+            self.checks_given[us.inner() as usize] = given + 1;
+        }
+    }
+
+    /// Pass the turn to the opponent without moving, as used by null-move pruning in search. The
+    /// side to move must not be in check.
+    ///
+    /// The null move is recorded in the history like any other move, and is undone with
+    /// `unmake_move`.
+    pub fn make_null_move(&mut self) {
+        debug_assert!(!self.in_check());
+
+        self.history.push(UndoableMove {
+            orig: Square(64),
+            dest: Square(64),
+            piece: Piece::None,
+            promo_piece_type: None,
+            captured: PieceType::None,
+            ty: MoveType::NULL,
+            prev_castling_rights: self.castling_rights,
+            prev_ep_square: self.ep_square,
+            prev_half_move_clock: self.half_move_clock,
+            state: self.state,
+            zobrist: self.zobrist,
+        });
+
+        self.zobrist.update_ep_square(self.ep_square, None);
+        self.ep_square = None;
+
+        self.half_move_clock += 1;
+        if self.turn == Player::BLACK {
+            self.move_number += 1;
+        }
+
+        self.zobrist.toggle_side_to_move();
+        self.turn = !self.turn;
+        self.state = State::from_position(self);
     }
 
     /// Unmake the most recent move, returning the `Position` to the previous state.
@@ -323,6 +444,19 @@ impl Position {
         if let Some(undoable_move) = self.history.pop() {
             self.turn = !self.turn();
             let us = self.turn();
+
+            if undoable_move.is_null() {
+                self.zobrist = undoable_move.zobrist;
+                self.half_move_clock = undoable_move.prev_half_move_clock;
+                self.ep_square = undoable_move.prev_ep_square;
+                self.state = undoable_move.state;
+                if us == Player::BLACK {
+                    self.move_number -= 1;
+                }
+
+                return Some(undoable_move);
+            }
+
             let orig = undoable_move.orig;
             let dest = undoable_move.dest;
             let mut piece_on = self.piece_at_sq(dest);
@@ -358,6 +492,11 @@ impl Position {
             self.half_move_clock = undoable_move.prev_half_move_clock;
             self.ep_square = undoable_move.prev_ep_square;
             self.castling_rights = undoable_move.prev_castling_rights;
+
+            // The current `State` is still the one after the move, so tells us if it gave check
+            if self.variant == Variant::ThreeCheck && self.state.checkers.is_not_empty() {
+                self.checks_given[us.inner() as usize] -= 1;
+            }
             self.state = undoable_move.state;
 
             if us == Player::BLACK {
@@ -471,6 +610,10 @@ impl Position {
 
         self.zobrist.toggle_piece_sq(piece, from);
         self.zobrist.toggle_piece_sq(piece, to);
+        if piece_ty == PieceType::Pawn {
+            self.pawn_key.toggle_piece_sq(piece, from);
+            self.pawn_key.toggle_piece_sq(piece, to);
+        }
     }
 
     /// Removes a `Piece` from the board for a given player.
@@ -491,6 +634,11 @@ impl Position {
         self.board.remove(square);
 
         self.zobrist.toggle_piece_sq(piece, square);
+        if piece.type_of() == PieceType::Pawn {
+            self.pawn_key.toggle_piece_sq(piece, square);
+        }
+        self.material_key
+            .toggle_piece_count(piece, self.bbs[piece as usize].popcnt());
     }
 
     /// Places a `Piece` on the board at a given `Square`.
@@ -510,9 +658,24 @@ impl Position {
         self.board.place(square, player, piece_ty);
 
         self.zobrist.toggle_piece_sq(piece, square);
+        if piece_ty == PieceType::Pawn {
+            self.pawn_key.toggle_piece_sq(piece, square);
+        }
+        self.material_key
+            .toggle_piece_count(piece, self.bbs[piece as usize].popcnt() - 1);
     }
 
     // CHECKING
+    /// Whether `player` has any pieces other than pawns and the king. Without them, zugzwang is
+    /// common, so passing the turn is a poor guide to the value of the position.
+    #[inline]
+    pub fn has_non_pawn_material(&self, player: Player) -> bool {
+        (self.get_occupied_player_runtime(player)
+            & !self.piece_bb(player, PieceType::Pawn)
+            & !self.piece_bb(player, PieceType::King))
+        .is_not_empty()
+    }
+
     /// Returns if current side to move is in check.
     #[inline(always)]
     pub fn in_check(&self) -> bool {
@@ -534,7 +697,23 @@ impl Position {
     /// TODO: this should really switch `Generation` type to `Evasions`? Need to thoroughly check
     /// correctness when making that change.
     pub fn in_checkmate(&self) -> bool {
-        self.in_check() && self.generate::<BasicMoveList, All, Legal>().is_empty()
+        self.in_check() && !self.has_legal_move()
+    }
+
+    /// Whether the player to move has already lost under the additional rules of this position's
+    /// variant, i.e. their opponent has given a third check in Three-check or has moved their king
+    /// onto the hill in King of the Hill. Always false in standard chess.
+    ///
+    /// Movegen does not know about these rules, so search routines must call this alongside their
+    /// checkmate and draw detection.
+    #[inline]
+    pub fn variant_loss(&self) -> bool {
+        let them = !self.turn();
+        match self.variant {
+            Variant::Standard => false,
+            Variant::ThreeCheck => self.checks_given(them) >= THREE_CHECK_LIMIT,
+            Variant::KingOfTheHill => (self.piece_bb(them, PieceType::King) & HILL).is_not_empty(),
+        }
     }
 
     pub fn in_double_check(&self) -> bool {
@@ -561,12 +740,17 @@ impl Position {
         let curr_zob = self.zobrist();
         let mut c = 0;
 
-        for m in self.history().iter().rev().skip(1).step_by(2) {
-            if m.ty.contains(MoveType::CAPTURE) || m.piece.type_of() == PieceType::Pawn {
+        // Positions before an irreversible move can't repeat, and we don't count positions
+        // before a null move.
+        for (i, m) in self.history().iter().rev().enumerate() {
+            if m.ty.intersects(MoveType::CAPTURE | MoveType::NULL)
+                || m.piece.type_of() == PieceType::Pawn
+            {
                 return false;
             }
 
-            if m.zobrist == curr_zob {
+            // Only positions with the same side to move can repeat.
+            if i % 2 == 1 && m.zobrist == curr_zob {
                 c += 1;
 
                 if c == 2 {
@@ -815,6 +999,26 @@ impl Position {
         MoveGen::generate_in::<ML, G, L>(&self, movelist);
     }
 
+    /// A lazy iterator over the legal moves of the current position of the kind given by `G`.
+    /// Moves are generated a piece at a time as the iterator is advanced.
+    #[inline]
+    pub fn legal_moves<G: Generate>(&self) -> MoveIter<'_, G> {
+        MoveIter::new(self)
+    }
+
+    /// Whether the side to move has at least one legal move. Stops generating as soon as one is
+    /// found.
+    #[inline]
+    pub fn has_legal_move(&self) -> bool {
+        self.legal_moves::<All>().next().is_some()
+    }
+
+    /// The number of legal moves in the current position, counted without storing them.
+    #[inline]
+    pub fn count_legal_moves(&self) -> usize {
+        MoveGen::count::<All, Legal>(self)
+    }
+
     #[inline]
     pub fn random_move(&self) -> Option<Move> {
         self.generate::<BasicMoveList, All, Legal>()
@@ -862,6 +1066,74 @@ impl Position {
             || aligned(mov.orig(), dest, self.king_sq(self.turn()))
     }
 
+    /// Tests if a given legal move puts the opponent in check, without playing it on the board.
+    ///
+    /// Discovered checks are read from the blockers of the enemy king. Castling and en passant
+    /// move or remove a second piece, so the resulting slider attacks are worked out from the new
+    /// occupancy.
+    pub fn gives_check(&self, mov: &Move) -> bool {
+        let us = self.turn();
+        let them = !us;
+        let ksq = self.king_sq(them);
+        let orig = mov.orig();
+        let dest = mov.dest();
+
+        // Castling can only give check with the rook.
+        if mov.is_castle() {
+            let (r_orig, r_dest) = if orig < dest {
+                (
+                    us.relative_square(Square::H1),
+                    us.relative_square(Square::F1),
+                )
+            } else {
+                (
+                    us.relative_square(Square::A1),
+                    us.relative_square(Square::D1),
+                )
+            };
+            let occupied =
+                (self.occupied() ^ orig.to_bb() ^ r_orig.to_bb()) | dest.to_bb() | r_dest.to_bb();
+
+            return (rook_moves(occupied, r_dest) & ksq.to_bb()).is_not_empty();
+        }
+
+        // Discovered check, unless the piece stays on the line between the slider and the king.
+        if (self.state.blockers[them.inner() as usize] & orig.to_bb()).is_not_empty()
+            && !aligned(orig, dest, ksq)
+        {
+            return true;
+        }
+
+        let mut occupied = (self.occupied() ^ orig.to_bb()) | dest.to_bb();
+
+        // The pawn captured en passant can also have been blocking one of our sliders.
+        if mov.is_en_passant() {
+            let captured_sq = Square((dest.0 as i8).wrapping_sub(us.pawn_push()) as u8);
+            occupied ^= captured_sq.to_bb();
+
+            if (rook_moves(occupied, ksq) & self.sliding_piece_bb(us)).is_not_empty()
+                || (bishop_moves(occupied, ksq) & self.diagonal_piece_bb(us)).is_not_empty()
+            {
+                return true;
+            }
+        }
+
+        let piece_type = mov
+            .promo_piece_type()
+            .unwrap_or_else(|| self.piece_at_sq(orig).type_of());
+
+        let attacks = match piece_type {
+            PieceType::Pawn => Bitboard(pawn_attacks_from(dest, us)),
+            PieceType::Knight => knight_moves(dest),
+            PieceType::Bishop => bishop_moves(occupied, dest),
+            PieceType::Rook => rook_moves(occupied, dest),
+            PieceType::Queen => queen_moves(occupied, dest),
+            _ => Bitboard(0),
+        };
+
+        (attacks & ksq.to_bb()).is_not_empty()
+    }
+
     /// Tests if a move applies to the current position. This is useful when checking transposition
     /// table or killer table moves, when the stored result could possibly be completely wrong for
     /// the current position.
@@ -974,6 +1246,8 @@ impl fmt::Debug for Position {
         writeln!(f, "Half move clock: {}", self.half_move_clock)?;
         writeln!(f, "Move number: {}", self.move_number)?;
         writeln!(f, "Zobrist key: {:b}", self.zobrist.0)?;
+        writeln!(f, "Pawn key: {:X}", self.pawn_key)?;
+        writeln!(f, "Material key: {:X}", self.material_key)?;
         writeln!(f)?;
         writeln!(f, "STATE\n=====\n")?;
 
@@ -0,0 +1,466 @@
+//! Reverse move generation, for retrograde analysis.
+//!
+//! An `Unmove` takes back the last move played in a position: it moves a piece of the side which
+//! isn't to move back to a square it could have come from, optionally restoring a piece it
+//! captured, turning it back into a pawn if it promoted, or restoring a pawn it captured en
+//! passant. `Position::generate_unmoves` generates every unmove leading to a legal predecessor,
+//! and `Position::predecessor` builds the position before the move.
+//!
+//! We don't know anything about the game before the current position, so predecessors keep the
+//! castling rights of the current position and only have an en passant square when the unmove
+//! is itself an en passant capture. Castling moves are never taken back. The half-move clock is
+//! stepped back by one for moves which don't reset it, and set to zero for those which do.
+
+use super::{Piece, PieceType, Player, Position, Square, Variant};
+use crate::bb::Bitboard;
+use crate::mov::{Move, MoveType};
+use crate::movegen::{bishop_moves, queen_moves, rook_moves};
+use crate::precalc::boards::{king_moves, knight_moves, pawn_attacks_from};
+
+use std::fmt;
+
+/// A move taken back.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Unmove {
+    /// The move which leads from the predecessor back to the current position.
+    mov: Move,
+    /// The type of the piece restored by taking back a capture, or `PieceType::None`.
+    uncaptured: PieceType,
+}
+
+impl Unmove {
+    fn new(mov: Move, uncaptured: PieceType) -> Self {
+        Self { mov, uncaptured }
+    }
+
+    /// The move which, played in the predecessor, leads back to the current position.
+    #[inline(always)]
+    pub fn mov(&self) -> Move {
+        self.mov
+    }
+
+    /// The type of the piece restored by this unmove, or `PieceType::None` if it doesn't take
+    /// back a capture.
+    #[inline(always)]
+    pub fn uncaptured(&self) -> PieceType {
+        self.uncaptured
+    }
+
+    #[inline(always)]
+    pub fn is_uncapture(&self) -> bool {
+        self.uncaptured != PieceType::None
+    }
+
+    #[inline(always)]
+    pub fn is_unpromotion(&self) -> bool {
+        self.mov.is_promo()
+    }
+}
+
+impl fmt::Display for Unmove {
+    /// Formats as the move taken back, followed by the piece restored if any, e.g. `e4d5xn`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_uncapture() {
+            write!(f, "{}x{:1}", self.mov, self.uncaptured)
+        } else {
+            write!(f, "{}", self.mov)
+        }
+    }
+}
+
+/// The piece types which can be restored by an uncapture. Pawns are handled separately, since
+/// they can't be restored on the first or last rank.
+const UNCAPTURABLE: [PieceType; 4] = [
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+/// The square `n` pawn pushes away from `sq` for `player`, where negative `n` goes backwards.
+fn pushed(sq: Square, player: Player, n: i8) -> Square {
+    Square((sq.0 as i8 + n * player.pawn_push()) as u8)
+}
+
+impl Position {
+    /// Generate every unmove of the current position which leads to a legal predecessor.
+    pub fn generate_unmoves(&self) -> Vec<Unmove> {
+        let mut unmoves = Vec::new();
+        self.generate_unmove_candidates(true, &mut unmoves);
+
+        unmoves.retain(|unmove| self.legal_unmove(unmove));
+        unmoves
+    }
+
+    /// Generate the unmoves of the current position which take back neither a capture nor a
+    /// promotion, and so keep the material the same, which leads to a legal predecessor.
+    pub fn generate_quiet_unmoves(&self) -> Vec<Unmove> {
+        let mut unmoves = Vec::new();
+        self.generate_unmove_candidates(false, &mut unmoves);
+
+        unmoves.retain(|unmove| self.legal_unmove(unmove));
+        unmoves
+    }
+
+    /// The position before the last move was `unmove.mov()`. The unmove must have been generated
+    /// for this position.
+    pub fn predecessor(&self, unmove: &Unmove) -> Position {
+        let them = self.turn();
+        let us = !them;
+        let mov = unmove.mov();
+        let (orig, dest) = (mov.orig(), mov.dest());
+
+        let mut pos = self.clone();
+        pos.history.clear();
+
+        let piece = pos.piece_at_sq(dest);
+        if mov.is_promo() {
+            pos.remove_piece_c(piece, dest);
+            pos.put_piece_c(Piece::make(us, PieceType::Pawn), orig);
+        } else {
+            pos.move_piece_c(piece, dest, orig);
+        }
+
+        if unmove.is_uncapture() {
+            let cap_sq = if mov.is_en_passant() {
+                pushed(dest, us, -1)
+            } else {
+                dest
+            };
+            pos.put_piece_c(Piece::make(them, unmove.uncaptured()), cap_sq);
+        }
+
+        pos.turn = us;
+        pos.ep_square = mov.is_en_passant().then_some(dest);
+
+        if unmove.is_uncapture() || piece.type_of() == PieceType::Pawn || mov.is_promo() {
+            pos.half_move_clock = 0;
+        } else {
+            pos.half_move_clock = self.half_move_clock.saturating_sub(1);
+        }
+        if us == Player::BLACK {
+            pos.move_number = std::cmp::max(1, self.move_number.saturating_sub(1));
+        }
+
+        // In Three-check, a move which gave check was counted.
+        if self.variant == Variant::ThreeCheck && self.in_check() {
+            let given = &mut pos.checks_given[us.inner() as usize];
+            *given = given.saturating_sub(1);
+        }
+
+        pos.set_state();
+        pos.set_zobrist();
+        pos
+    }
+
+    /// Whether the predecessor of `unmove` is a legal position, from which the move could have
+    /// been played.
+    fn legal_unmove(&self, unmove: &Unmove) -> bool {
+        let us = !self.turn();
+
+        if self.variant == Variant::ThreeCheck && self.in_check() && self.checks_given(us) == 0 {
+            return false;
+        }
+
+        // The predecessor is illegal if the side now to move was in check in it. Building it is
+        // expensive, so look at the squares which change instead: the piece moving back and the
+        // line pieces it, or a restored piece, uncovers or blocks.
+        let them = self.turn();
+        let mov = unmove.mov();
+        let (orig, dest) = (mov.orig(), mov.dest());
+        let ksq = self.king_sq(them);
+
+        let mut occupied = (self.occupied() ^ dest.to_bb()) | orig.to_bb();
+        if unmove.is_uncapture() {
+            occupied |= if mov.is_en_passant() {
+                pushed(dest, us, -1).to_bb()
+            } else {
+                dest.to_bb()
+            };
+        }
+
+        let others = self.get_occupied_player_runtime(us) & !dest.to_bb();
+        if (self.attack_defend(occupied, ksq) & others).is_not_empty() {
+            return false;
+        }
+
+        let pt = if mov.is_promo() {
+            PieceType::Pawn
+        } else {
+            self.piece_at_sq(dest).type_of()
+        };
+        let attacks = match pt {
+            PieceType::Pawn => Bitboard(pawn_attacks_from(orig, us)),
+            PieceType::Knight => knight_moves(orig),
+            PieceType::Bishop => bishop_moves(occupied, orig),
+            PieceType::Rook => rook_moves(occupied, orig),
+            PieceType::Queen => queen_moves(occupied, orig),
+            PieceType::King => king_moves(orig),
+            PieceType::None => unreachable!(),
+        };
+
+        (attacks & ksq.to_bb()).is_empty()
+    }
+
+    /// Generate unmoves without checking that the predecessor is legal, including uncaptures and
+    /// unpromotions if `converting` is set.
+    fn generate_unmove_candidates(&self, converting: bool, unmoves: &mut Vec<Unmove>) {
+        let them = self.turn();
+        let us = !them;
+        let occupied = self.occupied();
+        let empty = !occupied;
+
+        // The last move was a double pawn push, and we know which.
+        if let Some(ep) = self.ep_square {
+            let dest = pushed(ep, us, 1);
+            let orig = pushed(ep, us, -1);
+            if (orig.to_bb() & empty).is_not_empty() {
+                unmoves.push(Unmove::new(
+                    Move::build(orig, dest, None, MoveType::QUIET),
+                    PieceType::None,
+                ));
+            }
+            return;
+        }
+
+        for dest in self.get_occupied_player_runtime(us) {
+            // A piece which has just moved to a square can't be holding a castling right there.
+            if self.castling_rights.update(dest) != self.castling_rights {
+                continue;
+            }
+
+            let pt = self.piece_at_sq(dest).type_of();
+            let rank = us.relative_rank(dest.rank());
+
+            if pt == PieceType::Pawn {
+                self.pawn_unmoves(dest, converting, unmoves);
+                continue;
+            }
+
+            let origins = match pt {
+                PieceType::Knight => knight_moves(dest),
+                PieceType::Bishop => bishop_moves(occupied, dest),
+                PieceType::Rook => rook_moves(occupied, dest),
+                PieceType::Queen => queen_moves(occupied, dest),
+                PieceType::King => king_moves(dest),
+                _ => unreachable!(),
+            } & empty;
+
+            for orig in origins {
+                if self.castling_rights.update(orig) != self.castling_rights {
+                    continue;
+                }
+
+                let mov = Move::build(orig, dest, None, MoveType::QUIET);
+                unmoves.push(Unmove::new(mov, PieceType::None));
+                if converting {
+                    self.push_uncaptures(mov, dest.rank() != 0 && dest.rank() != 7, unmoves);
+                }
+            }
+
+            // Pieces on the last rank may have just promoted.
+            if converting && rank == 7 && pt != PieceType::King {
+                let orig = pushed(dest, us, -1);
+                if (orig.to_bb() & empty).is_not_empty() {
+                    let mov = Move::build(orig, dest, Some(pt), MoveType::PROMOTION);
+                    unmoves.push(Unmove::new(mov, PieceType::None));
+                }
+
+                for orig in Bitboard(pawn_attacks_from(dest, them)) & empty {
+                    let mov = Move::build(orig, dest, Some(pt), MoveType::PROMOTION);
+                    self.push_uncaptures(mov, false, unmoves);
+                }
+            }
+        }
+    }
+
+    /// Generate the unmoves of the pawn on `dest`, which belongs to the side not to move,
+    /// including uncaptures if `converting` is set.
+    fn pawn_unmoves(&self, dest: Square, converting: bool, unmoves: &mut Vec<Unmove>) {
+        let them = self.turn();
+        let us = !them;
+        let empty = !self.occupied();
+        let is_empty = |sq: Square| (sq.to_bb() & empty).is_not_empty();
+        let rank = us.relative_rank(dest.rank());
+
+        // A pawn on its second rank hasn't moved.
+        if rank < 2 {
+            return;
+        }
+
+        let orig = pushed(dest, us, -1);
+        if is_empty(orig) {
+            let mov = Move::build(orig, dest, None, MoveType::QUIET);
+            unmoves.push(Unmove::new(mov, PieceType::None));
+
+            // After a double push we would have an en passant square if they could capture.
+            let double = pushed(dest, us, -2);
+            if rank == 3
+                && is_empty(double)
+                && (Bitboard(pawn_attacks_from(orig, us)) & self.piece_bb(them, PieceType::Pawn))
+                    .is_empty()
+            {
+                let mov = Move::build(double, dest, None, MoveType::QUIET);
+                unmoves.push(Unmove::new(mov, PieceType::None));
+            }
+        }
+
+        if !converting {
+            return;
+        }
+
+        for orig in Bitboard(pawn_attacks_from(dest, them)) & empty {
+            let mov = Move::build(orig, dest, None, MoveType::CAPTURE);
+            self.push_uncaptures(mov, true, unmoves);
+
+            // An en passant capture of a pawn which double pushed past `dest`.
+            if rank == 5 && is_empty(pushed(dest, us, -1)) && is_empty(pushed(dest, us, 1)) {
+                let mov = Move::build(orig, dest, None, MoveType::EN_PASSANT | MoveType::CAPTURE);
+                unmoves.push(Unmove::new(mov, PieceType::Pawn));
+            }
+        }
+    }
+
+    /// Push an uncapture of each piece type for `mov`, including pawns if `pawns` is set.
+    fn push_uncaptures(&self, mov: Move, pawns: bool, unmoves: &mut Vec<Unmove>) {
+        let ty = (mov.move_type() - MoveType::QUIET) | MoveType::CAPTURE;
+        let mov = Move::build(mov.orig(), mov.dest(), mov.promo_piece_type(), ty);
+
+        if pawns {
+            unmoves.push(Unmove::new(mov, PieceType::Pawn));
+        }
+        for pt in UNCAPTURABLE {
+            unmoves.push(Unmove::new(mov, pt));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::init_globals;
+    use crate::mono_traits::All;
+
+    fn unmoves(fen: &str) -> (Position, Vec<Unmove>) {
+        init_globals();
+        let pos = Position::from_fen(fen).unwrap();
+        let unmoves = pos.generate_unmoves();
+        (pos, unmoves)
+    }
+
+    /// The position without its clocks, which unmoves don't restore exactly.
+    fn fen_without_clocks(pos: &Position) -> String {
+        pos.to_fen()
+            .split(' ')
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn assert_round_trips(fen: &str) {
+        let (pos, unmoves) = unmoves(fen);
+        assert!(!unmoves.is_empty());
+
+        for unmove in unmoves {
+            let mut prev = pos.predecessor(&unmove);
+            assert!(!prev.enemy_in_check(), "{} from {}", unmove, fen);
+            assert!(
+                prev.legal_moves::<All>().any(|m| m == unmove.mov()),
+                "{} is not legal in {}",
+                unmove.mov(),
+                prev.to_fen()
+            );
+
+            prev.make_move(&unmove.mov());
+            assert_eq!(fen_without_clocks(&prev), fen_without_clocks(&pos));
+            assert_eq!(prev.zobrist(), pos.zobrist(), "{} from {}", unmove, fen);
+        }
+    }
+
+    #[test]
+    fn unmoves_round_trip() {
+        assert_round_trips("4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_round_trips("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+        assert_round_trips("3Qk3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_round_trips("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+        assert_round_trips("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1");
+        assert_round_trips("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1");
+        assert_round_trips("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1");
+    }
+
+    #[test]
+    fn legality_matches_predecessor() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 b - - 0 1",
+            "3Qk3/8/8/8/8/8/8/4K3 b - - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1",
+            "8/8/8/2k5/3P4/8/8/B3K2R b - - 0 1",
+            "8/8/8/8/1k6/8/2P5/K2R4 b - - 0 1",
+        ] {
+            let (pos, _) = unmoves(fen);
+            let mut candidates = Vec::new();
+            pos.generate_unmove_candidates(true, &mut candidates);
+
+            for unmove in candidates {
+                assert_eq!(
+                    pos.legal_unmove(&unmove),
+                    !pos.predecessor(&unmove).enemy_in_check(),
+                    "{} from {}",
+                    unmove,
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unmove_counts() {
+        // The king came from one of 5 squares, maybe capturing one of 4 pieces.
+        let (_, bare) = unmoves("4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(bare.len(), 25);
+
+        // The pawn pushed, captured one of 5 pieces from either side or captured en passant.
+        let (_, pawn) = unmoves("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(pawn.len(), 25 + 1 + 10 + 2);
+        assert_eq!(pawn.iter().filter(|u| u.mov().is_en_passant()).count(), 2);
+
+        // Only the double push is possible with an en passant square.
+        let (_, ep) = unmoves("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+        assert_eq!(ep.len(), 1);
+        assert_eq!(ep[0].mov().orig(), Square::D7);
+
+        // Only the pawn push and the king's moves keep the material.
+        let (pos, _) = unmoves("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+        let quiet = pos.generate_quiet_unmoves();
+        assert_eq!(quiet.len(), 6);
+        assert!(quiet
+            .iter()
+            .all(|u| !u.is_uncapture() && !u.is_unpromotion()));
+
+        // The king and rooks can't have moved while white can still castle.
+        let (_, castling) = unmoves("4k3/8/8/8/8/8/8/R3K2R b KQ - 0 1");
+        assert!(castling.is_empty());
+    }
+
+    #[test]
+    fn unpromotions() {
+        let (_, unmoves) = unmoves("3Qk3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        // A pawn on d7 would have been giving check.
+        assert!(!unmoves
+            .iter()
+            .any(|u| u.is_unpromotion() && u.mov().orig() == Square::D7));
+
+        let from_c7: Vec<_> = unmoves
+            .iter()
+            .filter(|u| u.is_unpromotion() && u.mov().orig() == Square::C7)
+            .collect();
+        assert_eq!(from_c7.len(), 4);
+        assert!(from_c7.iter().all(|u| u.is_uncapture()
+            && u.uncaptured() != PieceType::Pawn
+            && u.mov().promo_piece_type() == Some(PieceType::Queen)));
+    }
+}
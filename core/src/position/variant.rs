@@ -0,0 +1,150 @@
+//! Chess variants which alter the rules for how a game ends.
+//!
+//! The variants supported here share standard chess movegen exactly. They only add extra ways for
+//! a game to be won, so they are implemented as additional state on `Position` and a check
+//! (`Position::variant_loss`) which search code calls alongside its checkmate and draw detection.
+
+use crate::bb::Bitboard;
+use crate::masks::{FILE_D, FILE_E, RANK_4, RANK_5};
+
+use std::fmt;
+
+/// The number of checks a player must give to win a game of Three-check.
+pub const THREE_CHECK_LIMIT: u8 = 3;
+
+/// The central squares d4, e4, d5 and e5. A king reaching any of these wins a game of King of the
+/// Hill.
+pub const HILL: Bitboard = Bitboard((FILE_D | FILE_E) & (RANK_4 | RANK_5));
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Variant {
+    /// Standard chess.
+    #[default]
+    Standard,
+    /// A player also wins by giving check three times.
+    ThreeCheck,
+    /// A player also wins by moving their king to one of the four central squares.
+    KingOfTheHill,
+}
+
+impl Variant {
+    /// All supported variants, in the order they are advertised over UCI.
+    pub const ALL: [Variant; 3] = [
+        Variant::Standard,
+        Variant::ThreeCheck,
+        Variant::KingOfTheHill,
+    ];
+
+    /// Parse a variant from the name used by the `UCI_Variant` option.
+    pub fn from_uci_name(name: &str) -> Option<Self> {
+        match name {
+            "chess" | "standard" => Some(Variant::Standard),
+            "3check" | "threecheck" => Some(Variant::ThreeCheck),
+            "kingofthehill" | "koth" => Some(Variant::KingOfTheHill),
+            _ => None,
+        }
+    }
+
+    /// The name of this variant as used by the `UCI_Variant` option.
+    pub fn uci_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::ThreeCheck => "3check",
+            Variant::KingOfTheHill => "kingofthehill",
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.uci_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::init_globals;
+    use crate::position::{Player, Position};
+
+    #[test]
+    fn three_check_counts_checks() {
+        init_globals();
+
+        let fen = "rnbqkbnr/ppp2ppp/8/3pp3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3";
+        let mut pos = Position::from_fen_variant(fen, Variant::ThreeCheck).unwrap();
+        let mut standard = Position::from_fen(fen).unwrap();
+        let start_zob = pos.zobrist();
+        assert_eq!(start_zob, standard.zobrist());
+
+        pos.make_uci_move("f1b5").unwrap();
+        standard.make_uci_move("f1b5").unwrap();
+        assert_eq!(pos.checks_given(Player::WHITE), 1);
+        assert_eq!(pos.checks_given(Player::BLACK), 0);
+        assert_ne!(pos.zobrist(), standard.zobrist());
+        assert_eq!(
+            pos.to_fen(),
+            "rnbqkbnr/ppp2ppp/8/1B1pp3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 1 3"
+        );
+
+        pos.unmake_move();
+        assert_eq!(pos.checks_given(Player::WHITE), 0);
+        assert_eq!(pos.zobrist(), start_zob);
+    }
+
+    #[test]
+    fn three_check_fen_round_trip() {
+        init_globals();
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 1+2 0 1";
+        let pos = Position::from_fen_variant(fen, Variant::ThreeCheck).unwrap();
+        assert_eq!(pos.checks_given(Player::WHITE), 2);
+        assert_eq!(pos.checks_given(Player::BLACK), 1);
+        assert_eq!(pos.to_fen(), fen);
+
+        assert!(Position::from_fen_variant(
+            "4k3/8/8/8/8/8/8/4K2R w K - 4+3 0 1",
+            Variant::ThreeCheck
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn three_check_loss() {
+        init_globals();
+
+        let mut pos =
+            Position::from_fen_variant("4k3/8/8/8/8/8/8/4K2R w K - 1+3 0 1", Variant::ThreeCheck)
+                .unwrap();
+        assert!(!pos.variant_loss());
+
+        pos.make_uci_move("h1h8").unwrap();
+        assert!(pos.variant_loss());
+        assert!(!pos.in_checkmate());
+    }
+
+    #[test]
+    fn king_of_the_hill_loss() {
+        init_globals();
+
+        let mut pos =
+            Position::from_fen_variant("4k3/8/8/8/8/3K4/8/8 w - - 0 1", Variant::KingOfTheHill)
+                .unwrap();
+        assert!(!pos.variant_loss());
+
+        pos.make_uci_move("d3d4").unwrap();
+        assert!(pos.variant_loss());
+
+        pos.unmake_move();
+        pos.set_variant(Variant::Standard);
+        pos.make_uci_move("d3d4").unwrap();
+        assert!(!pos.variant_loss());
+    }
+
+    #[test]
+    fn uci_names() {
+        for variant in Variant::ALL {
+            assert_eq!(Variant::from_uci_name(variant.uci_name()), Some(variant));
+        }
+    }
+}
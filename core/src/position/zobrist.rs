@@ -1,6 +1,7 @@
-use crate::position::{CastlingRights, Piece, Position, Square};
+use crate::position::{CastlingRights, Piece, PieceType, Player, Position, Square, PIECE_TYPES};
 use crate::precalc::zobrist::{
-    castling_rights_keys, ep_file_keys, piece_square_key, side_to_move_key, side_to_move_toggler,
+    castling_rights_keys, checks_given_key, ep_file_keys, piece_square_key, side_to_move_key,
+    side_to_move_toggler,
 };
 
 use std::fmt;
@@ -23,7 +24,10 @@ impl Zobrist {
         let mut zob = Zobrist::empty();
         // Piece-squares
         for (sq, piece) in &pos.board {
-            zob ^= piece_square_key(piece, sq);
+            // Empty squares aren't hashed, to match the incremental updates in `make_move`.
+            if !piece.is_none() {
+                zob ^= piece_square_key(piece, sq);
+            }
         }
         // Side-to-move
         zob ^= side_to_move_key(pos.turn());
@@ -34,10 +38,47 @@ impl Zobrist {
             Some(sq) => zob ^= ep_file_keys(sq),
             None => {}
         };
+        // Checks given (Three-check)
+        zob ^= checks_given_key(Player::WHITE, pos.checks_given(Player::WHITE));
+        zob ^= checks_given_key(Player::BLACK, pos.checks_given(Player::BLACK));
 
         zob
     }
 
+    /// Generates the pawn key of a `Position`: the piece-square keys of its pawns alone.
+    pub fn pawns_from_position(pos: &Position) -> Self {
+        let mut zob = Zobrist::empty();
+        for (sq, piece) in &pos.board {
+            if piece.type_of() == PieceType::Pawn {
+                zob ^= piece_square_key(piece, sq);
+            }
+        }
+
+        zob
+    }
+
+    /// Generates the material key of a `Position`. The `n`th piece of each type and colour
+    /// contributes the piece-square key of that piece on the `n`th square, so the key only
+    /// depends on how many of each piece there are.
+    pub fn material_from_position(pos: &Position) -> Self {
+        let mut zob = Zobrist::empty();
+        for player in [Player::WHITE, Player::BLACK] {
+            for &piece_type in &PIECE_TYPES[1..] {
+                for n in 0..pos.piece_bb(player, piece_type).popcnt() {
+                    zob.toggle_piece_count(Piece::make(player, piece_type), n);
+                }
+            }
+        }
+
+        zob
+    }
+
+    /// Updates a material key when the `n`th piece of a type and colour (counting from zero)
+    /// is added or removed.
+    pub fn toggle_piece_count(&mut self, piece: Piece, n: u32) {
+        *self ^= piece_square_key(piece, Square(n as u8));
+    }
+
     /// Updates a Zobrist key by xor'ing with the piece-square key for the given `Piece` and `Square`.
     /// For normal moves, this will be called twice: once to remove the key for where the piece started,
     /// and once to add in the key for where the piece moves to. For a capture, there will be another call,
@@ -59,6 +100,13 @@ impl Zobrist {
         }
     }
 
+    /// Update a Zobrist key when the number of checks given by `player` changes from `old` to
+    /// `new`.
+    pub fn update_checks_given(&mut self, player: Player, old: u8, new: u8) {
+        *self ^= checks_given_key(player, old);
+        *self ^= checks_given_key(player, new);
+    }
+
     /// Update a Zobrist key from an old en passant square to a new one.
     pub fn update_ep_square(&mut self, old: Option<Square>, new: Option<Square>) {
         match old {
@@ -96,3 +144,79 @@ impl fmt::Binary for Zobrist {
         fmt::Binary::fmt(&self.0, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::init_globals;
+    use crate::mono_traits::All;
+
+    fn assert_keys_match(pos: &Position) {
+        assert_eq!(pos.zobrist(), Zobrist::from_position(pos));
+        assert_eq!(pos.pawn_key(), Zobrist::pawns_from_position(pos));
+        assert_eq!(pos.material_key(), Zobrist::material_from_position(pos));
+    }
+
+    /// Walk the perft tree of `pos`, checking that the incrementally updated keys agree with the
+    /// keys computed from scratch at every node, and after a null move.
+    fn check_keys(pos: &mut Position, depth: usize) {
+        assert_keys_match(pos);
+
+        if !pos.in_check() {
+            let before = pos.clone();
+            pos.make_null_move();
+            assert_keys_match(pos);
+            pos.unmake_move();
+            assert_eq!(*pos, before);
+        }
+
+        if depth == 0 {
+            return;
+        }
+
+        for mov in pos.legal_moves::<All>().collect::<Vec<_>>() {
+            pos.make_move(&mov);
+            check_keys(pos, depth - 1);
+            pos.unmake_move();
+        }
+    }
+
+    #[test]
+    fn incremental_keys_match_from_scratch() {
+        init_globals();
+
+        for fen in [
+            crate::position::START_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            check_keys(&mut pos, 3);
+        }
+    }
+
+    #[test]
+    fn pawn_and_material_keys() {
+        init_globals();
+        let key = |fen| Position::from_fen(fen).unwrap();
+
+        // Moving a piece keeps the pawn and material keys.
+        let a = key("4k3/4p3/8/8/8/8/4P3/3QK3 w - - 0 1");
+        let b = key("4k3/4p3/8/8/8/8/4P3/4K2Q b - - 0 1");
+        assert_eq!(a.pawn_key(), b.pawn_key());
+        assert_eq!(a.material_key(), b.material_key());
+        assert_ne!(a.zobrist(), b.zobrist());
+
+        // Moving a pawn keeps the material key only.
+        let c = key("4k3/4p3/8/8/8/4P3/8/3QK3 w - - 0 1");
+        assert_ne!(a.pawn_key(), c.pawn_key());
+        assert_eq!(a.material_key(), c.material_key());
+
+        // Swapping the queen for a rook changes the material key only.
+        let d = key("4k3/4p3/8/8/8/8/4P3/3RK3 w - - 0 1");
+        assert_eq!(a.pawn_key(), d.pawn_key());
+        assert_ne!(a.material_key(), d.material_key());
+    }
+}
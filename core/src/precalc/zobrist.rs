@@ -8,8 +8,10 @@ static mut SIDE_TO_MOVE_KEYS: [u64; 2] = [0; 2];
 static mut SIDE_TO_MOVE_TOGGLER: u64 = 0;
 static mut CASTLING_RIGHTS_KEYS: [u64; 16] = [0; 16];
 static mut EP_FILE_KEYS: [u64; 8] = [0; 8];
+/// Keys indexed by player and the number of checks that player has given (Three-check only).
+static mut CHECKS_GIVEN_KEYS: [[u64; 4]; 2] = [[0; 4]; 2];
 
-const SEEDS: [u64; 4] = [10_123, 43_292_194, 19_023_734, 32_336];
+const SEEDS: [u64; 5] = [10_123, 43_292_194, 19_023_734, 32_336, 7_781_203];
 
 #[cold]
 pub fn init_zobrist() {
@@ -18,6 +20,7 @@ pub fn init_zobrist() {
         gen_side_to_move_keys();
         gen_castling_rights_keys();
         gen_ep_file_keys();
+        gen_checks_given_keys();
     }
 }
 
@@ -60,6 +63,17 @@ unsafe fn gen_ep_file_keys() {
     }
 }
 
+#[cold]
+unsafe fn gen_checks_given_keys() {
+    let mut rng = PRNG::init(SEEDS[4]);
+
+    for player in CHECKS_GIVEN_KEYS.iter_mut() {
+        for spot in player.iter_mut() {
+            *spot = rng.rand();
+        }
+    }
+}
+
 #[inline(always)]
 pub fn piece_square_key(piece: Piece, square: Square) -> u64 {
     debug_assert!(square.is_okay());
@@ -91,3 +105,20 @@ pub fn ep_file_keys(sq: Square) -> u64 {
     debug_assert!(sq.is_okay());
     unsafe { *EP_FILE_KEYS.get_unchecked(sq.rank() as usize) }
 }
+
+/// Returns the key for `player` having given `checks` checks. Having given no checks has a key of
+/// zero, so that positions outside of Three-check hash exactly as in standard chess. Any count
+/// beyond three (only reachable by playing on after the game has ended) shares the key for three.
+#[inline(always)]
+pub fn checks_given_key(player: Player, checks: u8) -> u64 {
+    let checks = std::cmp::min(checks, 3);
+    if checks == 0 {
+        0
+    } else {
+        unsafe {
+            *CHECKS_GIVEN_KEYS
+                .get_unchecked(player.inner() as usize)
+                .get_unchecked(checks as usize)
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! Depth-first proof-number search, for proving forced mates.
+//!
+//! Alpha-beta search with a material evaluation has no sense of direction in a mating attack, so
+//! long mates lie far beyond its horizon. Proof-number search instead grows the game tree towards
+//! the positions which are cheapest to settle: the proof number of a node is the smallest number
+//! of leaves which must be shown to be mates to prove it, and the disproof number the smallest
+//! number which must be shown not to be. The attacker's nodes are OR nodes, which one proven child
+//! proves, and the defender's are AND nodes, which need every child proven.
+//!
+//! Df-pn (Nagai, 2002) explores the same tree depth-first, keeping only a transposition table, by
+//! giving each node thresholds on its proof and disproof numbers and only returning to the parent
+//! once one of them is reached.
+//!
+//! We search for a mate within a fixed number of moves, trying each bound in turn from one move,
+//! so that the first proof found is a shortest mate. Repetitions and the fifty move rule are
+//! ignored; they can never make a mate shorter.
+
+use core::mono_traits::{All, Legal};
+use core::mov::Move;
+use core::movelist::{BasicMoveList, MoveList};
+use core::position::Position;
+
+use std::collections::HashMap;
+
+/// Proof and disproof numbers at or above this are infinite. It is small enough that adding a
+/// few of them together can't overflow.
+const INFINITY: u64 = u64::MAX / 1024;
+
+/// The longest mate we can look for. Its bound in plies, `2 * moves - 1`, must fit in a `u8`.
+pub const MAX_MATE_MOVES: u8 = 127;
+
+/// The proof and disproof numbers of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Numbers {
+    pn: u64,
+    dn: u64,
+}
+
+impl Numbers {
+    /// A node which is a mate.
+    const PROVEN: Numbers = Numbers {
+        pn: 0,
+        dn: INFINITY,
+    };
+
+    /// A node from which there is no mate.
+    const DISPROVEN: Numbers = Numbers {
+        pn: INFINITY,
+        dn: 0,
+    };
+
+    /// A node we know nothing about yet.
+    const UNKNOWN: Numbers = Numbers { pn: 1, dn: 1 };
+}
+
+/// The result of trying to solve a position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Solution {
+    /// The side to move mates in `moves` moves. `line` is the main line of the mating tree, in
+    /// which the defender puts off mate for as long as possible.
+    Mate { moves: u8, line: Vec<Move> },
+    /// There is no mate within the number of moves we were asked about.
+    NoMate,
+    /// The node limit was reached before the search could decide.
+    Unknown,
+}
+
+/// A df-pn solver for mates by the side to move.
+pub struct MateSolver {
+    /// The proof and disproof numbers of the positions we have visited, keyed by Zobrist key and
+    /// the number of plies left to mate in.
+    table: HashMap<(u64, u8), Numbers>,
+    /// Whether the attacker may only play checking moves.
+    checks_only: bool,
+    /// The number of nodes we may expand before giving up.
+    node_limit: usize,
+    /// The number of nodes expanded so far.
+    nodes: usize,
+}
+
+impl MateSolver {
+    pub fn new() -> Self {
+        Self {
+            table: HashMap::new(),
+            checks_only: false,
+            node_limit: usize::MAX,
+            nodes: 0,
+        }
+    }
+
+    /// Only consider checking moves for the attacker. Mates which need a quiet move are missed,
+    /// but long sequences of checks are proven far faster.
+    pub fn set_checks_only(&mut self, checks_only: bool) {
+        self.checks_only = checks_only;
+    }
+
+    /// Give up after expanding `nodes` nodes.
+    pub fn set_node_limit(&mut self, nodes: usize) {
+        self.node_limit = nodes;
+    }
+
+    /// The number of nodes expanded so far.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Search for the shortest mate by the side to move in `pos` within `max_moves` moves, or
+    /// within `MAX_MATE_MOVES` if that is fewer.
+    pub fn solve(&mut self, pos: &mut Position, max_moves: u8) -> Solution {
+        for moves in 1..=max_moves.min(MAX_MATE_MOVES) {
+            let plies = 2 * moves - 1;
+            let root = self.mid(pos, plies, INFINITY - 1, INFINITY - 1);
+
+            if root.pn == 0 {
+                return Solution::Mate {
+                    moves,
+                    line: self.main_line(pos, plies),
+                };
+            }
+            if root.dn != 0 {
+                return Solution::Unknown;
+            }
+        }
+
+        Solution::NoMate
+    }
+
+    /// Whether the side to move in a node `plies` plies short of the bound is the attacker.
+    fn attacker(plies: u8) -> bool {
+        plies % 2 == 1
+    }
+
+    /// The moves to search in `pos`, with `plies` plies left. The attacker's checks come first,
+    /// since they are the likeliest to lead to mate.
+    fn moves(&self, pos: &Position, plies: u8) -> BasicMoveList {
+        let moves = pos.generate::<BasicMoveList, All, Legal>();
+        if !Self::attacker(plies) {
+            return moves;
+        }
+
+        let mut ordered = BasicMoveList::new();
+        for mov in moves.iter().filter(|m| pos.gives_check(m)) {
+            ordered.push(*mov);
+        }
+        if !self.checks_only {
+            for mov in moves.iter().filter(|m| !pos.gives_check(m)) {
+                ordered.push(*mov);
+            }
+        }
+
+        ordered
+    }
+
+    /// Look up the numbers of `pos` with `plies` plies left, or settle it if it is a leaf.
+    fn lookup(&mut self, pos: &Position, plies: u8) -> Numbers {
+        if let Some(n) = self.table.get(&(pos.zobrist().0, plies)) {
+            return *n;
+        }
+
+        let n = if pos.variant_loss() {
+            if Self::attacker(plies) {
+                Numbers::DISPROVEN
+            } else {
+                Numbers::PROVEN
+            }
+        } else if !pos.has_legal_move() {
+            if !Self::attacker(plies) && pos.in_check() {
+                Numbers::PROVEN
+            } else {
+                Numbers::DISPROVEN
+            }
+        } else if plies == 0 {
+            Numbers::DISPROVEN
+        } else {
+            return Numbers::UNKNOWN;
+        };
+
+        self.table.insert((pos.zobrist().0, plies), n);
+        n
+    }
+
+    /// Expand `pos`, with `plies` plies left, until its proof number reaches `pn_limit` or its
+    /// disproof number reaches `dn_limit`, and return its numbers.
+    fn mid(&mut self, pos: &mut Position, plies: u8, pn_limit: u64, dn_limit: u64) -> Numbers {
+        let n = self.lookup(pos, plies);
+        if n.pn == 0 || n.dn == 0 {
+            return n;
+        }
+
+        let moves = self.moves(pos, plies);
+        if moves.is_empty() {
+            // Only the attacker's moves are filtered, so this is an attacker without a check.
+            self.table
+                .insert((pos.zobrist().0, plies), Numbers::DISPROVEN);
+            return Numbers::DISPROVEN;
+        }
+
+        let or_node = Self::attacker(plies);
+        let mut children = vec![Numbers::UNKNOWN; moves.len()];
+
+        loop {
+            for (mov, child) in moves.iter().zip(children.iter_mut()) {
+                pos.make_move(mov);
+                *child = self.lookup(pos, plies - 1);
+                pos.unmake_move();
+            }
+
+            // From the attacker's side, the cheapest child to prove proves the node, and every
+            // child must be disproven. The defender's side is the mirror image.
+            let n = if or_node {
+                Numbers {
+                    pn: children.iter().map(|c| c.pn).min().unwrap(),
+                    dn: sum(children.iter().map(|c| c.dn)),
+                }
+            } else {
+                Numbers {
+                    pn: sum(children.iter().map(|c| c.pn)),
+                    dn: children.iter().map(|c| c.dn).min().unwrap(),
+                }
+            };
+            self.table.insert((pos.zobrist().0, plies), n);
+
+            if n.pn >= pn_limit || n.dn >= dn_limit || self.nodes >= self.node_limit {
+                return n;
+            }
+
+            // Descend into the most promising child, until it looks worse than the second best.
+            let (best, second) = if or_node {
+                best_two(children.iter().map(|c| c.pn))
+            } else {
+                best_two(children.iter().map(|c| c.dn))
+            };
+            let child = children[best];
+            let (child_pn_limit, child_dn_limit) = if or_node {
+                (
+                    std::cmp::min(pn_limit, second.saturating_add(1)),
+                    (dn_limit - n.dn).saturating_add(child.dn).min(INFINITY),
+                )
+            } else {
+                (
+                    (pn_limit - n.pn).saturating_add(child.pn).min(INFINITY),
+                    std::cmp::min(dn_limit, second.saturating_add(1)),
+                )
+            };
+
+            self.nodes += 1;
+            pos.make_move(&moves[best]);
+            self.mid(pos, plies - 1, child_pn_limit, child_dn_limit);
+            pos.unmake_move();
+        }
+    }
+
+    /// The main line of the proven mate from `pos` with `plies` plies left, in which the attacker
+    /// mates as fast as the proof allows and the defender holds out as long as possible.
+    fn main_line(&mut self, pos: &mut Position, plies: u8) -> Vec<Move> {
+        let mut distances = HashMap::new();
+        let mut line = Vec::new();
+        let mut plies = plies;
+
+        loop {
+            let best = self
+                .proven_children(pos, plies)
+                .into_iter()
+                .map(|mov| {
+                    pos.make_move(&mov);
+                    let d = self.distance(pos, plies - 1, &mut distances);
+                    pos.unmake_move();
+                    (mov, d)
+                })
+                .reduce(|a, b| {
+                    let better = if Self::attacker(plies) {
+                        b.1 < a.1
+                    } else {
+                        b.1 > a.1
+                    };
+                    if better {
+                        b
+                    } else {
+                        a
+                    }
+                });
+
+            match best {
+                Some((mov, _)) => {
+                    line.push(mov);
+                    pos.make_move(&mov);
+                    plies -= 1;
+                }
+                None => break,
+            }
+        }
+
+        for _ in 0..line.len() {
+            pos.unmake_move();
+        }
+
+        line
+    }
+
+    /// The moves from `pos`, with `plies` plies left, to children which are proven mates.
+    fn proven_children(&mut self, pos: &mut Position, plies: u8) -> Vec<Move> {
+        if plies == 0 {
+            return Vec::new();
+        }
+
+        let mut proven = Vec::new();
+        for mov in self.moves(pos, plies).iter() {
+            pos.make_move(mov);
+            if self.lookup(pos, plies - 1).pn == 0 {
+                proven.push(*mov);
+            }
+            pos.unmake_move();
+        }
+
+        proven
+    }
+
+    /// The number of plies to mate in the proof of `pos`, with `plies` plies left, when the
+    /// attacker takes the fastest mate and the defender the slowest.
+    fn distance(
+        &mut self,
+        pos: &mut Position,
+        plies: u8,
+        distances: &mut HashMap<(u64, u8), u8>,
+    ) -> u8 {
+        let key = (pos.zobrist().0, plies);
+        if let Some(d) = distances.get(&key) {
+            return *d;
+        }
+
+        let children = self.proven_children(pos, plies);
+        let ds = children.iter().map(|mov| {
+            pos.make_move(mov);
+            let d = self.distance(pos, plies - 1, distances);
+            pos.unmake_move();
+            d + 1
+        });
+
+        // A defender without proven children has been mated.
+        let d = if Self::attacker(plies) {
+            ds.min().expect("a proven attacker node has a proven child")
+        } else {
+            ds.max().unwrap_or(0)
+        };
+
+        distances.insert(key, d);
+        d
+    }
+}
+
+impl Default for MateSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sum of `numbers`, capped at infinity.
+fn sum(numbers: impl Iterator<Item = u64>) -> u64 {
+    numbers
+        .fold(0, |acc: u64, n| acc.saturating_add(n))
+        .min(INFINITY)
+}
+
+/// The index of the smallest of `numbers`, and the second smallest value.
+fn best_two(numbers: impl Iterator<Item = u64>) -> (usize, u64) {
+    let mut best = (0, INFINITY);
+    let mut second = INFINITY;
+
+    for (i, n) in numbers.enumerate() {
+        if n < best.1 {
+            second = best.1;
+            best = (i, n);
+        } else if n < second {
+            second = n;
+        }
+    }
+
+    (best.0, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(fen: &str, max_moves: u8) -> Solution {
+        core::init::init_globals();
+
+        let mut pos = Position::from_fen(fen).unwrap();
+        let zobrist = pos.zobrist();
+        let solution = MateSolver::new().solve(&mut pos, max_moves);
+        assert_eq!(pos.zobrist(), zobrist);
+
+        solution
+    }
+
+    #[test]
+    fn proves_shortest_mates() {
+        let suite = [
+            ("3q2k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1, "d1d8"),
+            (
+                "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+                2,
+                "d5f6",
+            ),
+            ("7k/8/6K1/8/8/8/8/R7 w - - 0 1", 1, "a1a8"),
+            ("8/8/8/8/8/5k2/8/5K1R b - - 0 1", 0, ""),
+        ];
+
+        for (fen, moves, first) in suite {
+            match solve(fen, 3) {
+                Solution::Mate { moves: m, line } => {
+                    assert_eq!(m, moves, "{}", fen);
+                    assert_eq!(line.len(), 2 * moves as usize - 1, "{}", fen);
+                    assert_eq!(line[0].to_uci_string(), first, "{}", fen);
+                }
+                Solution::NoMate => assert_eq!(moves, 0, "{}", fen),
+                Solution::Unknown => panic!("{} unsolved", fen),
+            }
+        }
+    }
+
+    #[test]
+    fn mated_line_ends_in_checkmate() {
+        core::init::init_globals();
+
+        // Mate in 3 with two rooks against the bare king.
+        let mut pos = Position::from_fen("8/8/8/8/8/k7/8/1RR3K1 w - - 0 1").unwrap();
+        let Solution::Mate { moves, line } = MateSolver::new().solve(&mut pos, 4) else {
+            panic!("no mate found");
+        };
+
+        assert_eq!(line.len(), 2 * moves as usize - 1);
+        for mov in &line {
+            pos.make_move(mov);
+        }
+        assert!(pos.in_checkmate());
+    }
+}
@@ -0,0 +1,351 @@
+//! Retrograde generation of DTM tables.
+//!
+//! First every entry of the table is classified by generating its legal moves. Checkmates are
+//! lost in 0 plies. Captures and promotions lead to other material sets, whose tables we already
+//! have, so their outcome is known up front; the remaining moves stay in the table and we count
+//! them.
+//!
+//! Then we work outwards from the mates one ply at a time. For each position resolved at ply `n`,
+//! we generate its predecessors with `Position::generate_quiet_unmoves`. If the position is lost
+//! for the side to move, every predecessor wins in `n + 1`. If it is won, we decrement the count of
+//! unresolved moves of each predecessor, and once a predecessor has no moves left which avoid
+//! defeat, it is lost. Positions which are never resolved are drawn.
+//!
+//! Tables don't store en passant squares, but a double push which gives the opponent an en passant
+//! capture leads to a position with one more move than the table entry with the same placement.
+//! We resolve these positions as shadows of their entries, alongside the table: a shadow has the
+//! moves of its entry and the en passant capture as a conversion, and the double pushes which lead
+//! to it are resolved through it rather than through the entry.
+
+use super::{placed, DtmTable, EndgameTables, Layout, DRAW, ILLEGAL, MAX_PIECES, PIECE_ORDER};
+use crate::syzygy::Material;
+
+use core::mono_traits::{All, Legal};
+use core::mov::Move;
+use core::movelist::BasicMoveList;
+use core::position::{PieceType, Position, Square, Unmove};
+
+use std::collections::HashMap;
+
+/// Entry for a position not yet resolved during generation.
+const UNKNOWN: u8 = 253;
+
+/// The best outcome of the converting moves of a position, for a position which has none.
+const NO_CONVERSION: u8 = 252;
+
+/// Whether the layout's pieces can be placed on `squares`: no two share a square, and no pawn is
+/// on the first or last rank.
+fn is_placement(layout: &Layout, squares: &[u8]) -> bool {
+    let occupied = squares.iter().fold(0u64, |occ, &sq| occ | 1 << sq);
+    let pawn_on_back_rank = layout
+        .pieces
+        .iter()
+        .zip(squares)
+        .any(|(&(_, pt), &sq)| pt == PieceType::Pawn && !(8..56).contains(&sq));
+
+    occupied.count_ones() as usize == squares.len() && !pawn_on_back_rank
+}
+
+/// Whether the side to move in `pos` can capture en passant.
+fn has_en_passant(pos: &Position) -> bool {
+    pos.generate::<BasicMoveList, All, Legal>()
+        .iter()
+        .any(|m| m.is_en_passant())
+}
+
+/// The unmoves of `pos`, which has the layout's pieces on `squares`, which stay within the table.
+///
+/// Unmove generation only takes back a double push next to an enemy pawn when the en passant square
+/// is set, so we also generate the unmoves with it set. Where the en passant capture is legal, the
+/// push led to a shadow instead, and we leave it out.
+fn table_unmoves(layout: &Layout, pos: &Position, squares: &[u8]) -> Vec<Unmove> {
+    let mover = !pos.turn();
+    let mut unmoves = pos.generate_quiet_unmoves();
+
+    for sq in pos.piece_bb(mover, PieceType::Pawn) {
+        if mover.relative_rank(sq.rank()) != 3 {
+            continue;
+        }
+
+        let ep = Square((sq.0 as i8 - mover.pawn_push()) as u8);
+        let orig = Square((ep.0 as i8 - mover.pawn_push()) as u8);
+        if (pos.occupied() & (ep.to_bb() | orig.to_bb())).is_not_empty() {
+            continue;
+        }
+
+        let stm = pos.turn().is_black() as usize;
+        let pushed = layout.position(squares, stm, Some(ep));
+        if has_en_passant(&pushed) {
+            continue;
+        }
+
+        for unmove in pushed.generate_quiet_unmoves() {
+            if !unmoves.contains(&unmove) {
+                unmoves.push(unmove);
+            }
+        }
+    }
+
+    unmoves
+}
+
+/// The material sets reachable from `material` by one capture or promotion.
+pub(super) fn conversions(material: &Material) -> Vec<Material> {
+    let mut subs = Vec::new();
+
+    for side in 0..2 {
+        for pt in PIECE_ORDER {
+            if material.count(side, pt) == 0 {
+                continue;
+            }
+
+            subs.push(material.without(side, pt));
+
+            if pt == PieceType::Pawn {
+                for promo in &PIECE_ORDER[..4] {
+                    subs.push(material.without(side, pt).with(side, *promo));
+                }
+            }
+        }
+    }
+
+    subs
+}
+
+/// The outcome of a position for its side to move, given the entry of a position reached by one
+/// of its moves. Higher is better: quicker wins, then draws, then slower losses.
+fn rank(child: u8) -> i32 {
+    match child {
+        NO_CONVERSION => i32::MIN,
+        DRAW => 0,
+        plies if plies % 2 == 0 => 1000 - plies as i32,
+        plies => plies as i32 - 1000,
+    }
+}
+
+/// The positions just after a double push which gave the opponent an en passant capture. They
+/// follow the table's entries in the generator's arrays, from index `size`.
+struct Shadows {
+    size: usize,
+    /// The entry with the same placement and the en passant square of each shadow.
+    of: Vec<(usize, Square)>,
+    /// The shadows of each entry which has any.
+    by_entry: HashMap<usize, Vec<usize>>,
+}
+
+impl Shadows {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            of: Vec::new(),
+            by_entry: HashMap::new(),
+        }
+    }
+
+    /// The index of the shadow of `entry` with en passant square `ep`, added if it is new.
+    fn index(&mut self, entry: usize, ep: Square) -> usize {
+        let shadows = self.by_entry.entry(entry).or_default();
+        if let Some(&idx) = shadows
+            .iter()
+            .find(|&&idx| self.of[idx - self.size].1 == ep)
+        {
+            return idx;
+        }
+
+        let idx = self.size + self.of.len();
+        self.of.push((entry, ep));
+        shadows.push(idx);
+        idx
+    }
+
+    /// The shadows of `entry`, which have all its moves and so all its children in the table.
+    fn of_entry(&self, entry: usize) -> &[usize] {
+        self.by_entry.get(&entry).map_or(&[], |shadows| shadows)
+    }
+}
+
+/// Whether `mov` is a double pawn push in `pos`, returning the square it passes over if it is.
+fn double_push(pos: &Position, mov: &Move) -> Option<Square> {
+    let (orig, dest) = (mov.orig().0, mov.dest().0);
+    (pos.piece_at_sq(mov.orig()).type_of() == PieceType::Pawn && orig.abs_diff(dest) == 16)
+        .then(|| Square((orig + dest) / 2))
+}
+
+/// Classify `pos`, with side `stm` to move, by its moves. Returns the number which stay in the
+/// table, and the best entry reached by those which convert to another table. A double push which
+/// gives an en passant capture stays in the table, but leads to a shadow.
+fn classify(
+    layout: &Layout,
+    tables: &EndgameTables,
+    pos: &mut Position,
+    stm: usize,
+    shadows: &mut Shadows,
+) -> (u8, u8) {
+    let moves = pos.generate::<BasicMoveList, All, Legal>();
+    let mut in_table = 0;
+    let mut best = NO_CONVERSION;
+
+    for mov in &moves {
+        if !mov.is_capture() && !mov.is_promo() {
+            in_table += 1;
+
+            if let Some(ep) = double_push(pos, mov) {
+                let after = placed(&pos.play(mov));
+                let entry = layout.index_of(&after, stm ^ 1);
+                let mut squares = [0; MAX_PIECES];
+                layout.decode(entry, &mut squares);
+
+                let pushed = layout.position(&squares[..layout.pieces.len()], stm ^ 1, Some(ep));
+                if has_en_passant(&pushed) {
+                    shadows.index(entry, ep);
+                }
+            }
+            continue;
+        }
+
+        let after = pos.play(mov);
+        let child = tables
+            .lookup(&placed(&after), stm ^ 1)
+            .expect("tables of conversions are generated first");
+        if rank(child) > rank(best) {
+            best = child;
+        }
+    }
+
+    (in_table, best)
+}
+
+/// Generate the table for `material`. `tables` must hold the tables of all its conversions.
+pub(super) fn generate(material: Material, tables: &EndgameTables) -> DtmTable {
+    let layout = Layout::new(&material);
+    let n = layout.pieces.len();
+    assert!(n <= MAX_PIECES);
+
+    let size = layout.size();
+    let mut entries = vec![UNKNOWN; size];
+    let mut remaining = vec![0u8; size];
+    let mut conversion = vec![NO_CONVERSION; size];
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); 256];
+    let mut shadows = Shadows::new(size);
+
+    let mut squares = [0; MAX_PIECES];
+
+    // Classify every position by its moves, and then the shadows found along the way, which can
+    // lead to further shadows.
+    for idx in 0.. {
+        let (stm, ep) = if idx < size {
+            (layout.decode(idx, &mut squares), None)
+        } else if let Some(&(entry, ep)) = shadows.of.get(idx - size) {
+            (layout.decode(entry, &mut squares), Some(ep))
+        } else {
+            break;
+        };
+        let squares = &squares[..n];
+
+        if idx >= size {
+            entries.push(UNKNOWN);
+            remaining.push(0);
+            conversion.push(NO_CONVERSION);
+        } else if !is_placement(&layout, squares) {
+            entries[idx] = ILLEGAL;
+            continue;
+        }
+
+        let mut pos = layout.position(squares, stm, ep);
+        if pos.enemy_in_check() {
+            entries[idx] = ILLEGAL;
+            continue;
+        }
+
+        let (in_table, best) = classify(&layout, tables, &mut pos, stm, &mut shadows);
+        remaining[idx] = in_table;
+        conversion[idx] = best;
+
+        if in_table == 0 && best == NO_CONVERSION {
+            // There are no moves.
+            if pos.in_check() {
+                buckets[0].push(idx);
+            } else {
+                entries[idx] = DRAW;
+            }
+        } else if best != NO_CONVERSION && best != DRAW && best.is_multiple_of(2) {
+            // A conversion wins.
+            buckets[best as usize + 1].push(idx);
+        } else if in_table == 0 && best != DRAW {
+            // Every move is a conversion, and every conversion loses.
+            buckets[best as usize + 1].push(idx);
+        }
+    }
+
+    // Work outwards from the mates.
+    for plies in 0..buckets.len() {
+        let bucket = std::mem::take(&mut buckets[plies]);
+
+        for idx in bucket {
+            if entries[idx] != UNKNOWN {
+                continue;
+            }
+            entries[idx] = plies as u8;
+
+            // A shadow is only reached by the double push which gave the en passant square, and
+            // the predecessors of an entry never include such pushes.
+            let (entry, ep) = if idx < size {
+                (idx, None)
+            } else {
+                let (entry, ep) = shadows.of[idx - size];
+                (entry, Some(ep))
+            };
+            let stm = layout.decode(entry, &mut squares);
+            let mover = stm ^ 1;
+            let pos = layout.position(&squares[..n], stm, ep);
+            let unmoves = match ep {
+                None => table_unmoves(&layout, &pos, &squares[..n]),
+                Some(_) => pos.generate_quiet_unmoves(),
+            };
+
+            for unmove in unmoves {
+                let (orig, dest) = (unmove.mov().orig(), unmove.mov().dest());
+                let mut before = squares;
+                let i = (0..n).find(|&i| squares[i] == dest.0).unwrap();
+                before[i] = orig.0;
+                let prev = layout.index(&before[..n], mover);
+
+                // The shadows of the predecessor have the same move to this position.
+                for &prev in std::iter::once(&prev).chain(shadows.of_entry(prev)) {
+                    if entries[prev] != UNKNOWN {
+                        continue;
+                    }
+
+                    if plies % 2 == 0 {
+                        // We lose here, so the predecessor wins by moving here.
+                        buckets[plies + 1].push(prev);
+                    } else {
+                        remaining[prev] -= 1;
+
+                        if remaining[prev] == 0 {
+                            let best = conversion[prev];
+                            if best == NO_CONVERSION {
+                                buckets[plies + 1].push(prev);
+                            } else if best != DRAW && best % 2 == 1 {
+                                buckets[std::cmp::max(plies, best as usize) + 1].push(prev);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    entries.truncate(size);
+    for entry in entries.iter_mut() {
+        if *entry == UNKNOWN {
+            *entry = DRAW;
+        }
+    }
+
+    DtmTable {
+        material,
+        layout,
+        entries,
+    }
+}
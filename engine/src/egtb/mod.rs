@@ -0,0 +1,527 @@
+//! Endgame tables generated by retrograde analysis.
+//!
+//! For small material sets we can solve every position exactly without any external files. A
+//! table stores the distance to mate (DTM) of every placement of its pieces, for both sides to
+//! move, and is generated from the tables of the material sets reachable by a capture or a
+//! promotion. See [`gen`] for the generator.
+//!
+//! Tables ignore castling rights, en passant and the 50-move rule, so we only probe positions
+//! without castling rights or an en passant square.
+
+mod gen;
+
+use super::score::Score;
+use super::syzygy::Material;
+
+use core::position::{Piece, PieceType, Player, Position, Square, Variant};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The most pieces, including kings, in a table we generate. A table of `n` pieces has
+/// `2 * 64^n` entries of one byte each.
+pub const MAX_PIECES: usize = 4;
+
+/// The file extension of saved tables.
+pub const EXTENSION: &str = "dtm";
+
+/// Magic bytes at the start of a saved table.
+const MAGIC: &[u8; 6] = b"SBDTM\x01";
+
+/// Entry for a position which is drawn with best play.
+const DRAW: u8 = 255;
+/// Entry for a placement which isn't a legal position.
+const ILLEGAL: u8 = 254;
+
+/// The order of the non-king pieces of each side in a table.
+const PIECE_ORDER: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+/// The distance to mate of a position, in plies, from the point of view of the side to move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dtm {
+    /// The side to move mates in the given number of plies, which is always odd.
+    Win(u8),
+    /// The side to move is mated in the given number of plies, which is always even.
+    Loss(u8),
+    Draw,
+}
+
+impl Dtm {
+    fn from_entry(entry: u8) -> Option<Dtm> {
+        match entry {
+            ILLEGAL => None,
+            DRAW => Some(Dtm::Draw),
+            plies if plies % 2 == 1 => Some(Dtm::Win(plies)),
+            plies => Some(Dtm::Loss(plies)),
+        }
+    }
+
+    /// The search score of this result, or `None` if the mate is too distant to represent.
+    pub fn score(self) -> Option<Score> {
+        match self {
+            Dtm::Win(plies) if plies <= 100 => Some(Score::mate(plies as i8)),
+            Dtm::Loss(plies) if plies <= 100 => Some(Score::mate(-(plies as i8))),
+            Dtm::Draw => Some(Score::zero()),
+            _ => None,
+        }
+    }
+}
+
+/// A piece placed on the board, as `(side, piece type, square)` with side 0 for white.
+type Placed = (usize, PieceType, u8);
+
+/// The order of the pieces of a material set within a table. White's king comes first, then
+/// black's, then white's other pieces and black's other pieces in `PIECE_ORDER`.
+#[derive(Clone, Debug)]
+struct Layout {
+    pieces: Vec<(usize, PieceType)>,
+}
+
+impl Layout {
+    fn new(material: &Material) -> Self {
+        let mut pieces = vec![(0, PieceType::King), (1, PieceType::King)];
+        for side in 0..2 {
+            for pt in PIECE_ORDER {
+                for _ in 0..material.count(side, pt) {
+                    pieces.push((side, pt));
+                }
+            }
+        }
+
+        Self { pieces }
+    }
+
+    /// The number of entries in the table.
+    fn size(&self) -> usize {
+        2 << (6 * self.pieces.len())
+    }
+
+    /// The index of the position with the pieces of the layout on `squares`. Bit 0 is the side to
+    /// move and each piece then takes 6 bits.
+    fn index(&self, squares: &[u8], stm: usize) -> usize {
+        squares
+            .iter()
+            .rev()
+            .fold(0, |idx, &sq| (idx << 6) | sq as usize)
+            << 1
+            | stm
+    }
+
+    fn decode(&self, idx: usize, squares: &mut [u8; MAX_PIECES]) -> usize {
+        for (i, sq) in squares.iter_mut().take(self.pieces.len()).enumerate() {
+            *sq = ((idx >> (1 + 6 * i)) & 63) as u8;
+        }
+        idx & 1
+    }
+
+    /// The index of the position with the given pieces, which must match the layout's material.
+    fn index_of(&self, placed: &[Placed], stm: usize) -> usize {
+        let mut used = [false; MAX_PIECES];
+        let mut squares = [0; MAX_PIECES];
+
+        for (slot, &(side, pt)) in self.pieces.iter().enumerate() {
+            let i = (0..placed.len())
+                .find(|&i| !used[i] && placed[i].0 == side && placed[i].1 == pt)
+                .expect("placed pieces match the layout");
+            used[i] = true;
+            squares[slot] = placed[i].2;
+        }
+
+        self.index(&squares[..self.pieces.len()], stm)
+    }
+
+    /// The position with the pieces of the layout on `squares`, side `stm` to move and the given
+    /// en passant square.
+    fn position(&self, squares: &[u8], stm: usize, ep_square: Option<Square>) -> Position {
+        let mut pieces = [(Piece::None, Square(0)); MAX_PIECES];
+        for (i, (&(side, pt), &sq)) in self.pieces.iter().zip(squares).enumerate() {
+            pieces[i] = (Piece::make(player(side), pt), Square(sq));
+        }
+
+        Position::from_pieces(&pieces[..self.pieces.len()], player(stm), ep_square)
+    }
+}
+
+/// The player of a side, 0 for white.
+fn player(side: usize) -> Player {
+    if side == 0 {
+        Player::WHITE
+    } else {
+        Player::BLACK
+    }
+}
+
+/// The pieces placed on the board of `pos`.
+fn placed(pos: &Position) -> Vec<Placed> {
+    pos.occupied()
+        .map(|sq| {
+            let piece = pos.piece_at_sq(sq);
+            let side = (piece.player() == Player::BLACK) as usize;
+            (side, piece.type_of(), sq.0)
+        })
+        .collect()
+}
+
+/// Whether `material` is stored as it is, rather than with the colours swapped. The side with
+/// more pieces, or stronger pieces when both have as many, is stored as white.
+fn is_canonical(material: &Material) -> bool {
+    let strength = |side| {
+        let mut s = vec![PIECE_ORDER.iter().map(|&pt| material.count(side, pt)).sum()];
+        s.extend(PIECE_ORDER.iter().map(|&pt| material.count(side, pt)));
+        s
+    };
+
+    strength(0) >= strength(1)
+}
+
+/// The material of a list of placed pieces.
+fn material_of(placed: &[Placed]) -> Material {
+    placed
+        .iter()
+        .filter(|(_, pt, _)| *pt != PieceType::King)
+        .fold(Material::from_name("KvK").unwrap(), |m, &(side, pt, _)| {
+            m.with(side, pt)
+        })
+}
+
+/// The DTM table for one material set.
+pub struct DtmTable {
+    material: Material,
+    layout: Layout,
+    entries: Vec<u8>,
+}
+
+impl DtmTable {
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// The number of plies of the longest forced mate in the table.
+    pub fn longest_mate(&self) -> u8 {
+        self.entries
+            .iter()
+            .filter(|&&e| e != DRAW && e != ILLEGAL && e % 2 == 1)
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The number of legal positions in the table which the side to move wins, draws and loses.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.entries
+            .iter()
+            .fold((0, 0, 0), |(w, d, l), &e| match Dtm::from_entry(e) {
+                Some(Dtm::Win(_)) => (w + 1, d, l),
+                Some(Dtm::Draw) => (w, d + 1, l),
+                Some(Dtm::Loss(_)) => (w, d, l + 1),
+                None => (w, d, l),
+            })
+    }
+
+    /// Save the table to `dir`, named after its material, e.g. `KQvK.dtm`.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let path = dir.join(format!("{}.{}", self.material.name(), EXTENSION));
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&self.entries)?;
+        file.flush()
+    }
+
+    /// Load a table saved with `save`.
+    pub fn load(path: &Path) -> io::Result<DtmTable> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let material = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(Material::from_name)
+            .filter(|m| m.piece_count() <= MAX_PIECES && is_canonical(m))
+            .ok_or_else(|| invalid("file name is not a material set we generate"))?;
+        let layout = Layout::new(&material);
+
+        let mut magic = [0; MAGIC.len()];
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a DTM table"));
+        }
+
+        let mut entries = Vec::with_capacity(layout.size());
+        file.read_to_end(&mut entries)?;
+        if entries.len() != layout.size() {
+            return Err(invalid("table has the wrong size"));
+        }
+
+        Ok(DtmTable {
+            material,
+            layout,
+            entries,
+        })
+    }
+}
+
+/// A set of DTM tables.
+#[derive(Default)]
+pub struct EndgameTables {
+    tables: HashMap<u64, DtmTable>,
+}
+
+impl EndgameTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every table saved in `dir`. Files which aren't tables are skipped.
+    pub fn load_dir(dir: &Path) -> io::Result<Self> {
+        let mut tables = Self::new();
+
+        for file in fs::read_dir(dir)?.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(EXTENSION) {
+                if let Ok(table) = DtmTable::load(&path) {
+                    tables.insert(table);
+                }
+            }
+        }
+
+        Ok(tables)
+    }
+
+    pub fn insert(&mut self, table: DtmTable) {
+        self.tables.insert(table.material.key(), table);
+    }
+
+    /// The table for `material`, in either colouring.
+    pub fn get(&self, material: &Material) -> Option<&DtmTable> {
+        self.tables
+            .get(&material.key())
+            .or_else(|| self.tables.get(&material.flipped().key()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Generate the table for `material`, and first any tables it depends on which we don't have
+    /// yet. Returns the materials generated, in order.
+    pub fn generate(&mut self, material: Material) -> Vec<Material> {
+        assert!(material.piece_count() <= MAX_PIECES);
+
+        let material = if is_canonical(&material) {
+            material
+        } else {
+            material.flipped()
+        };
+
+        if material.piece_count() == 2 || self.get(&material).is_some() {
+            return Vec::new();
+        }
+
+        let mut generated = Vec::new();
+        for sub in gen::conversions(&material) {
+            generated.extend(self.generate(sub));
+        }
+
+        let table = gen::generate(material, self);
+        self.insert(table);
+        generated.push(material);
+
+        generated
+    }
+
+    /// Look up the distance to mate of `pos`. Returns `None` if we have no table for it, or it has
+    /// castling rights or an en passant square.
+    pub fn probe(&self, pos: &Position) -> Option<Dtm> {
+        if pos.variant() != Variant::Standard
+            || pos.occupied().popcnt() as usize > MAX_PIECES
+            || !pos.castling_rights().is_empty()
+            || pos.ep_square().is_some()
+        {
+            return None;
+        }
+
+        Dtm::from_entry(self.lookup(&placed(pos), pos.turn().is_black() as usize)?)
+    }
+
+    /// The entry for the position with `placed` pieces and side `stm` to move. Positions with
+    /// bare kings are drawn.
+    fn lookup(&self, placed: &[Placed], stm: usize) -> Option<u8> {
+        if placed.len() == 2 {
+            return Some(DRAW);
+        }
+
+        let material = material_of(placed);
+        if is_canonical(&material) {
+            let table = self.tables.get(&material.key())?;
+            Some(table.entries[table.layout.index_of(placed, stm)])
+        } else {
+            let flipped: Vec<Placed> = placed
+                .iter()
+                .map(|&(side, pt, sq)| (side ^ 1, pt, sq ^ 56))
+                .collect();
+            let table = self.tables.get(&material.flipped().key())?;
+            Some(table.entries[table.layout.index_of(&flipped, stm ^ 1)])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::mono_traits::{All, Legal};
+    use core::movelist::BasicMoveList;
+
+    fn probe(tables: &EndgameTables, fen: &str) -> Option<Dtm> {
+        tables.probe(&Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn pawnless_tables() {
+        core::init::init_globals();
+
+        let mut tables = EndgameTables::new();
+        let generated = tables.generate(Material::from_name("KQvK").unwrap());
+        assert_eq!(generated, vec![Material::from_name("KQvK").unwrap()]);
+        tables.generate(Material::from_name("KvKR").unwrap());
+        assert_eq!(tables.len(), 2);
+
+        // The longest mates are in 10 moves with a queen and 16 with a rook.
+        let kqk = tables.get(&Material::from_name("KQvK").unwrap()).unwrap();
+        assert_eq!(kqk.longest_mate(), 19);
+        let krk = tables.get(&Material::from_name("KRvK").unwrap()).unwrap();
+        assert_eq!(krk.longest_mate(), 31);
+
+        assert_eq!(
+            probe(&tables, "8/8/8/8/8/8/2Q5/k1K5 w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            probe(&tables, "8/8/8/8/8/8/1Q6/k1K5 b - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        // Stalemate
+        assert_eq!(
+            probe(&tables, "8/8/8/8/8/8/2Q5/k1K5 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        assert_eq!(
+            probe(&tables, "k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // The queen is lost.
+        assert_eq!(
+            probe(&tables, "8/8/8/8/8/8/1kQ5/7K b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // The first position with the colours swapped.
+        assert_eq!(
+            probe(&tables, "K1k5/2q5/8/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+    }
+
+    #[test]
+    fn kpk_matches_bitbase() {
+        core::init::init_globals();
+
+        let mut tables = EndgameTables::new();
+        tables.generate(Material::from_name("KPvK").unwrap());
+        assert_eq!(tables.len(), 5);
+
+        let kpk = tables.get(&Material::from_name("KPvK").unwrap()).unwrap();
+        let mut squares = [0; MAX_PIECES];
+
+        for idx in 0..kpk.layout.size() {
+            let Some(dtm) = Dtm::from_entry(kpk.entries[idx]) else {
+                continue;
+            };
+            let stm = kpk.layout.decode(idx, &mut squares);
+
+            let win = core::kpk::probe_squares(
+                core::position::Square(squares[0]),
+                core::position::Square(squares[2]),
+                core::position::Square(squares[1]),
+                stm == 0,
+            );
+            let expected = match (stm, win) {
+                (0, true) => matches!(dtm, Dtm::Win(_)),
+                (1, true) => matches!(dtm, Dtm::Loss(_)),
+                _ => dtm == Dtm::Draw,
+            };
+            assert!(expected, "{:?} at index {}", dtm, idx);
+        }
+    }
+
+    /// The value of `pos` from its moves, looking children up in `tables`. Children with an en
+    /// passant square can't be probed, so their value also comes from their moves.
+    fn value_from_moves(tables: &EndgameTables, pos: &mut Position) -> Dtm {
+        let moves = pos.generate::<BasicMoveList, All, Legal>();
+        let best = moves
+            .iter()
+            .map(|mov| {
+                let mut after = pos.play(mov);
+                let child = match after.ep_square() {
+                    Some(_) => value_from_moves(tables, &mut after),
+                    None => tables.probe(&after).unwrap(),
+                };
+                match child {
+                    Dtm::Win(plies) => Dtm::Loss(plies + 1),
+                    Dtm::Loss(plies) => Dtm::Win(plies + 1),
+                    Dtm::Draw => Dtm::Draw,
+                }
+            })
+            .max_by_key(|dtm| match *dtm {
+                Dtm::Win(plies) => 256 - plies as i32,
+                Dtm::Loss(plies) => plies as i32 - 256,
+                Dtm::Draw => 0,
+            });
+
+        match best {
+            Some(dtm) => dtm,
+            None if pos.in_check() => Dtm::Loss(0),
+            None => Dtm::Draw,
+        }
+    }
+
+    #[test]
+    #[ignore = "generates every four piece table, which takes minutes even in release"]
+    fn kpkp_en_passant() {
+        core::init::init_globals();
+
+        let mut tables = EndgameTables::new();
+        tables.generate(Material::from_name("KPvKP").unwrap());
+
+        // Black loses the pawn to the king, and c5 is met by bxc6 en passant.
+        assert_eq!(
+            probe(&tables, "1K6/2p2k2/8/1P6/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(24))
+        );
+
+        // Every entry agrees with its moves, including the double pushes which allow en passant.
+        let kpkp = tables.get(&Material::from_name("KPvKP").unwrap()).unwrap();
+        let mut squares = [0; MAX_PIECES];
+
+        for idx in 0..kpkp.layout.size() {
+            let Some(dtm) = Dtm::from_entry(kpkp.entries[idx]) else {
+                continue;
+            };
+            let stm = kpkp.layout.decode(idx, &mut squares);
+            let mut pos = kpkp.layout.position(&squares[..4], stm, None);
+
+            assert_eq!(dtm, value_from_moves(&tables, &mut pos), "{}", pos.to_fen());
+        }
+    }
+}
@@ -1,12 +1,22 @@
+use super::egtb::EndgameTables;
+use super::history::Histories;
+use super::options::{Config, EngineOpt, MAX_MULTI_PV};
 use super::search::{Master, Search, Worker};
+use super::syzygy::Tablebases;
 use super::time::TimingMode;
+use super::trace::SharedStats;
 use super::tt::Table;
 use super::uci::{self, Command};
-use core::position::Position;
+use core::mono_traits::{All, Legal};
+use core::mov::Move;
+use core::movelist::BasicMoveList;
+use core::position::{Position, Variant};
 
 use crossbeam_channel::unbounded;
 
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{
     io,
     thread::{self, Scope},
@@ -20,9 +30,15 @@ pub fn launch() {
 
     let stop_flag = AtomicBool::new(false);
     let flag = &stop_flag;
+    let ponder_flag = AtomicBool::new(false);
 
     let tt = Table::new(16);
+    let histories = Mutex::new(Histories::default());
+    let stats = SharedStats::new();
 
+    let mut config = Config::default();
+    let mut tb = Arc::new(Tablebases::new());
+    let mut egtb = Arc::new(EndgameTables::new());
     let mut pos = Position::start_pos();
 
     // Everything happens inside a global thread scope.
@@ -59,44 +75,61 @@ pub fn launch() {
         loop {
             match uci_rx.try_recv() {
                 Ok(Command::Quit) => {
+                    ponder_flag.store(false, Ordering::Relaxed);
                     stop_flag.store(true, Ordering::Relaxed);
                     break;
                 }
                 Ok(Command::Stop) => {
+                    ponder_flag.store(false, Ordering::Relaxed);
                     stop_flag.store(true, Ordering::Relaxed);
                 }
-                Ok(Command::Go(d)) => match d {
-                    TimingMode::Depth(depth) => {
-                        stop_flag.store(false, Ordering::Relaxed);
-                        launch_search(s, flag, None, 1, depth, pos.clone(), &tt);
-                    }
-                    TimingMode::Infinite => {
-                        stop_flag.store(false, Ordering::Relaxed);
-                        launch_search(s, flag, None, 1, MAX_DEPTH, pos.clone(), &tt);
-                    }
-                    TimingMode::Timed(tc) => {
-                        let move_time = tc.to_move_time(pos.move_number(), pos.turn());
-                        let stop_time = std::time::Instant::now()
-                            + std::time::Duration::from_millis(move_time.into());
-                        launch_search(s, flag, Some(stop_time), 1, MAX_DEPTH, pos.clone(), &tt);
-                    }
-                    TimingMode::MoveTime(t) => {
-                        let stop_time =
-                            std::time::Instant::now() + std::time::Duration::from_millis(t as u64);
-                        launch_search(s, flag, Some(stop_time), 1, MAX_DEPTH, pos.clone(), &tt);
+                Ok(Command::PonderHit) => {
+                    // The search restarts its clock once it sees that it's no longer pondering.
+                    ponder_flag.store(false, Ordering::Relaxed);
+                }
+                Ok(Command::Go(go)) => {
+                    let search_moves = legal_moves_from_uci(&pos, &go.search_moves);
+
+                    // Searching every root move instead of the ones asked for would answer a
+                    // different question, so we don't search at all. Every `go` still needs a
+                    // `bestmove`, so we answer with the null move.
+                    if !go.search_moves.is_empty() && search_moves.is_empty() {
+                        println!("info string no legal searchmoves");
+                        println!("bestmove 0000");
+                        continue;
                     }
-                },
-                Ok(Command::SetPosition((fen, moves))) => match Position::from_fen(&fen) {
-                    Ok(mut p) => {
-                        for mov in moves {
-                            if p.make_uci_move(&mov).is_none() {
-                                println!("invalid move {}", mov);
+
+                    stop_flag.store(false, Ordering::Relaxed);
+                    ponder_flag.store(go.ponder, Ordering::Relaxed);
+                    launch_search(
+                        s,
+                        flag,
+                        &ponder_flag,
+                        &go.timing,
+                        1,
+                        pos.clone(),
+                        search_moves,
+                        &tt,
+                        &histories,
+                        &stats,
+                        &tb,
+                        &egtb,
+                        &config,
+                    );
+                }
+                Ok(Command::SetPosition((fen, moves))) => {
+                    match Position::from_fen_variant(&fen, config.variant()) {
+                        Ok(mut p) => {
+                            for mov in moves {
+                                if p.make_uci_move(&mov).is_none() {
+                                    println!("invalid move {}", mov);
+                                }
                             }
+                            pos = p;
                         }
-                        pos = p;
+                        Err(err) => println!("invalid position; {}", err),
                     }
-                    Err(err) => println!("invalid position; {}", err),
-                },
+                }
                 Ok(Command::Display) => println!("{}", pos),
                 Ok(Command::DisplayLichess) => {
                     let fen_url_safe = pos.to_fen().replace(" ", "_");
@@ -121,8 +154,48 @@ pub fn launch() {
                 Ok(Command::Uci) => {
                     println!("id name seaborg 0.0.2");
                     println!("id author George Seabridge");
+                    println!(
+                        "option name UCI_Variant type combo default {}{}",
+                        Variant::Standard,
+                        Variant::ALL
+                            .iter()
+                            .map(|v| format!(" var {}", v))
+                            .collect::<String>()
+                    );
+                    println!("option name SyzygyPath type string default <empty>");
+                    println!("option name SyzygyProbeDepth type spin default 1 min 1 max 100");
+                    println!("option name EgtbPath type string default <empty>");
+                    println!(
+                        "option name MultiPV type spin default 1 min 1 max {}",
+                        MAX_MULTI_PV
+                    );
+                    println!("option name UCI_ShowCurrLine type check default false");
+                    println!("option name Ponder type check default false");
                     println!("uciok");
                 }
+                Ok(Command::SetOption(opt)) => {
+                    match &opt {
+                        EngineOpt::Variant(v) => pos.set_variant(*v),
+                        EngineOpt::SyzygyPath(path) => {
+                            tb = Arc::new(match path {
+                                Some(path) => Tablebases::open(path),
+                                None => Tablebases::new(),
+                            });
+                            println!("info string found {} tablebases", tb.len());
+                        }
+                        EngineOpt::EgtbPath(path) => {
+                            egtb = Arc::new(match path {
+                                Some(path) => {
+                                    EndgameTables::load_dir(Path::new(path)).unwrap_or_default()
+                                }
+                                None => EndgameTables::new(),
+                            });
+                            println!("info string loaded {} endgame tables", egtb.len());
+                        }
+                        _ => {}
+                    }
+                    config.set_option(opt);
+                }
                 Ok(Command::IsReady) => {
                     println!("readyok");
                 }
@@ -133,24 +206,99 @@ pub fn launch() {
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn launch_search<'scope, 'engine>(
     s: &'scope Scope<'scope, 'engine>,
     flag: &'engine AtomicBool,
-    stop_time: Option<std::time::Instant>,
+    ponder_flag: &'engine AtomicBool,
+    timing: &TimingMode,
     num_threads: u8,
-    depth: u8,
     pos: Position,
+    search_moves: Vec<Move>,
     tt: &'engine Table,
+    histories: &'engine Mutex<Histories>,
+    stats: &'engine SharedStats,
+    tb: &Arc<Tablebases>,
+    egtb: &Arc<EndgameTables>,
+    config: &Config,
 ) {
+    let (stop_time, depth) = match timing {
+        TimingMode::Depth(depth) => (None, *depth),
+        TimingMode::Infinite | TimingMode::Nodes(_) => (None, MAX_DEPTH),
+        TimingMode::Timed(tc) => {
+            let move_time = tc.to_move_time(pos.move_number(), pos.turn());
+            let stop_time =
+                std::time::Instant::now() + std::time::Duration::from_millis(move_time.into());
+            (Some(stop_time), MAX_DEPTH)
+        }
+        TimingMode::MoveTime(t) => {
+            let stop_time = std::time::Instant::now() + std::time::Duration::from_millis(*t as u64);
+            (Some(stop_time), MAX_DEPTH)
+        }
+        TimingMode::Mate(n) => {
+            // A mate in `n` moves is `2n - 1` plies deep.
+            let plies = (2 * *n as usize).saturating_sub(1);
+            (None, plies.clamp(1, MAX_DEPTH as usize) as u8)
+        }
+    };
+    let node_limit = match timing {
+        TimingMode::Nodes(n) => Some(*n),
+        _ => None,
+    };
+    let mate_limit = match timing {
+        TimingMode::Mate(n) => Some(*n),
+        _ => None,
+    };
+
+    stats.clear();
+
     for i in 0..num_threads {
         let thread_pos = pos.clone();
+        let search_moves = search_moves.clone();
+        let tb = Arc::clone(tb);
+        let egtb = Arc::clone(egtb);
+        let tb_probe_depth = config.syzygy_probe_depth();
+        let multi_pv = config.multi_pv();
+        let show_curr_line = config.show_curr_line();
+        let ponder = config.ponder();
         s.spawn(move || {
             let mut search = Search::new(thread_pos, flag, stop_time, tt);
+            search.set_tablebases(&tb, tb_probe_depth);
+            search.set_endgame_tables(&egtb);
+            search.set_multi_pv(multi_pv);
+            search.set_search_moves(search_moves);
+            search.set_node_limit(node_limit);
+            search.set_mate_limit(mate_limit);
+            search.set_shared_stats(stats);
+            search.set_show_curr_line(show_curr_line);
+            search.set_ponder_flag(ponder_flag);
+            search.set_report_ponder_move(ponder);
             if i == 0 {
+                // The master thread carries the history tables over from one search to the next.
+                search.set_histories(std::mem::take(&mut *histories.lock().unwrap()));
                 search.run::<Master>(depth);
+                *histories.lock().unwrap() = search.into_histories();
             } else {
                 search.run::<Worker>(depth);
             }
         });
     }
 }
+
+/// Find the legal moves of `pos` given in UCI notation by `moves`, once each, reporting any which
+/// aren't legal.
+fn legal_moves_from_uci(pos: &Position, moves: &[String]) -> Vec<Move> {
+    let legal = pos.generate::<BasicMoveList, All, Legal>();
+    let mut legal_moves = Vec::new();
+
+    for uci in moves {
+        match legal.iter().find(|m| m.to_uci_string() == *uci) {
+            // A repeated move would be searched, and given a MultiPV line, more than once.
+            Some(mov) if !legal_moves.contains(mov) => legal_moves.push(*mov),
+            Some(_) => {}
+            None => println!("info string invalid move {}", uci),
+        }
+    }
+
+    legal_moves
+}
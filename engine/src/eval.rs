@@ -1,4 +1,5 @@
-use core::position::{PieceType, Player, Position};
+use core::kpk::{self, Outcome};
+use core::position::{PieceType, Player, Position, Variant, HILL};
 
 pub const PAWN_VALUE: i16 = 100;
 pub const KNIGHT_VALUE: i16 = 300;
@@ -17,15 +18,38 @@ pub const PIECE_VALUES: [i16; 7] = [
     KING_VALUE,
 ];
 
+/// Bonus on top of the pawn for a king and pawn versus king position which the bitbase says is
+/// won. Large enough that the engine converts rather than trading down into anything else.
+pub const KPK_WIN_BONUS: i16 = 800;
+
+/// Bonus per rank the pawn has advanced in a won king and pawn versus king position, so that the
+/// search makes progress towards promotion.
+pub const KPK_PAWN_RANK_BONUS: i16 = 20;
+
+/// Bonus for having given the indexed number of checks in Three-check. Three checks ends the
+/// game, so never appears in a static evaluation.
+pub const CHECKS_GIVEN_BONUS: [i16; 4] = [0, 150, 450, 0];
+
+/// Bonus for a king at the indexed distance from the nearest hill square in King of the Hill. A
+/// king on the hill ends the game, so distance zero never appears in a static evaluation.
+pub const HILL_DISTANCE_BONUS: [i16; 7] = [0, 250, 80, 20, 0, 0, 0];
+
 /// Adds static evaluation functionality to a type representing a chess position.
 pub trait Evaluation {
     /// Simple material evaluation
     fn material_eval(&self) -> i16;
+
+    /// Evaluation of the terms specific to the position's variant. Zero in standard chess.
+    fn variant_eval(&self) -> i16;
 }
 
 impl Evaluation for Position {
     fn material_eval(&self) -> i16 {
-        material_evaluation(self)
+        kpk_evaluation(self).unwrap_or_else(|| material_evaluation(self))
+    }
+
+    fn variant_eval(&self) -> i16 {
+        variant_evaluation(self)
     }
 }
 
@@ -42,6 +66,61 @@ fn material_evaluation(pos: &Position) -> i16 {
         - pos.piece_bb(Player::BLACK, PieceType::Queen).popcnt() as i16 * QUEEN_VALUE
 }
 
+/// Exact evaluation of king and pawn versus king positions from the bitbase. Drawn positions are
+/// worth nothing, however far the pawn is advanced.
+fn kpk_evaluation(pos: &Position) -> Option<i16> {
+    if pos.variant() != Variant::Standard {
+        return None;
+    }
+
+    let outcome = kpk::probe(pos)?;
+    let strong = if pos.piece_bb(Player::WHITE, PieceType::Pawn).is_not_empty() {
+        Player::WHITE
+    } else {
+        Player::BLACK
+    };
+
+    let value = match outcome {
+        Outcome::Draw => 0,
+        Outcome::Win => {
+            let psq = pos.piece_bb(strong, PieceType::Pawn).to_square();
+            let rank = match strong {
+                Player::WHITE => psq.rank(),
+                Player::BLACK => 7 - psq.rank(),
+            };
+            PAWN_VALUE + KPK_WIN_BONUS + rank as i16 * KPK_PAWN_RANK_BONUS
+        }
+    };
+
+    Some(match strong {
+        Player::WHITE => value,
+        Player::BLACK => -value,
+    })
+}
+
+fn variant_evaluation(pos: &Position) -> i16 {
+    match pos.variant() {
+        Variant::Standard => 0,
+        Variant::ThreeCheck => {
+            checks_given_bonus(pos.checks_given(Player::WHITE))
+                - checks_given_bonus(pos.checks_given(Player::BLACK))
+        }
+        Variant::KingOfTheHill => {
+            hill_distance_bonus(pos, Player::WHITE) - hill_distance_bonus(pos, Player::BLACK)
+        }
+    }
+}
+
+fn checks_given_bonus(checks: u8) -> i16 {
+    CHECKS_GIVEN_BONUS[std::cmp::min(checks as usize, 3)]
+}
+
+fn hill_distance_bonus(pos: &Position, player: Player) -> i16 {
+    let ksq = pos.king_sq(player);
+    let distance = HILL.map(|sq| ksq.distance(sq)).min().unwrap_or(0);
+    HILL_DISTANCE_BONUS[distance as usize]
+}
+
 /// The material evaluation of `PieceType`.
 pub fn piece_value(piece_type: PieceType) -> i16 {
     unsafe { *PIECE_VALUES.get_unchecked(piece_type as usize) }
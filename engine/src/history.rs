@@ -1,6 +1,7 @@
 //! History tables.
 
-use core::position::{Player, Square};
+use core::mov::Move;
+use core::position::{Piece, PieceType, Player, Square, PIECE_TYPE_CNT};
 
 /// Butterfly boards.
 ///
@@ -104,14 +105,52 @@ where
     }
 }
 
-/// A structure storing two butterfly tables of `u32`s, used to record the history value of moves
-/// during search.
+impl<T> Butterfly<T> {
+    /// Get a mutable reference to the value indexed by `from` and `to`.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the squares passed are not valid squares (i.e. they satisfy
+    /// `square.is_okay() == true`).
+    pub fn get_mut(&mut self, from: Square, to: Square) -> &mut T {
+        assert!(from.is_okay());
+        assert!(to.is_okay());
+
+        // SAFETY: bounds have been checked above.
+        unsafe {
+            self.data
+                .get_unchecked_mut(from.0 as usize)
+                .get_unchecked_mut(to.0 as usize)
+        }
+    }
+}
+
+/// The largest magnitude a history score can reach.
+pub const MAX_HISTORY: i32 = 16384;
+
+/// The history bonus for a move which caused a cutoff at the given remaining depth. Moves which
+/// were searched before it without a cutoff receive the same amount as a malus.
+pub fn history_bonus(depth: u8) -> i32 {
+    std::cmp::min(32 * depth as i32 * depth as i32, 1536)
+}
+
+/// Apply `bonus` to a history score with "gravity": the update shrinks as the score approaches
+/// `MAX_HISTORY`, so scores saturate rather than overflow, and recent results outweigh old ones.
+#[inline]
+fn apply_gravity(entry: &mut i16, bonus: i32) {
+    let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+    let value = *entry as i32;
+    *entry = (value + bonus - value * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+/// A structure storing two butterfly tables of `i16`s, used to record the history value of quiet
+/// moves during search.
 ///
-/// This data structure occupies about 32KB of memory.
+/// This data structure occupies about 16KB of memory.
 #[derive(Debug)]
 pub struct HistoryTable {
-    white: Butterfly<u32>,
-    black: Butterfly<u32>,
+    white: Butterfly<i16>,
+    black: Butterfly<i16>,
 }
 
 impl HistoryTable {
@@ -122,36 +161,243 @@ impl HistoryTable {
         }
     }
 
-    pub fn inc(&mut self, from: Square, to: Square, amt: u32, side: Player) {
+    /// Apply a bonus, or a malus if negative, to the history of a move.
+    pub fn update(&mut self, from: Square, to: Square, bonus: i32, side: Player) {
         match side {
-            Player::WHITE => self.white.inc(from, to, amt),
-            Player::BLACK => self.black.inc(from, to, amt),
+            Player::WHITE => apply_gravity(self.white.get_mut(from, to), bonus),
+            Player::BLACK => apply_gravity(self.black.get_mut(from, to), bonus),
         }
     }
 
-    pub fn get(&self, from: Square, to: Square, side: Player) -> u32 {
+    pub fn get(&self, from: Square, to: Square, side: Player) -> i16 {
         match side {
             Player::WHITE => self.white.get(from, to),
             Player::BLACK => self.black.get(from, to),
         }
     }
 
-    pub unsafe fn inc_unchecked(&mut self, from: Square, to: Square, amt: u32, side: Player) {
-        match side {
-            Player::WHITE => self.white.inc_unchecked(from, to, amt),
-            Player::BLACK => self.black.inc_unchecked(from, to, amt),
+    /// Halve every score, so that what we learnt in earlier searches still orders moves but soon
+    /// gives way to what the next search finds.
+    pub fn age(&mut self) {
+        for board in [&mut self.white, &mut self.black] {
+            board.data.iter_mut().flatten().for_each(|h| *h /= 2);
         }
     }
+}
 
-    pub unsafe fn get_unchecked(&self, from: Square, to: Square, side: Player) -> u32 {
-        match side {
-            Player::WHITE => self.white.get_unchecked(from, to),
-            Player::BLACK => self.black.get_unchecked(from, to),
+impl Default for HistoryTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Continuation history: the history of quiet moves indexed by the move played before them. Both
+/// moves are identified by their piece and destination square, so that the table learns which
+/// replies work against which moves.
+///
+/// The same table serves for the move one ply back (counter-move history) and two plies back
+/// (follow-up history). It occupies about 1.4MB of memory.
+#[derive(Debug)]
+pub struct ContinuationHistory {
+    data: Vec<i16>,
+}
+
+impl ContinuationHistory {
+    pub fn new() -> Self {
+        ContinuationHistory {
+            data: vec![0; PIECE_TYPE_CNT * 64 * PIECE_TYPE_CNT * 64],
+        }
+    }
+
+    #[inline]
+    fn index(prev: (Piece, Square), piece: Piece, to: Square) -> usize {
+        assert!(prev.1.is_okay());
+        assert!(to.is_okay());
+
+        ((prev.0 as usize * 64 + prev.1 .0 as usize) * PIECE_TYPE_CNT + piece as usize) * 64
+            + to.0 as usize
+    }
+
+    /// Apply a bonus, or a malus if negative, to the history of `piece` moving to `to` after the
+    /// move `prev`.
+    pub fn update(&mut self, prev: (Piece, Square), piece: Piece, to: Square, bonus: i32) {
+        apply_gravity(&mut self.data[Self::index(prev, piece, to)], bonus);
+    }
+
+    pub fn get(&self, prev: (Piece, Square), piece: Piece, to: Square) -> i16 {
+        self.data[Self::index(prev, piece, to)]
+    }
+
+    /// Halve every score.
+    pub fn age(&mut self) {
+        self.data.iter_mut().for_each(|h| *h /= 2);
+    }
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Capture history: the history of captures indexed by the moving piece, the destination square and
+/// the type of the captured piece. It orders captures which SEE can't tell apart.
+///
+/// This data structure occupies about 12KB of memory.
+#[derive(Debug)]
+pub struct CaptureHistory {
+    data: [[[i16; 7]; 64]; PIECE_TYPE_CNT],
+}
+
+impl CaptureHistory {
+    pub fn new() -> Self {
+        CaptureHistory {
+            data: [[[0; 7]; 64]; PIECE_TYPE_CNT],
+        }
+    }
+
+    /// Apply a bonus, or a malus if negative, to the history of `piece` capturing a piece of type
+    /// `captured` on `to`.
+    pub fn update(&mut self, piece: Piece, to: Square, captured: PieceType, bonus: i32) {
+        assert!(to.is_okay());
+        apply_gravity(
+            &mut self.data[piece as usize][to.0 as usize][captured as usize],
+            bonus,
+        );
+    }
+
+    pub fn get(&self, piece: Piece, to: Square, captured: PieceType) -> i16 {
+        assert!(to.is_okay());
+        self.data[piece as usize][to.0 as usize][captured as usize]
+    }
+
+    /// Halve every score.
+    pub fn age(&mut self) {
+        self.data
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .for_each(|h| *h /= 2);
+    }
+}
+
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counter moves: for each move, identified by its piece and destination square, the quiet reply
+/// which last caused a beta cutoff.
+///
+/// This data structure occupies about 3KB of memory.
+#[derive(Debug)]
+pub struct CounterMoveTable {
+    data: [[Move; 64]; PIECE_TYPE_CNT],
+}
+
+impl CounterMoveTable {
+    pub fn new() -> Self {
+        CounterMoveTable {
+            data: [[Move::null(); 64]; PIECE_TYPE_CNT],
         }
     }
 
-    /// Reset the tables to zeros.
-    pub fn reset(&mut self) {
-        *self = Self::new()
+    /// Record `mov` as the counter to the move `prev`.
+    pub fn store(&mut self, prev: (Piece, Square), mov: Move) {
+        assert!(prev.1.is_okay());
+        self.data[prev.0 as usize][prev.1 .0 as usize] = mov;
+    }
+
+    /// The counter to the move `prev`, if one has been stored. It may not be legal in the current
+    /// position.
+    pub fn get(&self, prev: (Piece, Square)) -> Option<Move> {
+        assert!(prev.1.is_okay());
+        Some(self.data[prev.0 as usize][prev.1 .0 as usize]).filter(|m| !m.is_null())
+    }
+}
+
+impl Default for CounterMoveTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The tables a search learns move ordering from. The engine keeps them from one search to the
+/// next, so that each move starts with what the search for the previous one learnt.
+#[derive(Debug, Default)]
+pub struct Histories {
+    pub quiet: HistoryTable,
+    pub continuation: ContinuationHistory,
+    pub capture: CaptureHistory,
+    pub counter_moves: CounterMoveTable,
+}
+
+impl Histories {
+    /// Scale down the history scores before a new search. The counter moves are kept, as each is
+    /// replaced as soon as another move causes a cutoff.
+    pub fn age(&mut self) {
+        self.quiet.age();
+        self.continuation.age();
+        self.capture.age();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_saturates() {
+        let mut table = HistoryTable::new();
+        let (from, to) = (Square(12), Square(28));
+
+        for _ in 0..1000 {
+            table.update(from, to, history_bonus(20), Player::WHITE);
+        }
+        let high = table.get(from, to, Player::WHITE);
+        assert!(high > 0 && high as i32 <= MAX_HISTORY);
+
+        for _ in 0..1000 {
+            table.update(from, to, -history_bonus(20), Player::WHITE);
+        }
+        let low = table.get(from, to, Player::WHITE);
+        assert!(low < 0 && low as i32 >= -MAX_HISTORY);
+
+        // Each side has its own table.
+        assert_eq!(table.get(from, to, Player::BLACK), 0);
+    }
+
+    #[test]
+    fn aging_halves_scores() {
+        let mut histories = Histories::default();
+        let (from, to) = (Square(12), Square(28));
+        let prev = (Piece::WhiteKnight, Square(21));
+
+        histories.quiet.update(from, to, 1000, Player::WHITE);
+        histories
+            .continuation
+            .update(prev, Piece::BlackPawn, to, -1000);
+        histories
+            .capture
+            .update(Piece::WhiteQueen, to, PieceType::Rook, 1000);
+        let quiet = histories.quiet.get(from, to, Player::WHITE);
+        let continuation = histories.continuation.get(prev, Piece::BlackPawn, to);
+        let capture = histories
+            .capture
+            .get(Piece::WhiteQueen, to, PieceType::Rook);
+
+        histories.age();
+        assert_eq!(histories.quiet.get(from, to, Player::WHITE), quiet / 2);
+        assert_eq!(
+            histories.continuation.get(prev, Piece::BlackPawn, to),
+            continuation / 2
+        );
+        assert_eq!(
+            histories
+                .capture
+                .get(Piece::WhiteQueen, to, PieceType::Rook),
+            capture / 2
+        );
     }
 }
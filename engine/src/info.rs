@@ -1,5 +1,6 @@
 //! An engine info report.
 use super::score::Score;
+use super::tt::Bound;
 use core::mov::Move;
 
 /// A UCI info report.
@@ -7,6 +8,8 @@ use core::mov::Move;
 pub enum Info {
     Pv(PvInfo),
     CurrMove(CurrMoveInfo),
+    CurrLine(CurrLineInfo),
+    Stats(StatsInfo),
 }
 
 impl std::fmt::Display for Info {
@@ -15,6 +18,8 @@ impl std::fmt::Display for Info {
         match self {
             Pv(i) => i.fmt(f),
             CurrMove(i) => i.fmt(f),
+            CurrLine(i) => i.fmt(f),
+            Stats(i) => i.fmt(f),
         }
     }
 }
@@ -25,11 +30,17 @@ impl std::fmt::Display for Info {
 #[derive(Debug)]
 pub struct PvInfo {
     pub(super) depth: u8,
+    pub(super) seldepth: u8,
+    /// The rank of this variation among those searched, starting from 1.
+    pub(super) multipv: usize,
     pub(super) time: usize,
     pub(super) nodes: usize,
     pub(super) pv: String,
     pub(super) score: Score,
+    /// Whether `score` is exact, or only a bound because the search failed outside its window.
+    pub(super) bound: Bound,
     pub(super) hashfull: u16,
+    pub(super) tbhits: usize,
     pub(super) nps: u32,
 }
 
@@ -37,13 +48,18 @@ impl std::fmt::Display for PvInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "info ")?;
         write!(f, "depth {} ", self.depth)?;
-        // write!(f, "seldepth {}", self.seldepth)?; // TODO
-        write!(f, "multipv 1 ")?; // TODO: we don't have an option to send further PVs, so always
-                                  // send this.
+        write!(f, "seldepth {} ", self.seldepth)?;
+        write!(f, "multipv {} ", self.multipv)?;
         write!(f, "score {} ", self.score)?;
+        match self.bound {
+            Bound::Lower => write!(f, "lowerbound ")?,
+            Bound::Upper => write!(f, "upperbound ")?,
+            Bound::Exact => {}
+        }
         write!(f, "nodes {} ", self.nodes)?;
         write!(f, "nps {} ", self.nps)?;
         write!(f, "hashfull {} ", self.hashfull)?;
+        write!(f, "tbhits {} ", self.tbhits)?;
         write!(f, "time {} ", self.time)?;
         write!(f, "pv {}", self.pv)
     }
@@ -65,3 +81,44 @@ impl std::fmt::Display for CurrMoveInfo {
         write!(f, "currmovenumber {} ", self.number)
     }
 }
+
+/// A UCI report of the line currently being searched, sent when `UCI_ShowCurrLine` is on.
+#[derive(Debug)]
+pub struct CurrLineInfo {
+    pub(super) currline: String,
+}
+
+impl std::fmt::Display for CurrLineInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "info ")?;
+        write!(f, "currline {}", self.currline)
+    }
+}
+
+/// A UCI report of the search statistics alone.
+///
+/// These are issued periodically while an iteration is in progress, so that a long search shows
+/// signs of life between PV reports.
+#[derive(Debug)]
+pub struct StatsInfo {
+    pub(super) depth: u8,
+    pub(super) seldepth: u8,
+    pub(super) time: usize,
+    pub(super) nodes: usize,
+    pub(super) hashfull: u16,
+    pub(super) tbhits: usize,
+    pub(super) nps: u32,
+}
+
+impl std::fmt::Display for StatsInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "info ")?;
+        write!(f, "depth {} ", self.depth)?;
+        write!(f, "seldepth {} ", self.seldepth)?;
+        write!(f, "nodes {} ", self.nodes)?;
+        write!(f, "nps {} ", self.nps)?;
+        write!(f, "hashfull {} ", self.hashfull)?;
+        write!(f, "tbhits {} ", self.tbhits)?;
+        write!(f, "time {}", self.time)
+    }
+}
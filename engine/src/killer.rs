@@ -60,6 +60,16 @@ impl KillerTable {
         (ret1.0, ret2.0)
     }
 
+    /// Whether `mov` is one of the killer moves stored for `draft`.
+    pub fn contains(&self, mov: &Move, draft: u8) -> bool {
+        if draft == 0 || draft as usize > self.data.len() {
+            return false;
+        }
+
+        let entry = &self.data[draft as usize - 1];
+        entry.mov_a.0 == *mov || entry.mov_b.0 == *mov
+    }
+
     /// Store a killer move for a given draft (number of ply down from the root). This function
     /// does not accept `draft == 0`, since we do not have killer moves at the root node.
     pub fn store(&mut self, killer: Move, draft: u8) {
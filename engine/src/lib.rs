@@ -2,11 +2,14 @@
 #![feature(slice_from_ptr_range)]
 #![feature(iter_intersperse)]
 
+pub mod dfpn;
+pub mod egtb;
 pub mod engine;
 pub mod eval;
 pub mod history;
 pub mod info;
 pub mod killer;
+pub mod lmr;
 pub mod options;
 pub mod ordering;
 pub mod perft;
@@ -15,6 +18,7 @@ pub mod score;
 pub mod search;
 pub mod see;
 pub mod session;
+pub mod syzygy;
 pub mod time;
 pub mod trace;
 pub mod tt;
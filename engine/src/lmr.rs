@@ -0,0 +1,68 @@
+//! Late move reductions.
+//!
+//! With good move ordering, a cutoff usually comes from one of the first few moves searched, so we
+//! search moves late in the list to a reduced depth. If a reduced search unexpectedly beats alpha,
+//! the move is searched again at full depth.
+
+/// The largest depth and move count covered by the table. Larger values share the last entry.
+const MAX: usize = 64;
+
+/// Table of base reductions, indexed by remaining depth and move number.
+///
+/// The reduction grows with the logarithm of both, so that deep searches reduce late moves
+/// aggressively while shallow ones barely reduce at all.
+pub struct ReductionTable {
+    data: [[u8; MAX]; MAX],
+}
+
+impl ReductionTable {
+    pub fn new() -> Self {
+        let mut data = [[0; MAX]; MAX];
+
+        for (depth, row) in data.iter_mut().enumerate().skip(1) {
+            for (moves, r) in row.iter_mut().enumerate().skip(1) {
+                let x = 0.75 + (depth as f64).ln() * (moves as f64).ln() / 2.25;
+                *r = x as u8;
+            }
+        }
+
+        Self { data }
+    }
+
+    /// The base reduction for the `move_count`th move searched at `depth`.
+    #[inline(always)]
+    pub fn get(&self, depth: u8, move_count: u8) -> u8 {
+        let d = std::cmp::min(depth as usize, MAX - 1);
+        let m = std::cmp::min(move_count as usize, MAX - 1);
+        self.data[d][m]
+    }
+}
+
+impl Default for ReductionTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_count() {
+        let table = ReductionTable::new();
+
+        assert_eq!(table.get(1, 1), 0);
+        assert_eq!(table.get(2, 2), 0);
+        assert_eq!(table.get(1, 60), 0);
+        assert!(table.get(20, 40) >= 4);
+        assert_eq!(table.get(200, 200), table.get(63, 63));
+
+        for depth in 1..MAX as u8 {
+            for moves in 1..MAX as u8 {
+                assert!(table.get(depth + 1, moves) >= table.get(depth, moves));
+                assert!(table.get(depth, moves + 1) >= table.get(depth, moves));
+            }
+        }
+    }
+}
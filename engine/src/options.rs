@@ -1,3 +1,8 @@
+use core::position::Variant;
+
+/// The largest number of principal variations which can be requested with the `MultiPV` option.
+pub const MAX_MULTI_PV: usize = 256;
+
 /// Engine configuration.
 #[derive(Debug)]
 pub struct Config {
@@ -5,12 +10,54 @@ pub struct Config {
     debug_mode: bool,
     /// Transposition table configuration.
     hash: HashConfig,
+    /// The chess variant being played.
+    variant: Variant,
+    /// Directories holding Syzygy tablebases, separated as in the `PATH` environment variable.
+    syzygy_path: Option<String>,
+    /// The minimum remaining depth at which to probe the tablebases during search.
+    syzygy_probe_depth: u8,
+    /// Directory holding DTM endgame tables made by the `egtb` command.
+    egtb_path: Option<String>,
+    /// The number of principal variations to search and report.
+    multi_pv: usize,
+    /// Whether to report the line being searched in the periodic info reports.
+    show_curr_line: bool,
+    /// Whether the GUI may ask us to ponder, in which case we suggest a move to ponder on.
+    ponder: bool,
 }
 
 impl Config {
     pub fn debug_mode(&self) -> bool {
         self.debug_mode
     }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn syzygy_path(&self) -> Option<&str> {
+        self.syzygy_path.as_deref()
+    }
+
+    pub fn syzygy_probe_depth(&self) -> u8 {
+        self.syzygy_probe_depth
+    }
+
+    pub fn egtb_path(&self) -> Option<&str> {
+        self.egtb_path.as_deref()
+    }
+
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+
+    pub fn show_curr_line(&self) -> bool {
+        self.show_curr_line
+    }
+
+    pub fn ponder(&self) -> bool {
+        self.ponder
+    }
 }
 
 impl Default for Config {
@@ -18,6 +65,13 @@ impl Default for Config {
         Self {
             debug_mode: false,
             hash: Default::default(),
+            variant: Default::default(),
+            syzygy_path: None,
+            syzygy_probe_depth: 1,
+            egtb_path: None,
+            multi_pv: 1,
+            show_curr_line: false,
+            ponder: false,
         }
     }
 }
@@ -53,6 +107,13 @@ impl Config {
         match o {
             EngineOpt::Hash(v) => self.hash.set_size(v),
             EngineOpt::DebugMode(b) => self.debug_mode = b,
+            EngineOpt::Variant(v) => self.variant = v,
+            EngineOpt::SyzygyPath(p) => self.syzygy_path = p,
+            EngineOpt::SyzygyProbeDepth(d) => self.syzygy_probe_depth = d,
+            EngineOpt::EgtbPath(p) => self.egtb_path = p,
+            EngineOpt::MultiPv(n) => self.multi_pv = n,
+            EngineOpt::ShowCurrLine(b) => self.show_curr_line = b,
+            EngineOpt::Ponder(b) => self.ponder = b,
         }
     }
 }
@@ -64,4 +125,21 @@ pub enum EngineOpt {
     Hash(usize),
     /// Whether debug mode is turned on.
     DebugMode(bool),
+    /// The chess variant to play, set with the `UCI_Variant` option.
+    Variant(Variant),
+    /// Directories to load Syzygy tablebases from, set with the `SyzygyPath` option. `None`
+    /// unloads the tablebases.
+    SyzygyPath(Option<String>),
+    /// The minimum remaining depth at which to probe the tablebases, set with the
+    /// `SyzygyProbeDepth` option.
+    SyzygyProbeDepth(u8),
+    /// Directory to load DTM endgame tables from, set with the `EgtbPath` option. `None` unloads
+    /// the tables.
+    EgtbPath(Option<String>),
+    /// The number of principal variations to search and report, set with the `MultiPV` option.
+    MultiPv(usize),
+    /// Whether to report the line being searched, set with the `UCI_ShowCurrLine` option.
+    ShowCurrLine(bool),
+    /// Whether the GUI may ask us to ponder, set with the `Ponder` option.
+    Ponder(bool),
 }
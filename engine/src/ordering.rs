@@ -16,6 +16,11 @@ use std::slice::Iter as SliceIter;
 
 pub type ScoredMove = (Move, i16);
 
+/// Capture scores at or above this are good captures, scores from zero up to it are equal captures
+/// and negative scores are bad captures. A `Loader` maps the SEE of each capture into these bands,
+/// which leaves it room to order the captures within a band.
+pub const GOOD_CAPTURE: i16 = 8192;
+
 /// An entry in the move ordering `ArrayVec` buffer.
 #[derive(Debug)]
 struct Entry {
@@ -182,7 +187,8 @@ impl<'a> Iterator for KillerIter<'a> {
 }
 
 /// An iterator over the quiet moves. These are scored and they must each be checked against
-/// the hash table move to ensure that they haven't already been yielded, to avoid a re-search.
+/// the hash table, killer and counter moves to ensure that they haven't already been yielded, to
+/// avoid a re-search.
 ///
 /// This iterator assumes that all moves in the quiet segment are set to `yielded = false` - it
 /// does not check this. (However, `yielded = false` may not reflect that the move has been yielded
@@ -191,6 +197,7 @@ struct QuietsIter<'a> {
     quiets_sel_sort: SelectionSort<'a>,
     hash_segment: Segment<'a>,
     killer_segment: Segment<'a>,
+    counter_segment: Segment<'a>,
 }
 
 impl<'a> Iterator for QuietsIter<'a> {
@@ -214,6 +221,13 @@ impl<'a> Iterator for QuietsIter<'a> {
                     }
                 }
 
+                // If this move matches the counter move, skip it.
+                for cm in self.counter_segment.iter() {
+                    if *mov == cm.sm.0 {
+                        return self.next();
+                    }
+                }
+
                 // Now, we are safe to return it.
                 Some(mov)
             }
@@ -245,13 +259,14 @@ pub struct OrderedMoves {
     buf: ScoredMoveList,
     /// The index of the start of the current segment. A new segment is created each time the
     /// `Phase` increments. So in practice, we'll have a segment for the `HashMove`, a segment for
-    /// promotions, a segment for captures, a segment for killer moves, and a segment for quiet
-    /// moves.
+    /// promotions, a segment for captures, a segment for killer moves, a segment for the counter
+    /// move, and a segment for quiet moves.
     segment_start: usize,
     hash_segment: Range<usize>,
     promo_segment: Range<usize>,
     capt_segment: Range<usize>,
     killer_segment: Range<usize>,
+    counter_segment: Range<usize>,
     quiet_segment: Range<usize>,
     underpromo_segment: Range<usize>,
     phase: Phase,
@@ -274,6 +289,8 @@ pub enum Phase {
     /// another variation, and is therefore considered likely to have a similarly positive effect
     /// in this position too.
     Killers,
+    /// The quiet move which last caused a cutoff in reply to the opponent's previous move.
+    CounterMove,
     /// All other quiet (i.e. non-capturing or promoting) moves. These are further sorted according
     /// to the history heuristic, which scores moves based on how many times have they have caused
     /// cutoffs elsewhere in the tree.
@@ -301,6 +318,10 @@ pub trait Loader {
     /// Load the hash move(s) into the passed `MoveList`.
     fn load_hash(&mut self, _movelist: &mut ScoredMoveList) {}
 
+    /// Load moves which must not be yielded at all into the passed `MoveList`, such as the move
+    /// excluded from a singular extension search.
+    fn load_excluded(&mut self, _movelist: &mut ScoredMoveList) {}
+
     /// Load promotions into the passed `MoveList`.
     ///
     /// This function should only load queen promotions into the list. This saves time on move
@@ -319,6 +340,9 @@ pub trait Loader {
     /// Load killers into the passed `MoveList`.
     fn load_killers(&mut self, _movelist: &mut ScoredMoveList) {}
 
+    /// Load the counter move into the passed `MoveList`.
+    fn load_counter(&mut self, _movelist: &mut ScoredMoveList) {}
+
     /// Load quiet moves into the passed `MoveList`.
     fn load_quiets(&mut self, _movelist: &mut ScoredMoveList) {}
 
@@ -336,6 +360,7 @@ impl OrderedMoves {
             promo_segment: Range::default(),
             capt_segment: Range::default(),
             killer_segment: Range::default(),
+            counter_segment: Range::default(),
             quiet_segment: Range::default(),
             underpromo_segment: Range::default(),
             phase: Phase::Pre,
@@ -394,6 +419,18 @@ impl OrderedMoves {
         self.segment_start = self.buf.len();
     }
 
+    /// Record the location of the counter move segment in the underlying buffer, assuming that it
+    /// starts at `self.segment_start` and ends at `self.buf.len()`. This method therefore assumes
+    /// that it is being called immediately after the relevant moves have been loaded.
+    fn set_counter_segment(&mut self) {
+        self.counter_segment = Range {
+            start: self.segment_start,
+            end: self.buf.len(),
+        };
+
+        self.segment_start = self.buf.len();
+    }
+
     /// Record the location of the quiet move segment in the underlying buffer, assuming that it
     /// starts at `self.segment_start` and ends at `self.buf.len()`. This method therefore assumes
     /// that it is being called immediately after the relevant moves have been loaded.
@@ -454,6 +491,15 @@ impl OrderedMoves {
         unsafe { self.segment_from_range(self.killer_segment.clone()) }
     }
 
+    /// Return the counter move segment.
+    #[inline]
+    fn counter_segment(&self) -> Segment<'_> {
+        // SAFETY: the segment `Range` starts as `0..0`, which is always fine for us to get.
+        // We only ever change the `Range` when we know that moves have been placed in that
+        // location, so we are safe to derefence.
+        unsafe { self.segment_from_range(self.counter_segment.clone()) }
+    }
+
     /// Return the quiet segment.
     #[inline]
     fn quiets_segment(&self) -> Segment<'_> {
@@ -569,7 +615,19 @@ impl OrderedMoves {
                 }
                 HashTable => {
                     loader.load_hash(&mut self.buf);
+                    let excluded_start = self.buf.len() - self.segment_start;
+                    loader.load_excluded(&mut self.buf);
                     self.set_hash_segment();
+
+                    // Excluded moves live in the hash segment, so that the later phases skip
+                    // them, but they are marked as yielded so that they are never searched.
+                    for entry in &self.hash_segment()[excluded_start..] {
+                        // SAFETY: we can mark entries as yielded, since no-one outside the API has
+                        // a reference to the `yielded` flag.
+                        unsafe {
+                            *entry.yielded.get() = true;
+                        }
+                    }
                 }
                 QueenPromotions => {
                     loader.load_promotions(&mut self.buf);
@@ -589,6 +647,12 @@ impl OrderedMoves {
                     self.set_killer_segment();
                     self.dedup_segments(self.killer_segment(), self.hash_segment());
                 }
+                CounterMove => {
+                    loader.load_counter(&mut self.buf);
+                    self.set_counter_segment();
+                    self.dedup_segments(self.counter_segment(), self.hash_segment());
+                    self.dedup_segments(self.counter_segment(), self.killer_segment());
+                }
                 Quiet => {
                     loader.load_quiets(&mut self.buf);
                     self.set_quiet_segment();
@@ -674,13 +738,13 @@ impl OrderedMoves {
     #[inline]
     fn good_capt_iter<'a>(&'a self) -> SelectionSort<'a> {
         let segment = self.capt_segment();
-        SelectionSort::from(segment, |sm| sm.1 > 0)
+        SelectionSort::from(segment, |sm| sm.1 >= GOOD_CAPTURE)
     }
 
     #[inline]
     fn equal_capt_iter<'a>(&'a self) -> SelectionSort<'a> {
         let segment = self.capt_segment();
-        SelectionSort::from(segment, |sm| sm.1 == 0)
+        SelectionSort::from(segment, |sm| (0..GOOD_CAPTURE).contains(&sm.1))
     }
 
     #[inline]
@@ -694,16 +758,23 @@ impl OrderedMoves {
         }
     }
 
+    #[inline]
+    fn counter_iter<'a>(&'a self) -> SelectionSort<'a> {
+        SelectionSort::from(self.counter_segment(), |_| true)
+    }
+
     #[inline]
     fn quiets_iter<'a>(&'a self) -> QuietsIter<'a> {
         let hash_segment = self.hash_segment();
         let killer_segment = self.killer_segment();
+        let counter_segment = self.counter_segment();
         let quiets_segment = self.quiets_segment();
 
         QuietsIter {
             quiets_sel_sort: SelectionSort::from(quiets_segment, |_| true),
             hash_segment,
             killer_segment,
+            counter_segment,
         }
     }
 
@@ -733,6 +804,7 @@ enum IterInner<'a> {
     GoodCaptures(SelectionSort<'a>),
     EqualCaptures(SelectionSort<'a>),
     Killers(KillerIter<'a>),
+    CounterMove(SelectionSort<'a>),
     Quiet(QuietsIter<'a>),
     BadCaptures(SelectionSort<'a>),
     Underpromotions(PromotionsIter<'a>),
@@ -753,6 +825,7 @@ impl<'a> Iterator for IterInner<'a> {
             GoodCaptures(i) => i.next(),
             EqualCaptures(i) => i.next(),
             Killers(i) => i.next(),
+            CounterMove(i) => i.next(),
             Quiet(i) => i.next(),
             BadCaptures(i) => i.next(),
             Underpromotions(i) => i.next(),
@@ -785,6 +858,7 @@ impl<'a> IntoIterator for &'a OrderedMoves {
             GoodCaptures => IterInner::GoodCaptures(self.good_capt_iter()),
             EqualCaptures => IterInner::EqualCaptures(self.equal_capt_iter()),
             Killers => IterInner::Killers(self.killer_iter()),
+            CounterMove => IterInner::CounterMove(self.counter_iter()),
             Quiet => IterInner::Quiet(self.quiets_iter()),
             BadCaptures => IterInner::BadCaptures(self.bad_capt_iter()),
             Underpromotions => IterInner::Underpromotions(self.underpromo_iter()),
@@ -858,21 +932,38 @@ mod tests {
 
     struct TestLoader<'a> {
         pos: &'a mut Position,
+        excluded: Option<Move>,
     }
 
     impl<'a> TestLoader<'a> {
         fn from(pos: &'a mut Position) -> Self {
-            Self { pos }
+            Self {
+                pos,
+                excluded: None,
+            }
+        }
+
+        fn excluding(pos: &'a mut Position, excluded: Move) -> Self {
+            Self {
+                pos,
+                excluded: Some(excluded),
+            }
         }
     }
 
     impl<'a> Loader for TestLoader<'a> {
         fn load_hash(&mut self, movelist: &mut ScoredMoveList) {
             match self.pos.generate::<BasicMoveList, All, Legal>().random() {
-                Some(mv) => {
+                Some(mv) if Some(*mv) != self.excluded => {
                     movelist.push(*mv);
                 }
-                None => {}
+                _ => {}
+            }
+        }
+
+        fn load_excluded(&mut self, movelist: &mut ScoredMoveList) {
+            if let Some(mv) = self.excluded {
+                movelist.push(mv);
             }
         }
 
@@ -907,6 +998,13 @@ mod tests {
             }
         }
 
+        fn load_counter(&mut self, movelist: &mut ScoredMoveList) {
+            // Insert a random quiet move, which may duplicate a killer.
+            if let Some(mv) = self.pos.generate::<BasicMoveList, Quiets, Legal>().random() {
+                movelist.push(*mv);
+            }
+        }
+
         fn load_quiets(&mut self, movelist: &mut ScoredMoveList) {
             self.pos.generate_in::<_, Quiets, Legal>(movelist);
         }
@@ -945,4 +1043,26 @@ mod tests {
             assert_eq!(perft, r);
         }
     }
+
+    #[test]
+    fn excluded_moves_are_never_yielded() {
+        core::init::init_globals();
+
+        for (p, _, _) in TESTS {
+            let mut pos = Position::from_fen(p).unwrap();
+            let legal = pos.generate::<BasicMoveList, All, Legal>();
+
+            for excluded in legal.iter() {
+                let mut moves = OrderedMoves::new();
+                let mut yielded = Vec::new();
+
+                while moves.load_next_phase(TestLoader::excluding(&mut pos, *excluded)) {
+                    yielded.extend((&moves).into_iter().copied());
+                }
+
+                assert!(!yielded.contains(excluded));
+                assert_eq!(yielded.len(), legal.len() - 1);
+            }
+        }
+    }
 }
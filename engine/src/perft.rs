@@ -1,7 +1,7 @@
 use core::mono_traits::{All, Captures, Legal};
 use core::mov::Move;
 use core::movelist::BasicMoveList;
-use core::position::{Position, START_POSITION};
+use core::position::{BalanceCheck, Position, START_POSITION};
 
 use separator::Separatable;
 
@@ -181,6 +181,12 @@ impl<'a> Perft<'a> {
             self.data.nodes += 1;
         }
 
+        if depth == 1 && !self.options.detailed && !self.options.checks {
+            // Bulk counting: the leaves only need counting, not storing.
+            self.data.nodes += self.position.count_legal_moves();
+            return;
+        }
+
         let moves = self.position.generate::<_, All, Legal>();
 
         if depth == 1 {
@@ -200,11 +206,13 @@ impl<'a> Perft<'a> {
         print_data: bool,
     ) -> PerftData {
         let perft_options = PerftOptions::new(collect_detailed_data, collect_check_data);
+        let balance = BalanceCheck::new(position);
         let mut perft = Self::new(position, perft_options);
 
         let start = Instant::now();
         perft.perft_inner(depth);
         let elapsed = start.elapsed();
+        balance.verify(perft.position);
 
         if print_data {
             println!("{}", perft);
@@ -255,6 +263,29 @@ impl<'a> Perft<'a> {
         perft.output()
     }
 
+    /// Runs the "divide" perft routine without printing anything, returning the node count for
+    /// the subtree below each legal move in the given position. Used for comparing divide output
+    /// against another engine.
+    pub fn divide_counts(position: &'a mut Position, depth: usize) -> Vec<(Move, usize)> {
+        assert!(depth >= 1);
+        let mut perft = Self::new(position, PerftOptions::new(false, false));
+
+        let moves = perft.position.generate::<BasicMoveList, All, Legal>();
+        let mut counts = Vec::with_capacity(moves.len());
+
+        for mov in &moves {
+            if depth == 1 {
+                counts.push((*mov, 1));
+            } else {
+                let before = perft.data.nodes;
+                perft.recurse(mov, depth - 1);
+                counts.push((*mov, perft.data.nodes - before));
+            }
+        }
+
+        counts
+    }
+
     #[inline(always)]
     fn handle_leaf(&mut self, moves: &BasicMoveList) {
         self.data.nodes += moves.len();
@@ -280,14 +311,13 @@ impl<'a> Perft<'a> {
                 }
 
                 if self.options.checks {
-                    self.position.make_move(mov);
-                    if self.position.in_checkmate() {
+                    let pos = self.position.play(mov);
+                    if pos.in_checkmate() {
                         self.data.checkmate += 1;
                     }
-                    if self.position.in_double_check() {
+                    if pos.in_double_check() {
                         self.data.check += 1;
                     }
-                    self.position.unmake_move();
                 }
             }
         }
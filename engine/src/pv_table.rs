@@ -13,111 +13,106 @@
 //! PV tables are often not used when the search uses a transpostion table, since the principal
 //! variation can usually be recovered by inspecting this directly as needed.
 
+use super::score::Score;
+
 use core::mov::Move;
 
 /// Table for storing the principal variation during search.
+///
+/// The table is triangular: row `ply` holds the principal variation of the node at that distance
+/// from the root. Rows are indexed by ply rather than remaining depth, since with reductions and
+/// extensions the remaining depth no longer identifies a node on the current path.
 pub struct PVTable {
     data: Vec<Move>,
-    depth: usize,
+    len: Vec<usize>,
+    size: usize,
 }
 
 impl PVTable {
-    pub fn new(depth: u8) -> Self {
-        let d = depth as usize;
+    /// Create a table for variations of at most `size` plies.
+    pub fn new(size: u8) -> Self {
+        let n = size as usize;
         Self {
-            data: vec![Move::null(); d * d],
-            depth: d,
+            data: vec![Move::null(); n * n],
+            len: vec![0; n + 1],
+            size: n,
         }
     }
 
-    /// Called when a move searched at depth `d` improves the score. Copies the current principal
-    /// variation from depth `d-1` and copies it into the depth `d` column of the table, appending
-    /// the new move to the end.
-    pub fn copy_to(&mut self, d: u8, mov: Move) {
-        match d {
-            1 => self.update_leaf(mov),
-            n => self.update_internal(n, mov),
+    /// Called when a move searched at `ply` improves the score. Copies the principal variation of
+    /// the child at `ply + 1` into the row for `ply`, after the new move.
+    pub fn copy_to(&mut self, ply: u8, mov: Move) {
+        let ply = ply as usize;
+        if ply >= self.size {
+            return;
         }
-    }
 
-    /// Called when a move searched at depth `d` turns out to be a checkmate or stalemate position,
-    /// meaning that there is no variation following this point, and no move to include.
-    pub fn pv_leaf_at(&mut self, d: u8) {
-        let m = self.depth;
-        let d = d as usize;
-        let k = m - d;
-        let _ = &mut self.data[(k * m)..(k * m + d)].fill(Move::null());
-    }
+        let row = ply * self.size;
+        let child = std::cmp::min(self.len[ply + 1], self.size - ply - 1);
 
-    #[inline(always)]
-    fn update_leaf(&mut self, mov: Move) {
-        // Safety: we never mutate `self.depth` after the `PVTable` struct is created, so this
-        // index is guaranteed to exist.
-        let m = self.depth;
-        unsafe { *self.data.get_unchecked_mut(m * (m - 1)) = mov };
+        self.data[row] = mov;
+        self.data
+            .copy_within((row + self.size)..(row + self.size + child), row + 1);
+        self.len[ply] = child + 1;
     }
 
-    #[inline(always)]
-    fn update_internal(&mut self, d: u8, mov: Move) {
-        let m = self.depth;
-        let d = d as usize;
-        let k = m - d;
-
-        let _ = &mut self
-            .data
-            .copy_within(((k + 1) * m)..((k + 1) * m + d - 1), k * m);
-
-        self.data[k * m + d - 1] = mov;
+    /// Called when the node at `ply` has no variation following it, either because it is a leaf
+    /// or because it hasn't been searched yet.
+    pub fn pv_leaf_at(&mut self, ply: u8) {
+        if let Some(len) = self.len.get_mut(ply as usize) {
+            *len = 0;
+        }
     }
 
     /// Get an iterator over the principal variation.
     pub fn pv(&self) -> PVIter<'_> {
         PVIter {
-            iter: self.data[0..self.depth].iter().rev(),
+            iter: self.data[0..self.len[0]].iter(),
         }
     }
+
+    /// Take a copy of the principal variation, scored `score`, which outlives the search of the
+    /// next line or iteration.
+    pub fn line(&self, score: Score) -> PvLine {
+        PvLine {
+            score,
+            moves: self.pv().copied().collect(),
+        }
+    }
+}
+
+/// A principal variation from the root and its score. In MultiPV mode, we keep one for each of
+/// the best few root moves.
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    pub score: Score,
+    pub moves: Vec<Move>,
 }
 
 /// An iterator over the principal variation.
 pub struct PVIter<'a> {
-    iter: std::iter::Rev<std::slice::Iter<'a, Move>>,
+    iter: std::slice::Iter<'a, Move>,
 }
 
 impl<'a> Iterator for PVIter<'a> {
     type Item = &'a Move;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter
-            .next()
-            .and_then(|m| if m.is_null() { None } else { Some(m) })
+        self.iter.next()
     }
 }
 
 impl std::fmt::Debug for PVTable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let d = self.depth;
-
-        write!(f, "    │ ")?;
-        for col in 0..d {
-            write!(f, "{:^5} │ ", col)?;
-        }
-        writeln!(f)?;
+        for ply in 0..self.size {
+            let len = self.len[ply];
+            if len == 0 {
+                continue;
+            }
 
-        write!(f, "    ├")?;
-        for _ in 0..(d - 1) {
-            write!(f, "───────┼")?;
-        }
-        writeln!(f, "───────┤")?;
-
-        for row in 0..d {
-            write!(f, " {:>2} │ ", row)?;
-            for col in 0..d {
-                let mov = self.data[col * d + (d - row - 1)];
-                if mov.is_null() {
-                    write!(f, "  *   │ ")?;
-                } else {
-                    write!(f, " {:>5} │ ", mov)?;
-                }
+            write!(f, " {:>2} │", ply)?;
+            for mov in &self.data[(ply * self.size)..(ply * self.size + len)] {
+                write!(f, " {:>5}", mov)?;
             }
             writeln!(f)?;
         }
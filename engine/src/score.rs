@@ -16,6 +16,9 @@ use std::ops::{Add, Neg, Sub};
 /// choices of value.
 ///
 /// * -10_000 - 10_000 -> centipawn evaluations
+/// * 15_000 - 15_100 -> tablebase win, found by probing an endgame tablebase
+///   * 15_100 represents a tablebase win at the current position, 15_099 one ply away etc.
+/// * -15_100 - -15_000 -> tablebase loss, likewise
 /// * 20_000 - 20_100 -> positive mate-in-N (i.e. the player to move is mating the opponent)
 ///   * 20_100 represents mate-in-0, 20_099 represents mate-in-1 etc. This is so that shorter depth
 ///   to mate is better.
@@ -34,7 +37,8 @@ impl Score {
     /// Represents positive infinity.
     pub const INF_P: Score = Score(30_000);
 
-    /// Increment the depth to mate if this is a mate score. Otherwise, leave.
+    /// Increment the depth to mate if this is a mate score, or the distance to the tablebase
+    /// position if this is a tablebase score. Otherwise, leave.
     ///
     /// This is useful in search routines where we recursively call and need to increment the depth
     /// to mate from the parent position.
@@ -50,6 +54,27 @@ impl Score {
             Score(self.0 + 1)
         } else if self.0 > 20_000 {
             Score(self.0 - 1)
+        } else if self.0 < -15_000 && self.0 >= -15_100 {
+            Score(self.0 + 1)
+        } else if self.0 > 15_000 && self.0 <= 15_100 {
+            Score(self.0 - 1)
+        } else {
+            self
+        }
+    }
+
+    /// The inverse of `inc_mate`: move a mate or tablebase score one ply closer to the mate or
+    /// tablebase position.
+    ///
+    /// Scores are relative to the node they belong to, so this is how we pass a window down to a
+    /// child, which is one ply closer to any mate than its parent.
+    pub fn dec_mate(self) -> Self {
+        if self.0.abs() >= 30_000 {
+            self
+        } else if self.0 > 20_000 || (15_000..=15_100).contains(&self.0) {
+            Score(self.0 + 1)
+        } else if self.0 < -20_000 || (-15_100..=-15_000).contains(&self.0) {
+            Score(self.0 - 1)
         } else {
             self
         }
@@ -66,6 +91,18 @@ impl Score {
         }
     }
 
+    /// Construct a score representing a tablebase win `plies` away from the current position. This
+    /// is better than any centipawn evaluation but worse than any mate.
+    pub fn tb_win(plies: u8) -> Self {
+        debug_assert!(plies <= 100);
+        Score(15_100 - plies as i16)
+    }
+
+    /// Construct a score representing a tablebase loss `plies` away from the current position.
+    pub fn tb_loss(plies: u8) -> Self {
+        -Self::tb_win(plies)
+    }
+
     /// Construct a score representing `x` centipawns.
     pub fn cp(x: i16) -> Self {
         debug_assert!(x <= 10_000);
@@ -91,6 +128,20 @@ impl Score {
         }
     }
 
+    /// The number of moves in which the side to move mates, if this is a winning mate score.
+    pub fn moves_to_mate(&self) -> Option<u8> {
+        if self.0 > 20_000 && self.0 < 30_000 {
+            Some(((20_100 - self.0 + 1) / 2) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// True if this `Score` represents a tablebase win or loss.
+    pub fn is_tb(&self) -> bool {
+        (15_000..=15_100).contains(&self.0.abs())
+    }
+
     /// True if this `Score` represents a centipawn evaluation.
     pub fn is_cp(&self) -> bool {
         if -10_000 <= self.0 && self.0 <= 10_000 {
@@ -156,6 +207,10 @@ impl std::fmt::Debug for Score {
             write!(f, "Mate(-{})", self.0 + 20_100)
         } else if self.0 > 20_000 {
             write!(f, "Mate({})", 20_100 - self.0)
+        } else if self.0 < -15_000 {
+            write!(f, "TbLoss({})", self.0 + 15_100)
+        } else if self.0 > 15_000 {
+            write!(f, "TbWin({})", 15_100 - self.0)
         } else {
             write!(f, "Cp({})", self.0)
         }
@@ -184,6 +239,11 @@ impl std::fmt::Display for Score {
                                                    // so this should always be an odd number of plies.
 
             write!(f, "mate {}", moves_to_mate)
+        } else if self.is_tb() {
+            // UCI has no notation for tablebase wins, so report them as centipawn scores at the
+            // edge of the evaluation range: decisive, but below any mate.
+            let plies = 15_100 - self.0.abs();
+            write!(f, "cp {}", self.0.signum() * (10_100 - plies))
         } else {
             write!(f, "cp {}", self.0)
         }
@@ -212,5 +272,29 @@ mod tests {
         assert!(Score::mate(1) > Score::cp(300));
         assert!(Score::cp(0) > Score::INF_N);
         assert!(Score::cp(0) < Score::INF_P);
+        assert!(Score::tb_win(0) > Score::cp(10_000));
+        assert!(Score::tb_win(0) < Score::mate(100));
+        assert!(Score::tb_win(1) < Score::tb_win(0));
+        assert!(Score::tb_loss(0) < Score::cp(-10_000));
+        assert!(Score::tb_loss(0) > Score::mate(-100));
+        assert_eq!(Score::tb_win(0).inc_mate(), Score::tb_win(1));
+        assert_eq!((-Score::tb_win(3)).inc_mate(), Score::tb_loss(4));
+        assert_eq!(Score::mate(3).dec_mate(), Score::mate(2));
+        assert_eq!(Score::mate(-4).dec_mate(), Score::mate(-3));
+        assert_eq!(Score::mate(3).dec_mate().inc_mate(), Score::mate(3));
+        assert_eq!(Score::tb_loss(4).dec_mate().inc_mate(), Score::tb_loss(4));
+        assert_eq!(Score::mate(0).dec_mate().inc_mate(), Score::mate(0));
+        assert_eq!(Score::cp(42).dec_mate(), Score::cp(42));
+        assert_eq!(Score::INF_P.dec_mate(), Score::INF_P);
+    }
+
+    #[test]
+    fn uci_display() {
+        assert_eq!(Score::cp(-35).to_string(), "cp -35");
+        assert_eq!(Score::mate(3).to_string(), "mate 2");
+        assert_eq!(Score::mate(-4).to_string(), "mate -2");
+        assert_eq!(Score::tb_win(0).to_string(), "cp 10100");
+        assert_eq!(Score::tb_win(7).to_string(), "cp 10093");
+        assert_eq!(Score::tb_loss(4).to_string(), "cp -10096");
     }
 }
@@ -1,23 +1,62 @@
-use crate::history::HistoryTable;
+use crate::history::{history_bonus, Histories};
 
-use super::eval::Evaluation;
-use super::info::{CurrMoveInfo, Info, PvInfo};
+use super::egtb::{Dtm, EndgameTables};
+use super::eval::{piece_value, Evaluation};
+use super::info::{CurrLineInfo, CurrMoveInfo, Info, PvInfo, StatsInfo};
 use super::killer::KillerTable;
-use super::ordering::{Loader, OrderedMoves, ScoredMoveList, Scorer};
-use super::pv_table::PVTable;
+use super::lmr::ReductionTable;
+use super::ordering::{Loader, OrderedMoves, ScoredMoveList, Scorer, GOOD_CAPTURE};
+use super::pv_table::{PVTable, PvLine};
 use super::score::Score;
-use super::trace::Tracer;
+use super::syzygy::{RootProbe, Tablebases, Wdl};
+use super::trace::{SharedStats, Tracer};
 use super::tt::{Bound, Table};
 
-use core::mono_traits::{All as AllGen, Captures, Legal, QueenPromotions, Quiets};
+use core::mono_traits::{All as AllGen, Captures, Generate, Legal, QueenPromotions, Quiets};
 use core::mov::Move;
 use core::movelist::{BasicMoveList, MoveList};
-use core::position::{Player, Position};
+use core::position::{BalanceCheck, Piece, PieceType, Player, Position, Square, Variant};
 
 use separator::Separatable;
 
+use std::cell::Cell;
 use std::ops::Neg;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// The maximum distance from the root which the search will reach, including extensions. This is
+/// also the longest mate we can represent.
+pub const MAX_PLY: u8 = 100;
+
+/// The half-width of the first aspiration window, in centipawns.
+const ASPIRATION_DELTA: i16 = 50;
+
+/// The first iteration searched with an aspiration window. Shallower iterations are too unstable
+/// for the previous score to be a good guess.
+const ASPIRATION_MIN_DEPTH: u8 = 5;
+
+/// The widest aspiration window, in centipawns, before we fall back to a full window.
+const ASPIRATION_MAX_DELTA: i16 = 1000;
+
+/// Internal iterative reduction: the minimum depth and the reduction for nodes without a TT move.
+const IIR_PV_MIN_DEPTH: u8 = 4;
+const IIR_PV_REDUCTION: u8 = 1;
+const IIR_NON_PV_MIN_DEPTH: u8 = 7;
+const IIR_NON_PV_REDUCTION: u8 = 1;
+
+/// The number of nodes between each thread publishing its stats. Must be a power of two.
+const CHECK_IN_NODES: usize = 1024;
+
+/// The longest the master thread goes without an info report while searching.
+const REPORT_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// The pieces a pawn can promote to.
+const PROMOTION_TYPES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Knight,
+    PieceType::Bishop,
+];
 
 /// Trait to monomorphize search functionality over different thread types: master and worker.
 ///
@@ -160,14 +199,67 @@ pub struct Search<'engine> {
     tt: &'engine Table,
     /// The killer move table.
     kt: KillerTable,
-    /// The history table.
-    history: HistoryTable,
+    /// The history tables, and the quiet move which last refuted each previous move.
+    histories: Histories,
+    /// Base late move reductions by depth and move number.
+    reductions: ReductionTable,
     /// Flag to indicate when the search should start unwinding due to user intervention.
     stopping: &'engine AtomicBool,
     /// Time to at which to end search.
-    stop_time: Option<std::time::Instant>,
+    stop_time: Cell<Option<std::time::Instant>>,
+    /// When the search started, so that we know how much time `stop_time` allows for.
+    start_time: Instant,
+    /// Flag which is set while we are pondering, when `stop_time` doesn't apply yet.
+    pondering: Option<&'engine AtomicBool>,
+    /// Whether we were pondering when we last looked at the stop time, so that we notice
+    /// `ponderhit`.
+    was_pondering: Cell<bool>,
+    /// Whether to suggest a move to ponder on along with the best move.
+    report_ponder_move: bool,
+    /// The number of nodes, across all threads, after which to end search.
+    node_limit: Option<usize>,
+    /// End the search once it finds a mate in this many moves or fewer.
+    mate_limit: Option<u8>,
     search_depth: u8,
     depth_reached: u8,
+    /// The length of the position's history at the root, so that we can tell how many plies
+    /// deep a node is, whatever reductions led to it.
+    root_ply: usize,
+    /// Null moves are disabled before this ply while we verify a null move cutoff.
+    nmp_min_ply: u8,
+    /// The move to skip at the next node searched, set for singular extension searches.
+    excluded_move: Option<Move>,
+    /// The number of plies of extension on the path from the root to the current node.
+    path_extensions: u8,
+    /// The endgame tablebases, if any have been configured.
+    tb: Option<&'engine Tablebases>,
+    /// The DTM tables we generated ourselves, if any have been loaded.
+    egtb: Option<&'engine EndgameTables>,
+    /// The minimum remaining depth at which to probe the tablebases in search.
+    tb_probe_depth: u8,
+    /// The largest number of pieces for which we probe the tablebases in search. Zero disables
+    /// probing.
+    tb_cardinality: usize,
+    /// The moves the root search was restricted to by `go searchmoves`. If empty, every move may be
+    /// searched.
+    search_moves: Vec<Move>,
+    /// The moves to search at the root. If empty, all legal moves are searched.
+    root_moves: Vec<Move>,
+    /// The score given by the tablebases to the root position, if it was found in them.
+    root_tb_score: Option<Score>,
+    /// The number of principal variations to search and report.
+    multi_pv: usize,
+    /// Root moves which head lines already found in this iteration, and which the search for the
+    /// next line skips.
+    excluded_root_moves: Vec<Move>,
+    /// The principal variations found by the last complete iteration, best first.
+    pv_lines: Vec<PvLine>,
+    /// Stats totalled across all the threads of the search, if there are any others.
+    shared: Option<&'engine SharedStats>,
+    /// Whether to report the line being searched along with the periodic stats.
+    show_curr_line: bool,
+    /// When we last sent an info report.
+    last_report: Instant,
 }
 
 impl<'engine> Search<'engine> {
@@ -181,16 +273,104 @@ impl<'engine> Search<'engine> {
             pos,
             tt,
             kt: KillerTable::new(20),
-            history: HistoryTable::new(),
-            pvt: PVTable::new(8),
+            histories: Histories::default(),
+            reductions: ReductionTable::new(),
+            pvt: PVTable::new(MAX_PLY),
             trace: Tracer::new(),
             stopping: flag,
-            stop_time,
+            stop_time: Cell::new(stop_time),
+            start_time: Instant::now(),
+            pondering: None,
+            was_pondering: Cell::new(false),
+            report_ponder_move: false,
+            node_limit: None,
+            mate_limit: None,
             search_depth: 0,
             depth_reached: 0,
+            root_ply: 0,
+            nmp_min_ply: 0,
+            excluded_move: None,
+            path_extensions: 0,
+            tb: None,
+            egtb: None,
+            tb_probe_depth: 1,
+            tb_cardinality: 0,
+            search_moves: Vec::new(),
+            root_moves: Vec::new(),
+            root_tb_score: None,
+            multi_pv: 1,
+            excluded_root_moves: Vec::new(),
+            pv_lines: Vec::new(),
+            shared: None,
+            show_curr_line: false,
+            last_report: Instant::now(),
         }
     }
 
+    /// Use the endgame tablebases `tb`, probing them in search at nodes with at least
+    /// `probe_depth` remaining depth.
+    pub fn set_tablebases(&mut self, tb: &'engine Tablebases, probe_depth: u8) {
+        self.tb = Some(tb);
+        self.tb_probe_depth = probe_depth;
+    }
+
+    /// Search and report the best `lines` root moves, each with its principal variation.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines;
+    }
+
+    /// Start from the history tables of an earlier search, rather than from empty ones.
+    pub fn set_histories(&mut self, histories: Histories) {
+        self.histories = histories;
+    }
+
+    /// Give up the history tables, so that the next search can start from them.
+    pub fn into_histories(self) -> Histories {
+        self.histories
+    }
+
+    /// Only search the root moves in `moves`, or all of them if it is empty.
+    pub fn set_search_moves(&mut self, moves: Vec<Move>) {
+        self.search_moves = moves;
+    }
+
+    /// Ignore the stop time while `flag` is set, as it is while we ponder.
+    pub fn set_ponder_flag(&mut self, flag: &'engine AtomicBool) {
+        self.pondering = Some(flag);
+        self.was_pondering.set(flag.load(Ordering::Relaxed));
+    }
+
+    /// Suggest a move to ponder on along with the best move.
+    pub fn set_report_ponder_move(&mut self, report: bool) {
+        self.report_ponder_move = report;
+    }
+
+    /// End the search once all threads have visited `nodes` nodes between them, if given.
+    pub fn set_node_limit(&mut self, nodes: Option<usize>) {
+        self.node_limit = nodes;
+    }
+
+    /// End the search as soon as it finds a mate in `moves` moves or fewer, if given.
+    pub fn set_mate_limit(&mut self, moves: Option<u8>) {
+        self.mate_limit = moves;
+    }
+
+    /// Publish our stats to `shared`, and report the totals from there, so that the reports cover
+    /// every thread of the search.
+    pub fn set_shared_stats(&mut self, shared: &'engine SharedStats) {
+        self.shared = Some(shared);
+    }
+
+    /// Report the line being searched along with the periodic stats.
+    pub fn set_show_curr_line(&mut self, show: bool) {
+        self.show_curr_line = show;
+    }
+
+    /// Use the DTM tables `egtb`, probing them at every non-root node they cover.
+    pub fn set_endgame_tables(&mut self, egtb: &'engine EndgameTables) {
+        self.egtb = Some(egtb);
+    }
+
     pub fn run<T: Thread>(&mut self, d: u8) -> (Score, Move) {
         self.trace = Tracer::new();
 
@@ -198,58 +378,204 @@ impl<'engine> Search<'engine> {
 
         // Some bookeeping and prep.
         let start_zob = self.pos.zobrist();
+        let balance = BalanceCheck::new(&self.pos);
 
         // TODO: shouldn't have to do this. There is a bug somewhere. It seems to have something to
         // do with the PVS returning immediately from tt stored moves.
         self.tt.clear();
 
         self.trace.commence_search();
+        self.histories.age();
+        self.last_report = Instant::now();
         self.search_depth = d;
+        self.depth_reached = 0;
+        self.root_ply = self.pos.history().len();
+        self.nmp_min_ply = 0;
+        self.path_extensions = 0;
+        self.probe_root();
 
         let (score, best_move) = self.iterative_deepening::<T>(d);
         self.trace.end_search();
+        self.publish_stats();
 
+        // The balance check also catches a changed history length, but only in debug builds, so
+        // keep checking the key in release builds too.
+        balance.verify(&self.pos);
         assert_eq!(start_zob, self.pos.zobrist());
 
         if T::is_master() {
             self.report_telemetry(d, score);
-            println!("bestmove {}", best_move);
-        }
 
-        self.history.reset();
+            // While pondering, we mustn't send the best move until we hear `ponderhit` or `stop`,
+            // even if the search has finished.
+            while self.pondering() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            match self.ponder_move(best_move) {
+                Some(ponder) if self.report_ponder_move => {
+                    println!("bestmove {} ponder {}", best_move, ponder)
+                }
+                _ => println!("bestmove {}", best_move),
+            }
+        }
 
         (score, best_move)
     }
 
+    /// Probe the tablebases at the root. If the root position is in the tablebases, we only search
+    /// the moves which preserve its outcome, and report the tablebase score.
+    fn probe_root(&mut self) {
+        self.root_moves = self.search_moves.clone();
+        self.root_tb_score = None;
+        self.tb_cardinality = 0;
+
+        let Some(tb) = self.tb else {
+            return;
+        };
+        if self.pos.variant() != Variant::Standard {
+            return;
+        }
+
+        self.tb_cardinality = tb.max_pieces();
+
+        // The moves we were told to search needn't preserve the tablebase outcome, so we leave
+        // the tablebases to the search below the root.
+        if !self.search_moves.is_empty() {
+            return;
+        }
+
+        if let Some(probe) = tb.probe_root(&mut self.pos) {
+            self.trace.tb_hit();
+            let best = probe.best_rank();
+            self.root_moves = probe.best_moves();
+            self.root_tb_score = Some(RootProbe::score(best));
+
+            // With DTZ ranks every remaining root move makes progress, and in a drawn or lost
+            // position there is nothing to gain, so further probes would only slow the search.
+            if probe.dtz || best <= 0 {
+                self.tb_cardinality = 0;
+            }
+        }
+    }
+
     fn iterative_deepening<T: Thread>(&mut self, depth: u8) -> (Score, Move) {
         let mut score = Score::INF_N;
         let mut best_move = Move::null();
 
-        for d in 1..=depth {
+        // In MultiPV mode, each iteration searches the root once per line, excluding the first
+        // moves of the lines already found.
+        let root_move_count = if self.root_moves.is_empty() {
+            self.pos.count_legal_moves()
+        } else {
+            self.root_moves.len()
+        };
+        let num_lines = self.multi_pv.clamp(1, std::cmp::max(root_move_count, 1));
+
+        'deepening: for d in 1..=depth {
             if self.stopping() {
                 break;
             }
 
-            self.pvt = PVTable::new(d);
             self.search_depth = d;
-            let value = self.search::<T, Root>(Score::INF_N, Score::INF_P, d);
-
-            if !self.stopping() {
-                score = value;
-                best_move = match self.pvt.pv().into_iter().next() {
-                    Some(mov) => *mov,
-                    None => {
-                        let entry = self.tt.probe(&self.pos).into_inner();
-                        let tt_entry = entry.read();
-                        assert!(!tt_entry.is_empty());
-                        tt_entry.mov.to_move(&self.pos)
+            let prev_lines = std::mem::take(&mut self.pv_lines);
+            self.excluded_root_moves.clear();
+
+            for line in 0..num_lines {
+                self.pvt = PVTable::new(MAX_PLY);
+                let prev_line = prev_lines.get(line);
+
+                // Aspiration windows: the score rarely moves far between iterations, so from a
+                // few plies on we search a narrow window around the last score, which cuts off
+                // far more. If the score falls outside it, we report the bound and widen the
+                // window on that side until the score fits.
+                let mut delta = ASPIRATION_DELTA;
+                let (mut alpha, mut beta) = match prev_line {
+                    Some(prev) if d >= ASPIRATION_MIN_DEPTH && prev.score.is_cp() => {
+                        (prev.score - Score::cp(delta), prev.score + Score::cp(delta))
                     }
+                    _ => (Score::INF_N, Score::INF_P),
                 };
-                self.depth_reached = d;
+
+                let value = loop {
+                    let value = self.search::<T, Root>(alpha, beta, d);
+
+                    // An interrupted iteration tells us nothing, so we keep the last complete
+                    // one.
+                    if self.stopping() {
+                        self.pv_lines = prev_lines;
+                        break 'deepening;
+                    }
+
+                    // Past a large enough window, or outside the centipawn range, we might as
+                    // well search the full window.
+                    if value <= alpha {
+                        if T::is_master() {
+                            let moves = prev_line.map(|l| l.moves.clone()).unwrap_or_default();
+                            let pv = PvLine {
+                                score: value,
+                                moves,
+                            };
+                            self.report_pv(d, line + 1, Bound::Upper, &pv);
+                        }
+                        alpha = if value.is_cp() && delta <= ASPIRATION_MAX_DELTA {
+                            value - Score::cp(delta)
+                        } else {
+                            Score::INF_N
+                        };
+                    } else if value >= beta {
+                        if T::is_master() {
+                            self.report_pv(d, line + 1, Bound::Lower, &self.pvt.line(value));
+                        }
+                        beta = if value.is_cp() && delta <= ASPIRATION_MAX_DELTA {
+                            value + Score::cp(delta)
+                        } else {
+                            Score::INF_P
+                        };
+                    } else {
+                        break value;
+                    }
+
+                    delta *= 2;
+                };
+
+                if let Some(mov) = self.pvt.pv().next() {
+                    self.excluded_root_moves.push(*mov);
+                }
+                self.pv_lines.push(self.pvt.line(value));
             }
 
+            // A later line can score higher than an earlier one, since each search of the root
+            // sees a different set of moves, so we sort the lines before reporting them.
+            self.pv_lines.sort_by_key(|l| std::cmp::Reverse(l.score));
+
+            score = self.pv_lines[0].score;
+            best_move = match self.pv_lines[0].moves.first() {
+                Some(mov) => *mov,
+                None => {
+                    let entry = self.tt.probe(&self.pos).into_inner();
+                    let tt_entry = entry.read();
+                    assert!(!tt_entry.is_empty());
+                    tt_entry.mov.to_move(&self.pos)
+                }
+            };
+            self.depth_reached = d;
+            self.trace.complete_iteration();
+
             if T::is_master() {
-                self.report_pv(self.depth_reached, score);
+                let lines = self.pv_lines.clone();
+                for (i, line) in lines.iter().enumerate() {
+                    self.report_pv(self.depth_reached, i + 1, Bound::Exact, line);
+                }
+            }
+
+            // Once we find a mate as short as we were asked for, there is nothing left to look
+            // for, so we stop the other threads too.
+            if let (Some(limit), Some(moves)) = (self.mate_limit, score.moves_to_mate()) {
+                if moves <= limit {
+                    self.stopping.store(true, Ordering::Relaxed);
+                    break;
+                }
             }
         }
 
@@ -264,9 +590,15 @@ impl<'engine> Search<'engine> {
     ) -> Score {
         self.trace.visit_node();
 
-        let draft = self.search_depth - depth;
+        let draft = (self.pos.history().len() - self.root_ply) as u8;
+        self.trace.reach_ply(draft);
+        self.check_in::<T>();
+
+        let excluded = self.excluded_move.take();
         let mut tt_move = false;
 
+        self.pvt.pv_leaf_at(draft);
+
         debug_assert!(Score::INF_N <= alpha);
         debug_assert!(alpha < beta);
         debug_assert!(beta <= Score::INF_P);
@@ -277,24 +609,33 @@ impl<'engine> Search<'engine> {
             return Score::zero();
         }
 
-        // Step 2. check for immediate draw.
+        if draft >= MAX_PLY {
+            return self.evaluate();
+        }
+
+        // Step 2. Check for a game already lost under the rules of the variant being played.
+        if !Node::root() && self.pos.variant_loss() {
+            return Score::mate(0);
+        }
+
+        // Step 3. Check for immediate draw.
         if self.pos.in_threefold() || self.pos.half_move_clock() >= 50 {
             return Score::zero();
         }
 
-        // Step 2. Mate distance pruning.
+        // Step 4. Mate distance pruning.
         if !Node::root() {
-            // If we mate at the next move, the value at the root would be Mate(draft). If we
-            // already have alpha greater than this, then we had a quicker mate elsewhere in the
-            // tree. So we can prune here.
-            alpha = std::cmp::max(Score::mate(draft as i8).neg(), alpha);
-            beta = std::cmp::min(Score::mate(draft as i8 + 1), beta);
+            // Scores are relative to this node, so the best we can hope for is to mate at the next
+            // move, and the worst is to be mated here. If the window lies outside these bounds,
+            // we can prune here.
+            alpha = std::cmp::max(Score::mate(0), alpha);
+            beta = std::cmp::min(Score::mate(1), beta);
             if alpha >= beta {
                 return alpha;
             }
         }
 
-        // Step 3. Load transposition table entry.
+        // Step 5. Load transposition table entry.
         let (tt_entry, tt_mov) = {
             use super::tt::Probe::*;
             match self.tt.probe(&self.pos) {
@@ -322,8 +663,15 @@ impl<'engine> Search<'engine> {
             }
         };
 
-        // Step 4. Check for early cutoff.
-        if !Node::pv() && tt_move {
+        let (tt_value, tt_depth, tt_bound) = {
+            let entry = tt_entry.read();
+            (entry.score, entry.depth, entry.bound())
+        };
+
+        // Step 6. Check for early cutoff.
+        //
+        // The entry describes the node with all its moves, so it is no use when we exclude one.
+        if !Node::pv() && tt_move && excluded.is_none() {
             let entry = tt_entry.read();
 
             if !entry.is_empty() && entry.depth >= depth {
@@ -353,64 +701,264 @@ impl<'engine> Search<'engine> {
             }
         }
 
-        // Step 5. Straight to quiescence search if depth <= 0.
+        // Step 7. Straight to quiescence search if depth <= 0.
         if depth == 0 {
-            let score = self.quiesce::<T, Node>(alpha, beta);
-            if score == Score::mate(0) {
-                self.pvt.pv_leaf_at(0);
+            return self.quiesce::<T, Node>(alpha, beta, true);
+        }
+
+        // Step 8. Probe the endgame tablebases.
+        //
+        // Our own DTM tables give exact mate distances, so they take precedence.
+        let probe = !Node::root() && excluded.is_none();
+        if let Some(egtb) = self.egtb.filter(|t| probe && !t.is_empty()) {
+            if let Some(score) = egtb.probe(&self.pos).and_then(Dtm::score) {
+                self.trace.tb_hit();
+                return score;
             }
+        }
 
-            return score;
+        // We only probe the Syzygy tables just after a zeroing move, since they take the 50-move
+        // rule into account from there.
+        let mut tb_min = Score::INF_N;
+        let mut tb_max = Score::INF_P;
+        if let Some(tb) = self.tb.filter(|_| probe && self.tb_cardinality > 0) {
+            let pieces = self.pos.occupied().popcnt() as usize;
+
+            if pieces <= self.tb_cardinality
+                && (pieces < self.tb_cardinality || depth >= self.tb_probe_depth)
+                && self.pos.half_move_clock() == 0
+                && self.pos.castling_rights().is_empty()
+            {
+                if let Some(wdl) = tb.probe_wdl(&mut self.pos) {
+                    self.trace.tb_hit();
+
+                    let (value, bound) = match wdl {
+                        Wdl::Win => (Score::tb_win(0), Bound::Lower),
+                        Wdl::Loss => (Score::tb_loss(0), Bound::Upper),
+                        _ => (Score::cp(2 * wdl as i16), Bound::Exact),
+                    };
+
+                    if bound == Bound::Exact
+                        || (bound == Bound::Lower && value >= beta)
+                        || (bound == Bound::Upper && value <= alpha)
+                    {
+                        return value;
+                    }
+
+                    // In PV nodes we keep searching for the best move, but the score can't leave
+                    // the bound given by the tablebases.
+                    if Node::pv() {
+                        if bound == Bound::Lower {
+                            tb_min = value;
+                            alpha = std::cmp::max(alpha, value);
+                        } else {
+                            tb_max = value;
+                        }
+                    }
+                }
+            }
         }
 
-        // Step 6. Static evaluation.
+        // Step 9. Static evaluation.
         let eval = self.evaluate();
+        let in_check = self.pos.in_check();
 
-        // Step 7. Razoring.
+        // Step 10. Razoring.
         // When eval is very low, check with quiescence whether it has any hope of raising alpha. If
-        // not, return a fail low.
-        if depth <= 6 && eval + Score::cp(426 + 252 * depth as i16 * depth as i16) < alpha {
-            let value = self.quiesce::<Master, NonPv>(alpha - Score::cp(1), alpha);
+        // not, return a fail low. Subtracting from a mate score leaves it unchanged, so only razor
+        // with a centipawn alpha, where the null window below it isn't empty.
+        if excluded.is_none()
+            && depth <= 6
+            && alpha.is_cp()
+            && eval + Score::cp(426 + 252 * depth as i16 * depth as i16) < alpha
+        {
+            let value = self.quiesce::<T, NonPv>(alpha - Score::cp(1), alpha, true);
             if value < alpha {
                 return value;
             }
         }
 
-        // Step 8. Futility pruning.
-        //         TODO
+        // Step 11. Futility pruning.
+        //
+        // Reverse futility (static null move) pruning: near the horizon, if the static eval beats
+        // beta by a margin which grows with depth, we assume the opponent can't recover. The
+        // static eval knows nothing of pawn races, so we don't trust it in pawn endings.
+        if !Node::pv()
+            && !in_check
+            && excluded.is_none()
+            && depth <= 6
+            && beta.is_cp()
+            && self.pos.has_non_pawn_material(self.pos.turn())
+            && eval - Score::cp(80 * depth as i16) >= beta
+        {
+            return eval;
+        }
 
-        // Step 9. Null move search with verification (non-PV only).
-        //         TODO
+        // Per-move futility pruning: if the static eval is so far below alpha that a quiet move
+        // can't plausibly raise it, we only search captures, promotions and checks.
+        let futile = !Node::pv()
+            && !in_check
+            && depth <= 6
+            && alpha.is_cp()
+            && eval + Score::cp(100 + 120 * depth as i16) <= alpha;
+
+        // Step 12. Null move search with verification (non-PV only).
+        //
+        // If we can pass and a reduced search still fails high, a real move will almost certainly
+        // fail high too. Passing is only unsound in zugzwang, so we require non-pawn material, and
+        // at high depth we verify the cutoff with a reduced search of our own moves during which
+        // null moves are disabled for the first few plies.
+        if !Node::pv()
+            && excluded.is_none()
+            && depth >= 2
+            && eval >= beta
+            && beta.is_cp()
+            && draft >= self.nmp_min_ply
+            && !in_check
+            && !self.pos.history().last().is_some_and(|m| m.is_null())
+            && self.pos.has_non_pawn_material(self.pos.turn())
+        {
+            let r = 3 + depth / 4 + std::cmp::min((eval - beta).to_i16() / 200, 3) as u8;
+            let null_depth = depth.saturating_sub(1 + r);
 
-        // Step 10. ProbCut.
-        //         TODO
+            self.trace.null_move_try();
+            self.pos.make_null_move();
+            let mut value = self
+                .search::<T, NonPv>(-beta, (-beta).inc_one(), null_depth)
+                .neg()
+                .inc_mate();
+            self.pos.unmake_move();
 
-        // Step 11. In PV nodes, if the move is not in TT, decrease depth by 3.
-        //          TODO
+            if self.stopping() {
+                return Score::zero();
+            }
+
+            if value >= beta {
+                // A null move can't prove a mate.
+                if !value.is_cp() {
+                    value = beta;
+                }
 
-        // Step 12. If depth <= 0, run quiescence search.
-        // if depth == 0 {
-        //     let score = self.quiesce::<T>(alpha, beta);
-        //     if score == Score::mate(0) {
-        //         self.pvt.pv_leaf_at(0);
-        //     }
-        //     return score;
-        // }
+                if self.nmp_min_ply != 0 || depth < 12 {
+                    self.trace.null_move_cutoff();
+                    return value;
+                }
 
-        // Step 13. In non-PV nodes with depth >= 7 and not in TT, decrease depth by 2.
-        //          TODO
+                self.nmp_min_ply = draft + (3 * null_depth / 4);
+                let verified = self.search::<T, NonPv>(beta - Score::cp(1), beta, null_depth);
+                self.nmp_min_ply = 0;
+
+                if verified >= beta {
+                    self.trace.null_move_cutoff();
+                    return value;
+                }
+            }
+        }
+
+        // Step 13. ProbCut.
+        //
+        // If a good capture beats beta by a clear margin in a much shallower search, the full
+        // depth search will very likely fail high too. We only try captures whose SEE already
+        // brings the static eval up to the raised beta, and confirm with quiescence before paying
+        // for the reduced search.
+        let probcut_beta = beta + Score::cp(200);
+        if !Node::pv()
+            && !in_check
+            && excluded.is_none()
+            && depth >= 5
+            && beta.is_cp()
+            && !(tt_move && tt_depth + 3 >= depth && tt_value < probcut_beta)
+        {
+            let threshold = probcut_beta - eval;
+            let captures = self.pos.generate::<BasicMoveList, Captures, Legal>();
+
+            for mov in captures.iter().filter(|m| m.is_capture()) {
+                let see = self.see(
+                    mov.orig(),
+                    mov.dest(),
+                    self.pos.piece_at_sq(mov.dest()).type_of(),
+                    self.pos.piece_at_sq(mov.orig()).type_of(),
+                );
+                if see < threshold {
+                    continue;
+                }
+
+                self.trace.probcut_try();
+                self.pos.make_move(mov);
+                let mut value = self
+                    .quiesce::<T, NonPv>(-probcut_beta, (-probcut_beta).inc_one(), true)
+                    .neg()
+                    .inc_mate();
+                if value >= probcut_beta {
+                    value = self.scout::<T, Node>(probcut_beta - Score::cp(1), depth - 4);
+                }
+                self.pos.unmake_move();
+
+                if self.stopping() {
+                    return Score::zero();
+                }
+
+                // The reduced search only proves the raised bound, so we hand back the margin
+                // rather than trust its score as a bound on the full depth search.
+                if value >= probcut_beta {
+                    self.trace.probcut_cutoff();
+                    tt_entry.write(&self.pos, value, depth - 3, Bound::Lower, mov);
+                    return if value.is_cp() {
+                        value - (probcut_beta - beta)
+                    } else {
+                        value
+                    };
+                }
+            }
+        }
+
+        // Step 14. Internal iterative reduction in PV nodes.
+        //
+        // Without a TT move our move ordering is poor. Rather than pay for a full depth search
+        // with bad ordering, we search the node shallower, and the next iteration will find the
+        // move it stored in the TT. Pawn endings are decided by races where every ply counts, so
+        // we leave them at full depth.
+        let mut depth = depth;
+        let iir = !Node::root()
+            && !in_check
+            && excluded.is_none()
+            && tt_mov.is_none()
+            && self.pos.has_non_pawn_material(self.pos.turn());
+        if iir && Node::pv() && depth >= IIR_PV_MIN_DEPTH {
+            self.trace.iir_reduction();
+            depth -= IIR_PV_REDUCTION;
+        }
+
+        // Step 15. The reductions never take the depth to zero, so there is no need to drop into
+        // the quiescence search here.
 
-        // Step 14. If PV move and TT move failed low, this is a likely fail-low.
+        // Step 16. Internal iterative reduction in non-PV nodes.
+        //
+        // These are less important to get right, so we only reduce them at high depth.
+        if iir && !Node::pv() && depth >= IIR_NON_PV_MIN_DEPTH {
+            self.trace.iir_reduction();
+            depth -= IIR_NON_PV_REDUCTION;
+        }
+
+        // Step 17. If PV move and TT move failed low, this is a likely fail-low.
         //          TODO
 
-        // Step 15. Iterate moves.
-        let mut best_value = Score::INF_N;
+        // Step 18. Iterate moves.
+        let mut best_value = tb_min;
         let mut best_move = Move::null();
         let mut moves = OrderedMoves::new();
         let mut move_count = 0;
         let mut did_raise_alpha = false;
+        let mut quiets_searched = BasicMoveList::new();
+        let mut captures_searched = BasicMoveList::new();
 
-        'move_loop: while moves.load_next_phase(MoveLoader::from(self, tt_mov, draft)) {
+        'move_loop: while moves.load_next_phase(MoveLoader::from(
+            self,
+            tt_mov,
+            excluded,
+            draft,
+            Node::root(),
+        )) {
             for mov in &moves {
                 if self.stopping() {
                     break 'move_loop;
@@ -424,24 +972,135 @@ impl<'engine> Search<'engine> {
                     self.report_curr_move(depth, &mov, move_count);
                 }
 
-                // Step 16. Reductions & extensions.
-                //          TODO
+                // Step 19. Reductions & extensions.
+                //
+                // Each path has a budget of extensions of half the iteration depth, so that long
+                // sequences of checks can't make the search explode.
+                let can_extend = self.path_extensions < self.search_depth / 2;
+                let mut extension = 0;
+
+                // Singular extension: if the TT move beats every alternative by a margin, in a
+                // reduced search which excludes it, it is the only good move here and we extend
+                // it.
+                if can_extend
+                    && !Node::root()
+                    && excluded.is_none()
+                    && depth >= 8
+                    && Some(*mov) == tt_mov
+                    && tt_depth + 3 >= depth
+                    && tt_bound != Bound::Upper
+                    && tt_value.is_cp()
+                {
+                    let singular_beta = tt_value - Score::cp(2 * depth as i16);
+
+                    self.excluded_move = Some(*mov);
+                    let value = self.search::<T, NonPv>(
+                        singular_beta - Score::cp(1),
+                        singular_beta,
+                        (depth - 1) / 2,
+                    );
 
-                // Step 17. Late move reduction.
-                //          TODO
+                    // The excluded search ran at this ply, so discard its variation.
+                    self.pvt.pv_leaf_at(draft);
 
-                // Step 18. Make the move.
+                    if value < singular_beta {
+                        self.trace.singular_extension();
+                        extension = 1;
+                    }
+                }
+
+                // Recapture extension: in PV nodes, a recapture on the square of the last
+                // capture is usually forced, so it shouldn't cost us depth.
+                if can_extend
+                    && Node::pv()
+                    && mov.is_capture()
+                    && self
+                        .pos
+                        .history()
+                        .last()
+                        .is_some_and(|m| m.captured != PieceType::None && m.dest == mov.dest())
+                {
+                    extension = 1;
+                }
+
+                // Step 20. Late move reduction.
+                //
+                // Quiet moves late in the list rarely produce a cutoff, so we search them to a
+                // reduced depth first. We reduce less where the move matters more: in PV and Cut
+                // nodes, and for killers and moves with a good history. Checks aren't reduced.
+                let mut reduction = 0;
+                if depth >= 3
+                    && move_count > 3 + 2 * Node::pv() as u8
+                    && mov.is_quiet()
+                    && !in_check
+                {
+                    let mut r = self.reductions.get(depth, move_count) as i16;
+
+                    if Node::pv() {
+                        r -= 1;
+                    }
+                    if Node::cut() {
+                        r -= 1;
+                    }
+                    if self.kt.contains(mov, draft) {
+                        r -= 1;
+                    }
+
+                    r -= (self.quiet_history(mov) / 8192).clamp(-1, 2) as i16;
+
+                    reduction = r;
+                }
+
+                let gives_check = self.pos.gives_check(mov);
+
+                // Futile quiet moves are skipped before we pay for making them.
+                if futile && move_count > 1 && mov.is_quiet() && !gives_check {
+                    self.trace.futility_prune();
+                    continue;
+                }
+
+                // Check extension.
+                if can_extend && gives_check {
+                    extension = 1;
+                }
+
+                // Step 21. Make the move.
                 self.pos.make_move(mov);
+                debug_assert_eq!(gives_check, self.pos.in_check());
 
-                // Step 19. Search non-PV move with null window.
-                if !Node::pv() || move_count > 1 {
-                    value = self
-                        .search::<T, NonPv>(-alpha.inc_one(), -alpha, depth - 1)
-                        .neg()
-                        .inc_mate();
+                let new_depth = depth - 1 + extension;
+                self.path_extensions += extension;
+
+                if gives_check {
+                    reduction = 0;
                 }
 
-                // Step 20. Search PV move, or perform re-search if null window search failed high.
+                // Never reduce straight into the quiescence search.
+                let reduction = if reduction > 0 {
+                    std::cmp::min(reduction as u8, new_depth - 1)
+                } else {
+                    0
+                };
+
+                // Step 22. Search non-PV move with null window.
+                //
+                // A reduced search which beats alpha must be confirmed at full depth.
+                if reduction > 0 {
+                    self.trace.lmr_reduction();
+                    value = self.scout::<T, Node>(alpha, new_depth - reduction);
+
+                    if value > alpha {
+                        self.trace.lmr_research();
+                    }
+                }
+
+                if (reduction == 0 && (!Node::pv() || move_count > 1))
+                    || (reduction > 0 && value > alpha)
+                {
+                    value = self.scout::<T, Node>(alpha, new_depth);
+                }
+
+                // Step 23. Search PV move, or perform re-search if null window search failed high.
                 //
                 // If this is a PV node, do a full search on the first move and any move for which
                 // the null-window search failed to produce a cutoff.
@@ -449,27 +1108,28 @@ impl<'engine> Search<'engine> {
                     && (move_count == 1 || (value > alpha && (Node::root() || value < beta)))
                 {
                     value = self
-                        .search::<T, Pv>(-beta, -alpha, depth - 1)
+                        .search::<T, Pv>(-beta.dec_mate(), -alpha.dec_mate(), new_depth)
                         .neg()
                         .inc_mate();
                 }
 
                 debug_assert!(Node::pv() || !(value > alpha && (Node::root() || value < beta)));
 
-                // Step 21. Undo move.
+                // Step 24. Undo move.
                 self.pos.unmake_move();
+                self.path_extensions -= extension;
 
                 debug_assert!(value > Score::INF_N);
                 debug_assert!(value < Score::INF_P);
 
-                // Step 22. Check for new best move.
+                // Step 25. Check for new best move.
                 if value > best_value {
                     best_value = value;
 
                     if value > alpha {
                         best_move = *mov;
 
-                        self.pvt.copy_to(depth, *mov);
+                        self.pvt.copy_to(draft, *mov);
 
                         if Node::pv() && value < beta {
                             alpha = value;
@@ -477,36 +1137,61 @@ impl<'engine> Search<'engine> {
                             // TODO: reduce depth on remaining moves.
                         } else {
                             debug_assert!(value >= beta);
-                            // beta-cutoff; record killer and history
+                            // beta-cutoff; record killer, counter move and history
+                            let bonus = history_bonus(depth);
                             if mov.is_quiet() {
-                                self.kt.store(*mov, draft);
+                                // The root can fail high against an aspiration window, but it
+                                // has no killer slot.
+                                if !Node::root() {
+                                    self.kt.store(*mov, draft);
+                                }
+                                if let Some(prev) = self.prev_moves()[0] {
+                                    self.histories.counter_moves.store(prev, *mov);
+                                }
+
+                                // The quiets searched before the cutoff move failed to cut, so
+                                // they get a malus.
+                                self.update_quiet_history(mov, bonus);
+                                for quiet in &quiets_searched {
+                                    self.update_quiet_history(quiet, -bonus);
+                                }
+                            } else if mov.is_capture() {
+                                self.update_capture_history(mov, bonus);
                             }
 
-                            // self.history.inc(
-                            //     mov.orig(),
-                            //     mov.dest(),
-                            //     depth as u32 * depth as u32,
-                            //     self.pos.turn(),
-                            // );
+                            for capture in &captures_searched {
+                                self.update_capture_history(capture, -bonus);
+                            }
 
                             break 'move_loop;
                         }
                     }
                 }
+
+                if mov.is_quiet() {
+                    quiets_searched.push(*mov);
+                } else if mov.is_capture() {
+                    captures_searched.push(*mov);
+                }
             }
         }
 
         debug_assert!(
-            move_count > 0 || self.pos.generate::<BasicMoveList, AllGen, Legal>().len() == 0
+            move_count > 0
+                || excluded.is_some()
+                || self.pos.generate::<BasicMoveList, AllGen, Legal>().len() == 0
         );
 
         if self.stopping() {
             return Score::zero();
         }
 
-        // Step 23. Check for mate and stalemate.
+        // Step 26. Check for mate and stalemate.
         if move_count == 0 {
-            self.pvt.pv_leaf_at(depth);
+            // With the only legal move excluded, there is nothing to compare it with.
+            if excluded.is_some() {
+                return alpha;
+            }
 
             best_value = if self.pos.in_check() {
                 Score::mate(0)
@@ -517,7 +1202,22 @@ impl<'engine> Search<'engine> {
 
         debug_assert!(best_value > Score::INF_N);
 
-        // Step 24. Write node information to the transposition table.
+        if Node::pv() {
+            best_value = std::cmp::min(best_value, tb_max);
+        }
+
+        // Step 27. Write node information to the transposition table.
+        //
+        // A search which excluded a move doesn't describe the node, so we don't store it. The
+        // same goes for the root when it skips the moves of earlier MultiPV lines, or those left
+        // out by `go searchmoves`.
+        if excluded.is_some()
+            || (Node::root()
+                && (!self.excluded_root_moves.is_empty() || !self.search_moves.is_empty()))
+        {
+            return best_value;
+        }
+
         tt_entry.write(
             &self.pos,
             best_value,
@@ -539,17 +1239,61 @@ impl<'engine> Search<'engine> {
             &best_move,
         );
 
-        // Step 25. Return best value.
+        // Step 28. Return best value.
         best_value
     }
 
+    /// Search the position after a move with a null window around `alpha`, returning the score
+    /// from the perspective of the parent.
+    ///
+    /// The children of a Cut node are All nodes, and the remaining non-PV children are Cut nodes.
     #[inline(always)]
+    fn scout<T: Thread, Node: NodeType>(&mut self, alpha: Score, depth: u8) -> Score {
+        let value = if Node::cut() {
+            self.search::<T, All>(-alpha.inc_one().dec_mate(), -alpha.dec_mate(), depth)
+        } else {
+            self.search::<T, Cut>(-alpha.inc_one().dec_mate(), -alpha.dec_mate(), depth)
+        };
+
+        value.neg().inc_mate()
+    }
+
+    #[inline(always)]
+    /// Whether the root move `mov` may be searched in the current line.
+    fn root_move_allowed(&self, mov: &Move) -> bool {
+        (self.root_moves.is_empty() || self.root_moves.contains(mov))
+            && !self.excluded_root_moves.contains(mov)
+    }
+
+    /// Whether we are pondering, and so not yet bound by the stop time.
+    fn pondering(&self) -> bool {
+        self.pondering.is_some_and(|p| p.load(Ordering::Relaxed))
+    }
+
+    /// The time at which to end the search, once we aren't pondering. After `ponderhit` we are
+    /// playing the move for real, so the time we were given counts from then rather than from
+    /// the start of the search.
+    fn stop_time(&self) -> Option<Instant> {
+        if self.was_pondering.replace(false) {
+            if let Some(stop_time) = self.stop_time.get() {
+                let move_time = stop_time.saturating_duration_since(self.start_time);
+                self.stop_time.set(Some(Instant::now() + move_time));
+            }
+        }
+
+        self.stop_time.get()
+    }
+
     fn stopping(&self) -> bool {
         self.stopping.load(Ordering::Relaxed)
-            || self
-                .stop_time
-                .map(|s| s <= std::time::Instant::now())
-                .unwrap_or(false)
+            || (!self.pondering()
+                && self
+                    .stop_time()
+                    .map(|s| s <= std::time::Instant::now())
+                    .unwrap_or(false))
+            // We always finish the first iteration, so that there is a move to play.
+            || (self.depth_reached > 0
+                && self.node_limit.is_some_and(|n| self.total_nodes() >= n))
     }
 
     /// Returns the static evaluation, from the perspective of the side to move.
@@ -558,7 +1302,8 @@ impl<'engine> Search<'engine> {
         let material = (self.pos.material_eval() * self.pov()) as f32;
         let hmc = (50 - std::cmp::min(self.pos.half_move_clock(), 50)) as f32 / 50.;
         let scaled_material = (material * hmc).round() as i16;
-        Score::cp(scaled_material)
+        let variant = self.pos.variant_eval() * self.pov();
+        Score::cp(scaled_material + variant)
     }
 
     /// Returns 1 if the player to move is White, -1 if Black. Useful wherever we are using
@@ -572,9 +1317,102 @@ impl<'engine> Search<'engine> {
         }
     }
 
+    /// The piece and destination square of the moves played one and two plies before the current
+    /// position, where they exist and weren't null moves.
+    fn prev_moves(&self) -> [Option<(Piece, Square)>; 2] {
+        let mut prev = self
+            .pos
+            .history()
+            .iter()
+            .rev()
+            .map(|m| (!m.is_null()).then_some((m.piece, m.dest)));
+
+        [prev.next().flatten(), prev.next().flatten()]
+    }
+
+    /// The history score of a quiet move in the current position, combining the butterfly and
+    /// continuation histories.
+    fn quiet_history(&self, mov: &Move) -> i32 {
+        let piece = self.pos.piece_at_sq(mov.orig());
+        let mut history = self
+            .histories
+            .quiet
+            .get(mov.orig(), mov.dest(), self.pos.turn()) as i32;
+
+        for prev in self.prev_moves().into_iter().flatten() {
+            history += self.histories.continuation.get(prev, piece, mov.dest()) as i32;
+        }
+
+        history
+    }
+
+    /// The type of the piece captured by `mov`.
+    fn captured_type(&self, mov: &Move) -> PieceType {
+        if mov.is_en_passant() {
+            PieceType::Pawn
+        } else {
+            self.pos.piece_at_sq(mov.dest()).type_of()
+        }
+    }
+
+    /// The ordering score of a capture in the current position. Its SEE decides whether it falls
+    /// in the good, equal or bad band, and the capture history orders the good and equal captures
+    /// within their bands.
+    fn capture_score(&mut self, mov: &Move) -> i16 {
+        let piece = self.pos.piece_at_sq(mov.orig());
+        let captured = self.captured_type(mov);
+        let see = self
+            .see(mov.orig(), mov.dest(), captured, piece.type_of())
+            .to_i16() as i32;
+        let history = self.histories.capture.get(piece, mov.dest(), captured) as i32 / 16;
+        let good = GOOD_CAPTURE as i32;
+
+        let score = match see {
+            s if s > 0 => (good + 4 * s + history).clamp(good, i16::MAX as i32),
+            0 => good / 2 + history,
+            s => s.max(-(i16::MAX as i32)),
+        };
+
+        score as i16
+    }
+
+    /// Apply a history bonus, or a malus if negative, to a capture in the current position.
+    fn update_capture_history(&mut self, mov: &Move, bonus: i32) {
+        let piece = self.pos.piece_at_sq(mov.orig());
+        let captured = self.captured_type(mov);
+        self.histories
+            .capture
+            .update(piece, mov.dest(), captured, bonus);
+    }
+
+    /// Apply a history bonus, or a malus if negative, to a quiet move in the current position.
+    fn update_quiet_history(&mut self, mov: &Move, bonus: i32) {
+        let piece = self.pos.piece_at_sq(mov.orig());
+        self.histories
+            .quiet
+            .update(mov.orig(), mov.dest(), bonus, self.pos.turn());
+
+        for prev in self.prev_moves().into_iter().flatten() {
+            self.histories
+                .continuation
+                .update(prev, piece, mov.dest(), bonus);
+        }
+    }
+
     /// The quiescence search.
-    fn quiesce<T: Thread, Node: NodeType>(&mut self, mut alpha: Score, mut beta: Score) -> Score {
+    ///
+    /// When in check, every evasion is searched. Otherwise we search captures and promotions, plus
+    /// quiet moves which give check if `checks` is set, as it is at the first ply of quiescence.
+    fn quiesce<T: Thread, Node: NodeType>(
+        &mut self,
+        mut alpha: Score,
+        mut beta: Score,
+        checks: bool,
+    ) -> Score {
         self.trace.visit_q_node();
+        self.trace
+            .reach_ply((self.pos.history().len() - self.root_ply) as u8);
+        self.check_in::<T>();
 
         debug_assert!(!Node::root());
         debug_assert!(Score::INF_N <= alpha);
@@ -587,8 +1425,17 @@ impl<'engine> Search<'engine> {
             return Score::zero();
         }
 
-        // Step 1. Check for an immediate draw or max ply reached.
-        //         TODO
+        // Step 1. Check for a variant loss, immediate draw or max ply reached.
+        //         TODO: draws
+        if self.pos.variant_loss() {
+            return Score::mate(0);
+        }
+
+        // Chains of checks and evasions can run on for a while, so qsearch needs the same guard
+        // as the main search.
+        if self.pos.history().len() - self.root_ply >= MAX_PLY as usize {
+            return self.evaluate();
+        }
 
         // Step 2. Load transposition table entry.
         let (tt_entry, tt_mov, tt_value) = {
@@ -649,10 +1496,17 @@ impl<'engine> Search<'engine> {
             }
         }
 
-        // Step 4. Static evaluation.
-        let stand_pat = match tt_value {
-            Some(s) => s,
-            None => self.evaluate(),
+        let in_check = self.pos.in_check();
+
+        // Step 4. Static evaluation. We can't stand pat in check, since the position may be lost,
+        // so we start from a mated score and let the evasions raise it.
+        let stand_pat = if in_check {
+            Score::mate(0)
+        } else {
+            match tt_value {
+                Some(s) => s,
+                None => self.evaluate(),
+            }
         };
 
         if stand_pat >= beta {
@@ -663,29 +1517,34 @@ impl<'engine> Search<'engine> {
             alpha = stand_pat;
         }
 
-        // TODO: deal with this by looking at quiet check evasions, rather than going back to main
-        // search.
-        if self.pos.in_check() {
-            // A one move search extension. The main alphabeta function will tell us if we are in
-            // checkmate or stalemate, and if not, it will try the possible evasions.
-
-            // Commenting out as this is currently causing stack overflows. We need to generate
-            // evasions when in check, instead of dropping back to main search.
-            // return self.search::<T, Pv>(alpha, beta, 1);
-        }
-
         let mut score: Score;
         let mut moves = OrderedMoves::new();
+        let mut legal_moves = false;
 
         // Step 5. Loop through all the moves until no moves remain or a beta cutoff occurs.
-        'move_loop: while moves.load_next_phase(QMoveLoader::from(self)) {
+        'move_loop: while moves.load_next_phase(QMoveLoader::from(self, checks)) {
             for mov in &moves {
                 if self.stopping() {
                     break 'move_loop;
                 }
 
+                // Delta pruning: skip captures which can't raise alpha even if they win the
+                // captured piece for free, with a margin for positional gains.
+                if !in_check && mov.is_capture() && !mov.is_promo() && stand_pat.is_cp() {
+                    let captured = self.captured_type(mov);
+
+                    if stand_pat + Score::cp(piece_value(captured) + 200) < alpha {
+                        self.trace.delta_prune();
+                        continue;
+                    }
+                }
+
+                legal_moves = true;
                 self.pos.make_move(mov);
-                score = self.quiesce::<T, Node>(-beta, -alpha).neg().inc_mate();
+                score = self
+                    .quiesce::<T, Node>(-beta.dec_mate(), -alpha.dec_mate(), false)
+                    .neg()
+                    .inc_mate();
                 self.pos.unmake_move();
 
                 if score >= beta {
@@ -698,29 +1557,131 @@ impl<'engine> Search<'engine> {
             }
         }
 
+        // Step 6. With every evasion generated, no legal moves in check means checkmate.
+        if in_check && !legal_moves && !self.stopping() {
+            return Score::mate(0);
+        }
+
         alpha
     }
 
-    fn report_pv(&self, depth: u8, score: Score) {
+    /// Every so often, publish our stats for the master thread to report. On the master thread,
+    /// also report the stats if we haven't sent anything for a while, so that GUIs can see a long
+    /// iteration is still making progress.
+    #[inline(always)]
+    fn check_in<T: Thread>(&mut self) {
+        if self.trace.all_nodes_visited() & (CHECK_IN_NODES - 1) != 0 {
+            return;
+        }
+
+        self.publish_stats();
+
+        if T::is_master() && self.last_report.elapsed() >= REPORT_INTERVAL {
+            self.report_stats();
+        }
+    }
+
+    /// Add our latest stats to the totals across all threads, if we share them.
+    fn publish_stats(&mut self) {
+        if let Some(shared) = self.shared {
+            self.trace.publish(shared);
+        }
+    }
+
+    /// The number of nodes visited by all threads of the search.
+    fn total_nodes(&self) -> usize {
+        self.shared.map_or(self.trace.all_nodes_visited(), |s| {
+            s.nodes() + self.trace.unpublished_nodes()
+        })
+    }
+
+    /// The number of tablebase hits by all threads of the search.
+    fn total_tb_hits(&self) -> usize {
+        self.shared.map_or(self.trace.tb_hits(), |s| s.tb_hits())
+    }
+
+    /// The selective depth reached by any thread of the search.
+    fn total_seldepth(&self) -> u8 {
+        self.shared.map_or(self.trace.seldepth(), |s| s.seldepth())
+    }
+
+    /// The nodes per second of all threads, given the time elapsed in milliseconds.
+    fn total_nps(&self, time: usize) -> u32 {
+        (self.total_nodes() * 1000 / std::cmp::max(time, 1)) as u32
+    }
+
+    /// Report the principal variation `pv`, which is line number `multipv`, and its score, which is
+    /// only a `bound` if the search failed outside its aspiration window.
+    fn report_pv(&mut self, depth: u8, multipv: usize, bound: Bound, pv: &PvLine) {
+        self.publish_stats();
+        self.last_report = Instant::now();
+        let time = self.trace.live_elapsed().as_millis() as usize;
+
+        // The search can't see a tablebase win at the root beyond its horizon, so we report the
+        // tablebase score unless the search found a mate.
+        let (score, bound) = match self.root_tb_score {
+            Some(tb_score) if !pv.score.is_mate() => (tb_score, Bound::Exact),
+            _ => (pv.score, bound),
+        };
+
         println!(
             "{}",
             Info::Pv(PvInfo {
                 depth,
+                seldepth: self.total_seldepth(),
+                multipv,
                 score,
-                time: self.trace.live_elapsed().as_millis() as usize,
-                nodes: self.trace.nodes_visited(),
-                pv: self
-                    .pvt
-                    .pv()
+                bound,
+                time,
+                nodes: self.total_nodes(),
+                pv: pv
+                    .moves
+                    .iter()
                     .map(|m| format!("{}", m))
                     .intersperse(" ".to_string())
                     .collect::<String>(),
                 hashfull: self.tt.hashfull(),
-                nps: self.trace.live_nps() as u32,
+                tbhits: self.total_tb_hits(),
+                nps: self.total_nps(time),
             })
         );
     }
 
+    /// Report the search stats on their own, and the line currently being searched if
+    /// `UCI_ShowCurrLine` is on.
+    fn report_stats(&mut self) {
+        self.last_report = Instant::now();
+        let time = self.trace.live_elapsed().as_millis() as usize;
+
+        println!(
+            "{}",
+            Info::Stats(StatsInfo {
+                depth: self.search_depth,
+                seldepth: self.total_seldepth(),
+                time,
+                nodes: self.total_nodes(),
+                hashfull: self.tt.hashfull(),
+                tbhits: self.total_tb_hits(),
+                nps: self.total_nps(time),
+            })
+        );
+
+        if self.show_curr_line {
+            // A null move isn't a move the GUI could play, so the line stops short of any.
+            println!(
+                "{}",
+                Info::CurrLine(CurrLineInfo {
+                    currline: self.pos.history()[self.root_ply..]
+                        .iter()
+                        .take_while(|m| !m.is_null())
+                        .map(|m| m.to_uci_string())
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                })
+            );
+        }
+    }
+
     fn report_curr_move(&self, depth: u8, mov: &Move, num: u8) {
         println!(
             "{}",
@@ -754,6 +1715,42 @@ impl<'engine> Search<'engine> {
                 "see skips: {}",
                 self.trace.see_skipped_nodes().separated_string()
             );
+            println!(
+                "null move: {} cutoffs from {} tries",
+                self.trace.null_move_cutoffs().separated_string(),
+                self.trace.null_move_tries().separated_string()
+            );
+            println!(
+                "probcut:   {} cutoffs from {} tries",
+                self.trace.probcut_cutoffs().separated_string(),
+                self.trace.probcut_tries().separated_string()
+            );
+            println!(
+                "lmr:       {} re-searches from {} reductions",
+                self.trace.lmr_researches().separated_string(),
+                self.trace.lmr_reductions().separated_string()
+            );
+            println!(
+                "futility:  {} moves pruned, {} delta prunes",
+                self.trace.futility_prunes().separated_string(),
+                self.trace.delta_prunes().separated_string()
+            );
+            println!(
+                "singular:  {}",
+                self.trace.singular_extensions().separated_string()
+            );
+            println!(
+                "iir:       {}",
+                self.trace.iir_reductions().separated_string()
+            );
+            println!(
+                "ttd:       {}",
+                (1..=self.depth_reached)
+                    .filter_map(|d| self.trace.time_to_depth(d))
+                    .map(|t| format!("{}ms", t.as_millis()))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            );
             println!(
                 "time:      {}ms",
                 self.trace
@@ -791,8 +1788,10 @@ impl<'engine> Search<'engine> {
             println!("-------------------------");
             println!(
                 "pv:        {}",
-                self.pvt
-                    .pv()
+                self.pv_lines
+                    .first()
+                    .map_or(&[][..], |l| &l.moves)
+                    .iter()
                     .map(|m| m.to_uci_string())
                     .collect::<Vec<String>>()
                     .join(" ")
@@ -809,6 +1808,36 @@ impl<'engine> Search<'engine> {
         }
     }
 
+    /// The reply we expect to `best_move`, for the GUI to ponder on: the next move of the
+    /// principal variation, or failing that the TT move after `best_move`.
+    fn ponder_move(&mut self, best_move: Move) -> Option<Move> {
+        if best_move.is_null() {
+            return None;
+        }
+
+        if let Some(line) = self.pv_lines.first() {
+            if line.moves.first() == Some(&best_move) && line.moves.len() > 1 {
+                return Some(line.moves[1]);
+            }
+        }
+
+        self.pos.make_move(&best_move);
+        let ponder = match self.tt.probe(&self.pos) {
+            super::tt::Probe::Hit(entry) => {
+                let e = entry.read();
+                if e.mov.is_null() {
+                    None
+                } else {
+                    Some(e.mov.to_move(&self.pos)).filter(|m| self.pos.valid_move(m))
+                }
+            }
+            _ => None,
+        };
+        self.pos.unmake_move();
+
+        ponder
+    }
+
     fn report_best_move(&self) {
         // Get TT entry.
         let entry = self.tt.probe(&self.pos).into_inner();
@@ -821,17 +1850,59 @@ impl<'engine> Search<'engine> {
 pub struct MoveLoader<'a, 'search> {
     search: &'a mut Search<'search>,
     hash_move: Option<Move>,
+    excluded: Option<Move>,
     draft: u8,
+    /// Whether we are loading the moves of the root, which may be restricted.
+    root: bool,
 }
 
 impl<'a, 'engine> MoveLoader<'a, 'engine> {
     /// Create a `MoveLoader` from the passed `Search`.
     #[inline(always)]
-    pub fn from(search: &'a mut Search<'engine>, hash_move: Option<Move>, draft: u8) -> Self {
+    pub fn from(
+        search: &'a mut Search<'engine>,
+        hash_move: Option<Move>,
+        excluded: Option<Move>,
+        draft: u8,
+        root: bool,
+    ) -> Self {
+        let hash_move = hash_move
+            .filter(|m| Some(*m) != excluded)
+            .filter(|m| !root || search.root_move_allowed(m));
+
         MoveLoader {
             search,
             hash_move,
+            excluded,
             draft,
+            root,
+        }
+    }
+
+    /// Whether `mov` may be searched here. Only the root restricts its moves.
+    #[inline(always)]
+    fn allowed(&self, mov: &Move) -> bool {
+        !self.root || self.search.root_move_allowed(mov)
+    }
+
+    /// Whether any of the promotions on the squares of the promotion `mov` may be searched.
+    fn any_promotion_allowed(&self, mov: &Move) -> bool {
+        PROMOTION_TYPES
+            .iter()
+            .any(|pt| self.allowed(&mov.set_promo_type(*pt)))
+    }
+
+    /// Load the legal moves of kind `G`, leaving out any which may not be searched.
+    fn load_allowed<G: Generate>(&mut self, movelist: &mut ScoredMoveList) {
+        if self.root {
+            let moves = self.search.pos.generate::<BasicMoveList, G, Legal>();
+            for mov in &moves {
+                if self.allowed(mov) {
+                    movelist.push(*mov);
+                }
+            }
+        } else {
+            self.search.pos.generate_in::<_, G, Legal>(movelist);
         }
     }
 }
@@ -850,61 +1921,90 @@ impl<'a, 'search> Loader for MoveLoader<'a, 'search> {
         }
     }
 
+    fn load_excluded(&mut self, movelist: &mut ScoredMoveList) {
+        if let Some(mv) = self.excluded {
+            movelist.push(mv);
+        }
+
+        // Underpromotions are made from the queen promotions, so at the root we exclude those
+        // which come from a queen promotion we load but may not be searched themselves.
+        if self.root {
+            let promos = self
+                .search
+                .pos
+                .generate::<BasicMoveList, QueenPromotions, Legal>();
+            for mov in promos.iter().filter(|m| self.any_promotion_allowed(m)) {
+                for pt in PROMOTION_TYPES {
+                    let promo = mov.set_promo_type(pt);
+                    if !self.allowed(&promo) {
+                        movelist.push(promo);
+                    }
+                }
+            }
+        }
+    }
+
     fn load_promotions(&mut self, movelist: &mut ScoredMoveList) {
-        self.search
-            .pos
-            .generate_in::<_, QueenPromotions, Legal>(movelist);
+        if self.root {
+            let promos = self
+                .search
+                .pos
+                .generate::<BasicMoveList, QueenPromotions, Legal>();
+            for mov in promos.iter().filter(|m| self.any_promotion_allowed(m)) {
+                movelist.push(*mov);
+            }
+        } else {
+            self.search
+                .pos
+                .generate_in::<_, QueenPromotions, Legal>(movelist);
+        }
     }
 
     fn load_captures(&mut self, movelist: &mut ScoredMoveList) {
-        self.search.pos.generate_in::<_, Captures, Legal>(movelist);
+        self.load_allowed::<Captures>(movelist);
     }
 
     fn load_killers(&mut self, movelist: &mut ScoredMoveList) {
         let (km1, km2) = self.search.kt.probe(self.draft, &self.search.pos);
         let mut cnt = 0;
 
-        if km1.is_some() {
+        if let Some(km) = km1.filter(|m| self.allowed(m)) {
             cnt += 1;
-            movelist.push(km1.unwrap());
+            movelist.push(km);
         }
-        if km2.is_some() {
+        if let Some(km) = km2.filter(|m| self.allowed(m)) {
             cnt += 1;
-            movelist.push(km2.unwrap());
+            movelist.push(km);
         }
         self.search.trace.killers_per_node.push_many(cnt, 2);
     }
 
+    fn load_counter(&mut self, movelist: &mut ScoredMoveList) {
+        let counter = self.search.prev_moves()[0]
+            .and_then(|prev| self.search.histories.counter_moves.get(prev));
+
+        if let Some(mv) = counter.filter(|m| self.search.pos.valid_move(m) && self.allowed(m)) {
+            movelist.push(mv);
+        }
+    }
+
     fn load_quiets(&mut self, movelist: &mut ScoredMoveList) {
-        self.search.pos.generate_in::<_, Quiets, Legal>(movelist);
+        self.load_allowed::<Quiets>(movelist);
     }
 
     fn score_captures(&mut self, captures: Scorer) {
         for (mov, score) in captures {
             if mov.is_capture() {
-                *score = self
-                    .search
-                    .see(
-                        mov.orig(),
-                        mov.dest(),
-                        self.search.pos.piece_at_sq(mov.dest()).type_of(),
-                        self.search.pos.piece_at_sq(mov.orig()).type_of(),
-                    )
-                    .to_i16();
+                *score = self.search.capture_score(mov);
             }
         }
     }
 
     fn score_quiets(&mut self, quiets: Scorer) {
-        let turn = self.search.pos.turn();
         for (mov, score) in quiets {
-            // SAFETY: these are legal moves, so the squares must be valid.
-            unsafe {
-                *score = self
-                    .search
-                    .history
-                    .get_unchecked(mov.orig(), mov.dest(), turn) as i16;
-            }
+            // Keep clear of `i16::MIN`, which the selection sort never yields.
+            let max = i16::MAX as i32;
+            *score = self.search.quiet_history(mov).clamp(-max, max) as i16;
         }
     }
 }
@@ -912,13 +2012,15 @@ impl<'a, 'search> Loader for MoveLoader<'a, 'search> {
 /// Move loader for the quiescence search.
 pub struct QMoveLoader<'a, 'search> {
     search: &'a mut Search<'search>,
+    checks: bool,
 }
 
 impl<'a, 'engine> QMoveLoader<'a, 'engine> {
-    /// Create a `MoveLoader` from the passed `Search`.
+    /// Create a `QMoveLoader` from the passed `Search`. If `checks` is set, quiet moves which give
+    /// check are loaded as well as captures.
     #[inline(always)]
-    pub fn from(search: &'a mut Search<'engine>) -> Self {
-        QMoveLoader { search }
+    pub fn from(search: &'a mut Search<'engine>, checks: bool) -> Self {
+        QMoveLoader { search, checks }
     }
 }
 
@@ -935,36 +2037,38 @@ impl<'a, 'search> Loader for QMoveLoader<'a, 'search> {
 
     fn load_quiets(&mut self, movelist: &mut ScoredMoveList) {
         if self.search.pos.in_check() {
+            // All evasions must be searched to detect checkmate.
             self.search.pos.generate_in::<_, Quiets, Legal>(movelist);
+        } else if self.checks {
+            // Quiet checks which lose material to SEE are not worth searching.
+            let quiets = self.search.pos.generate::<BasicMoveList, Quiets, Legal>();
+            for mov in &quiets {
+                if self.search.pos.gives_check(mov) {
+                    let moving = self.search.pos.piece_at_sq(mov.orig()).type_of();
+                    let see = self
+                        .search
+                        .see(mov.orig(), mov.dest(), PieceType::None, moving);
+                    if see >= Score::zero() {
+                        movelist.push(*mov);
+                    }
+                }
+            }
         }
     }
 
     fn score_captures(&mut self, captures: Scorer) {
         for (mov, score) in captures {
             if mov.is_capture() {
-                *score = self
-                    .search
-                    .see(
-                        mov.orig(),
-                        mov.dest(),
-                        self.search.pos.piece_at_sq(mov.dest()).type_of(),
-                        self.search.pos.piece_at_sq(mov.orig()).type_of(),
-                    )
-                    .to_i16();
+                *score = self.search.capture_score(mov);
             }
         }
     }
 
     fn score_quiets(&mut self, quiets: Scorer) {
-        let turn = self.search.pos.turn();
         for (mov, score) in quiets {
-            // SAFETY: these are legal moves, so the squares must be valid.
-            unsafe {
-                *score = self
-                    .search
-                    .history
-                    .get_unchecked(mov.orig(), mov.dest(), turn) as i16;
-            }
+            // Keep clear of `i16::MIN`, which the selection sort never yields.
+            let max = i16::MAX as i32;
+            *score = self.search.quiet_history(mov).clamp(-max, max) as i16;
         }
     }
 }
@@ -985,7 +2089,7 @@ mod tests {
                 ("5R2/1p1r2pk/p1n1B2p/2P1q3/2Pp4/P6b/1B1P4/2K3R1 w - - 5 3", 6, Score::mate(5), Score::mate(5), "e6g8"),
                 ("1r6/p5pk/1q1p2pp/3P3P/4Q1P1/3p4/PP6/3KR3 w - - 0 36", 6, Score::mate(5), Score::mate(5), "h5g6"),
                 ("1r4k1/p3p1bp/5P1r/3p2Q1/5R2/3Bq3/P1P2RP1/6K1 b - - 0 33", 6, Score::mate(5), Score::mate(5), "b8b1"),
-                ("2q4k/3r3p/2p2P2/p7/2P5/P2Q2P1/5bK1/1R6 w - - 0 36", 6, Score::mate(5), Score::mate(5), "d3d7"),
+                ("2q4k/3r3p/2p2P2/p7/2P5/P2Q2P1/5bK1/1R6 w - - 0 36", 7, Score::mate(5), Score::mate(5), "d3d7"),
                 ("5rk1/rb3ppp/p7/1pn1q3/8/1BP2Q2/PP3PPP/3R1RK1 w - - 7 21", 6, Score::mate(5), Score::mate(5), "f3f7"),
                 ("6rk/p7/1pq1p2p/4P3/5BrP/P3Qp2/1P1R1K1P/5R2 b - - 0 34", 8, Score::mate(7), Score::mate(7), "g4g2"),
                 ("6k1/1p2qppp/4p3/8/p2PN3/P5QP/1r4PK/8 w - - 0 40", 6, Score::mate(5), Score::mate(5), "e4f6"),
@@ -995,7 +2099,7 @@ mod tests {
 
                 // // Winning material
                 ("rn1q1rk1/5pp1/pppb4/5Q1p/3P4/3BPP1P/PP3PK1/R1B2R2 b - - 1 15", 7, Score::cp(290), Score::cp(310), "g7g6"),
-                ("4k3/8/8/4q3/8/8/7P/3K2R1 w - - 0 1", 3, Score::cp(100), Score::cp(100), "g1e1"), 
+                ("4k3/8/8/4q3/8/8/7P/3K2R1 w - - 0 1", 3, Score::cp(0), Score::cp(0), "g1e1"), // Qxe1+ Kxe1 leaves a drawn KPK with a rook pawn
                 ("6k1/8/3q4/8/8/3B4/2P5/1K1R4 w - - 0 1", 3, Score::cp(850), Score::cp(950), "d3c4"),
                 ("r5k1/p1P5/8/8/8/8/3RK3/8 w - - 0 1", 6, Score::cp(900), Score::cp(900), "d2d8"),
                 ("6k1/8/8/3q4/8/8/P7/1KNB4 w - - 0 1", 4, Score::cp(380), Score::cp(420), "d1b3"),
@@ -1028,4 +2132,123 @@ mod tests {
             assert_eq!(m.to_uci_string(), bm);
         }
     }
+
+    #[test]
+    fn multi_pv_reports_distinct_sorted_lines() {
+        core::init::init_globals();
+
+        // Rxd8 mates on the back rank; the other lines must follow it, headed by different moves.
+        let pos = Position::from_fen("3q2k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let flag = AtomicBool::new(false);
+        let tt = Table::new(16);
+        let mut search = Search::new(pos, &flag, None, &tt);
+        search.set_multi_pv(3);
+        let (s, m) = search.run::<Master>(5);
+
+        assert_eq!(m.to_uci_string(), "d1d8");
+        assert_eq!(search.pv_lines.len(), 3);
+        assert_eq!(search.pv_lines[0].score, s);
+        assert!(search.pv_lines.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let mut heads: Vec<String> = search
+            .pv_lines
+            .iter()
+            .map(|l| l.moves[0].to_uci_string())
+            .collect();
+        heads.sort();
+        heads.dedup();
+        assert_eq!(heads.len(), 3);
+    }
+
+    #[test]
+    fn search_moves_restrict_the_root() {
+        core::init::init_globals();
+
+        // Rxd8 mates, but we only allow the search a quiet rook move and a pawn push.
+        let pos = Position::from_fen("3q2k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let allowed: Vec<Move> = pos
+            .generate::<BasicMoveList, AllGen, Legal>()
+            .iter()
+            .filter(|m| ["d1e1", "h2h3"].contains(&m.to_uci_string().as_str()))
+            .copied()
+            .collect();
+        assert_eq!(allowed.len(), 2);
+
+        let flag = AtomicBool::new(false);
+        let tt = Table::new(16);
+        let mut search = Search::new(pos, &flag, None, &tt);
+        search.set_search_moves(allowed.clone());
+        let (s, m) = search.run::<Master>(4);
+
+        assert!(allowed.contains(&m));
+        assert!(!s.is_mate());
+    }
+
+    #[test]
+    fn node_limit_ends_search() {
+        core::init::init_globals();
+
+        let flag = AtomicBool::new(false);
+        let tt = Table::new(16);
+        let mut search = Search::new(Position::start_pos(), &flag, None, &tt);
+        search.set_node_limit(Some(20_000));
+        let (_, m) = search.run::<Master>(MAX_PLY);
+
+        assert!(!m.is_null());
+        assert!(search.trace.all_nodes_visited() < 20_100);
+    }
+
+    #[test]
+    fn mate_limit_ends_search() {
+        core::init::init_globals();
+
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R3R1K1 w - - 0 1").unwrap();
+        let flag = AtomicBool::new(false);
+        let tt = Table::new(16);
+        let mut search = Search::new(pos, &flag, None, &tt);
+        search.set_mate_limit(Some(3));
+        let (s, m) = search.run::<Master>(5);
+
+        assert_eq!(s.moves_to_mate(), Some(1));
+        assert!(["a1a8", "e1e8"].contains(&m.to_uci_string().as_str()));
+        assert_eq!(search.depth_reached, 1);
+    }
+
+    #[test]
+    fn ponder_move_follows_best_move() {
+        core::init::init_globals();
+
+        let flag = AtomicBool::new(false);
+        let tt = Table::new(16);
+        let mut search = Search::new(Position::start_pos(), &flag, None, &tt);
+        let (_, m) = search.run::<Master>(6);
+
+        let ponder = search.ponder_move(m).unwrap();
+        assert_eq!(Some(&ponder), search.pv_lines[0].moves.get(1));
+
+        search.pos.make_move(&m);
+        assert!(search.pos.valid_move(&ponder));
+    }
+
+    #[test]
+    fn ponderhit_restarts_the_clock() {
+        core::init::init_globals();
+
+        let flag = AtomicBool::new(false);
+        let pondering = AtomicBool::new(true);
+        let tt = Table::new(16);
+        let stop_time = Instant::now() + Duration::from_millis(100);
+        let mut search = Search::new(Position::start_pos(), &flag, Some(stop_time), &tt);
+        search.set_ponder_flag(&pondering);
+
+        // Pondering for longer than the time we were given doesn't use any of it up.
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(!search.stopping());
+
+        pondering.store(false, Ordering::Relaxed);
+        assert!(!search.stopping());
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(search.stopping());
+    }
 }
@@ -0,0 +1,184 @@
+//! Constant tables used to map a position to its index in a Syzygy table.
+//!
+//! These follow the layout of the reference probing code exactly, since the index of a position
+//! is baked into the files. Squares are numbered from a1 = 0 to h8 = 63.
+
+/// The maximum number of pieces in a table supported by the file format.
+pub const TB_PIECES: usize = 7;
+
+/// The rank of a square minus its file. Negative below the a1-h8 diagonal, zero on it and
+/// positive above it.
+pub const fn off_a1h8(sq: u8) -> i8 {
+    (sq >> 3) as i8 - (sq & 7) as i8
+}
+
+/// Encodes a square below the a1-h8 diagonal to 0..27.
+pub const MAP_B1H1H7: [u8; 64] = {
+    let mut map = [0; 64];
+    let mut code = 0;
+    let mut sq = 0;
+    while sq < 64 {
+        if off_a1h8(sq) < 0 {
+            map[sq as usize] = code;
+            code += 1;
+        }
+        sq += 1;
+    }
+    map
+};
+
+/// Encodes a square in the a1-d1-d4 triangle to 0..9. Squares strictly below the diagonal come
+/// first, followed by the four diagonal squares.
+pub const MAP_A1D1D4: [u8; 64] = {
+    let mut map = [0; 64];
+    let mut code = 0;
+    let mut sq = 0;
+    while sq <= 27 {
+        if off_a1h8(sq) < 0 && sq & 7 <= 3 {
+            map[sq as usize] = code;
+            code += 1;
+        }
+        sq += 1;
+    }
+    sq = 0;
+    while sq <= 27 {
+        if off_a1h8(sq) == 0 && sq & 7 <= 3 {
+            map[sq as usize] = code;
+            code += 1;
+        }
+        sq += 1;
+    }
+    map
+};
+
+/// Encodes the 462 legal placements of two kings where the first is in the a1-d1-d4 triangle,
+/// indexed by `MAP_A1D1D4` of the first king and the square of the second. If the first king is on
+/// the a1-d4 diagonal, the second may not be above the a1-h8 diagonal. Placements with both kings
+/// on the diagonal are encoded last.
+pub const MAP_KK: [[u16; 64]; 10] = {
+    const fn adjacent(a: u8, b: u8) -> bool {
+        let dr = (a >> 3) as i8 - (b >> 3) as i8;
+        let df = (a & 7) as i8 - (b & 7) as i8;
+        dr >= -1 && dr <= 1 && df >= -1 && df <= 1
+    }
+
+    let mut map = [[0; 64]; 10];
+    let mut both_on_diagonal = [(0usize, 0u8); 64];
+    let mut diagonal_count = 0;
+    let mut code = 0;
+
+    let mut idx = 0;
+    while idx < 10 {
+        let mut s1 = 0;
+        while s1 <= 27 {
+            // b1 is the only square mapped to 0, every other square outside the triangle also
+            // reads as 0.
+            if MAP_A1D1D4[s1 as usize] as usize == idx && (idx != 0 || s1 == 1) {
+                let mut s2 = 0;
+                while s2 < 64 {
+                    if adjacent(s1, s2) {
+                        // Illegal position
+                    } else if off_a1h8(s1) == 0 && off_a1h8(s2) > 0 {
+                        // First on the diagonal, second above it
+                    } else if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal[diagonal_count] = (idx, s2);
+                        diagonal_count += 1;
+                    } else {
+                        map[idx][s2 as usize] = code;
+                        code += 1;
+                    }
+                    s2 += 1;
+                }
+            }
+            s1 += 1;
+        }
+        idx += 1;
+    }
+
+    let mut i = 0;
+    while i < diagonal_count {
+        let (idx, s2) = both_on_diagonal[i];
+        map[idx][s2 as usize] = code;
+        code += 1;
+        i += 1;
+    }
+
+    map
+};
+
+/// `BINOMIAL[k][n]` is the number of ways to choose `k` elements from a set of `n`.
+pub const BINOMIAL: [[u64; 64]; TB_PIECES] = {
+    let mut binomial = [[0; 64]; TB_PIECES];
+    binomial[0][0] = 1;
+
+    let mut n = 1;
+    while n < 64 {
+        let mut k = 0;
+        while k < TB_PIECES && k <= n {
+            binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 }
+                + if k < n { binomial[k][n - 1] } else { 0 };
+            k += 1;
+        }
+        n += 1;
+    }
+
+    binomial
+};
+
+/// Encodes the squares a2-h7 to 0..47. This is the number of squares available to the other
+/// pawns of the leading group when the leading pawn is on the square: the leading pawn is the one
+/// with the highest value, i.e. nearest the edge and, among pawns on the same file, the one with
+/// the lowest rank.
+pub const MAP_PAWNS: [u8; 64] = {
+    let mut map = [0; 64];
+    let mut available: u8 = 47;
+
+    let mut file = 0;
+    while file < 4 {
+        let mut rank = 1;
+        while rank < 7 {
+            let sq = rank * 8 + file;
+            map[sq] = available;
+            map[sq ^ 7] = available - 1;
+            available = available.saturating_sub(2);
+            rank += 1;
+        }
+        file += 1;
+    }
+
+    map
+};
+
+/// The index of a leading pawn group of the given size, given the square of its leading pawn.
+/// Only defined for leading pawns on files a-d.
+pub const LEAD_PAWN_IDX: [[u64; 64]; 6] = lead_pawn_tables().0;
+
+/// The number of placements of a leading pawn group of the given size, for each leading pawn file.
+pub const LEAD_PAWNS_SIZE: [[u64; 4]; 6] = lead_pawn_tables().1;
+
+const fn lead_pawn_tables() -> ([[u64; 64]; 6], [[u64; 4]; 6]) {
+    let mut lead_pawn_idx = [[0; 64]; 6];
+    let mut lead_pawns_size = [[0; 4]; 6];
+
+    let mut count = 1;
+    while count <= 5 {
+        let mut file = 0;
+        while file < 4 {
+            // The table is split by the file of the leading pawn, so the index restarts on
+            // every file.
+            let mut idx = 0;
+            let mut rank = 1;
+            while rank < 7 {
+                let sq = rank * 8 + file;
+                lead_pawn_idx[count][sq] = idx;
+                idx += BINOMIAL[count - 1][MAP_PAWNS[sq] as usize];
+                rank += 1;
+            }
+            lead_pawns_size[count][file] = idx;
+            file += 1;
+        }
+        count += 1;
+    }
+
+    (lead_pawn_idx, lead_pawns_size)
+}
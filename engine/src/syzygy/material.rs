@@ -0,0 +1,136 @@
+//! Material signatures, used to find the table for a position.
+
+use core::position::{PieceType, Player, Position};
+
+use super::encoding::TB_PIECES;
+
+/// Piece letters in the order used in table names, strongest first.
+const PIECE_CHARS: [(char, PieceType); 6] = [
+    ('K', PieceType::King),
+    ('Q', PieceType::Queen),
+    ('R', PieceType::Rook),
+    ('B', PieceType::Bishop),
+    ('N', PieceType::Knight),
+    ('P', PieceType::Pawn),
+];
+
+/// The number of pieces of each type for each player, indexed by `[player][piece type - 1]`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Material([[u8; 6]; 2]);
+
+impl Material {
+    /// Parse a table name such as `KRPvKR`. The first side is white, and must be the stronger.
+    pub fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let mut counts = [[0; 6]; 2];
+
+        for (side, pieces) in [white, black].iter().enumerate() {
+            for c in pieces.chars() {
+                let (_, pt) = PIECE_CHARS.iter().find(|(ch, _)| *ch == c)?;
+                counts[side][*pt as usize - 1] += 1;
+            }
+            if counts[side][PieceType::King as usize - 1] != 1 {
+                return None;
+            }
+        }
+
+        let material = Material(counts);
+        if material.piece_count() > TB_PIECES {
+            return None;
+        }
+
+        Some(material)
+    }
+
+    /// The material on the board in `pos`.
+    pub fn from_position(pos: &Position) -> Material {
+        let mut counts = [[0; 6]; 2];
+
+        for (side, player) in [Player::WHITE, Player::BLACK].into_iter().enumerate() {
+            for (_, pt) in PIECE_CHARS {
+                counts[side][pt as usize - 1] = pos.piece_bb(player, pt).popcnt() as u8;
+            }
+        }
+
+        Material(counts)
+    }
+
+    /// A key identifying this material, distinct for every possible material signature.
+    pub fn key(&self) -> u64 {
+        self.0
+            .iter()
+            .flatten()
+            .fold(0, |key, &count| (key << 4) | count as u64)
+    }
+
+    /// The same material with one more piece of type `pt` for `side`.
+    pub fn with(&self, side: usize, pt: PieceType) -> Material {
+        let mut counts = self.0;
+        counts[side][pt as usize - 1] += 1;
+        Material(counts)
+    }
+
+    /// The same material with one fewer piece of type `pt` for `side`.
+    pub fn without(&self, side: usize, pt: PieceType) -> Material {
+        let mut counts = self.0;
+        counts[side][pt as usize - 1] -= 1;
+        Material(counts)
+    }
+
+    /// The same material with the colours swapped.
+    pub fn flipped(&self) -> Material {
+        Material([self.0[1], self.0[0]])
+    }
+
+    /// Whether both sides have the same pieces.
+    pub fn is_symmetric(&self) -> bool {
+        self.0[0] == self.0[1]
+    }
+
+    pub fn count(&self, side: usize, pt: PieceType) -> u8 {
+        self.0[side][pt as usize - 1]
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.0.iter().flatten().map(|&c| c as usize).sum()
+    }
+
+    pub fn has_pawns(&self) -> bool {
+        self.count(0, PieceType::Pawn) + self.count(1, PieceType::Pawn) > 0
+    }
+
+    /// Whether either side has a piece other than the king of which there is only one.
+    pub fn has_unique_pieces(&self) -> bool {
+        self.0.iter().any(|side| side[..5].contains(&1))
+    }
+
+    /// The side whose pawns lead the encoding, 0 for white. When both sides have pawns this is
+    /// the side with fewer, since that compresses better.
+    pub fn lead_side(&self) -> usize {
+        let white = self.count(0, PieceType::Pawn);
+        let black = self.count(1, PieceType::Pawn);
+
+        if black == 0 || (white > 0 && black >= white) {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// The number of pawns of the leading side (`i = 0`) or the other side (`i = 1`).
+    pub fn pawn_count(&self, i: usize) -> usize {
+        self.count(self.lead_side() ^ i, PieceType::Pawn) as usize
+    }
+
+    /// The name of the table holding this material, e.g. `KRPvKR`.
+    pub fn name(&self) -> String {
+        let side = |s: usize| {
+            PIECE_CHARS
+                .iter()
+                .flat_map(|(c, pt)| std::iter::repeat_n(*c, self.count(s, *pt) as usize))
+                .collect::<String>()
+        };
+
+        format!("{}v{}", side(0), side(1))
+    }
+}
@@ -0,0 +1,701 @@
+//! Probing of Syzygy endgame tablebases.
+//!
+//! Syzygy tables come in two kinds. WDL tables (`.rtbw`) store whether a position is won, drawn
+//! or lost, taking the 50-move rule into account, and are small enough to probe during search. DTZ
+//! tables (`.rtbz`) store the distance to the next capture or pawn move (which resets the 50-move
+//! counter) on a path which preserves the WDL value, and are probed at the root so that we make
+//! progress towards actually winning a won endgame.
+//!
+//! Neither kind stores positions where a capture is available and the best move, since the
+//! generator resolves those with a short search. We do the same here, so probing needs a mutable
+//! `Position` to make and unmake those captures. Neither kind stores positions with castling
+//! rights, and positions with an en passant capture are handled by the capture search too.
+//!
+//! Tables are memory-mapped on first use. `Tablebases` only scans the configured directories for
+//! file names up front, so it is cheap to point at a directory holding a full set of tables.
+
+mod encoding;
+mod material;
+mod table;
+
+use super::eval::PAWN_VALUE;
+use super::score::Score;
+
+use core::mono_traits::{All, Legal};
+use core::mov::{Move, MoveType};
+use core::movelist::BasicMoveList;
+use core::position::{Piece, PieceType, Player, Position};
+
+pub use material::Material;
+
+use encoding::TB_PIECES;
+use table::{Table, TableKind};
+
+use std::collections::HashMap;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The highest DTZ rank given to a root move. Ranks are only compared with each other.
+const MAX_DTZ: i32 = 1 << 18;
+
+/// A win/draw/loss value from the point of view of the side to move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Wdl {
+    Loss = -2,
+    /// A loss which can be saved by the 50-move rule.
+    BlessedLoss = -1,
+    Draw = 0,
+    /// A win which can't be forced before the 50-move rule draws.
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+
+    /// The DTZ of a position whose best move is a zeroing move with this outcome.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Wdl::from_value(-(self as i32)).unwrap()
+    }
+}
+
+/// The outcome of probing the tablebases for the moves at the root.
+#[derive(Debug)]
+pub struct RootProbe {
+    /// Each legal move, with a rank where a higher rank is a better outcome.
+    pub moves: Vec<(Move, i32)>,
+    /// Whether the ranks come from DTZ tables, so that playing the best ranked move always makes
+    /// progress. Otherwise they come from WDL tables only.
+    pub dtz: bool,
+}
+
+impl RootProbe {
+    /// The best rank of any move.
+    pub fn best_rank(&self) -> i32 {
+        self.moves
+            .iter()
+            .map(|(_, rank)| *rank)
+            .max()
+            .unwrap_or(-MAX_DTZ)
+    }
+
+    /// The moves which share the best rank.
+    pub fn best_moves(&self) -> Vec<Move> {
+        let best = self.best_rank();
+        self.moves
+            .iter()
+            .filter(|(_, rank)| *rank == best)
+            .map(|(mov, _)| *mov)
+            .collect()
+    }
+
+    /// The score to report for a move of the given rank. Wins which can be forced within the
+    /// 50-move rule are reported as tablebase wins; cursed wins and blessed losses get a small
+    /// score which grows as the real result comes into reach.
+    pub fn score(rank: i32) -> Score {
+        let bound = MAX_DTZ - 100;
+
+        if rank >= bound {
+            Score::tb_win(0)
+        } else if rank > 0 {
+            Score::cp((3.max(rank - (MAX_DTZ - 200)) * PAWN_VALUE as i32 / 200) as i16)
+        } else if rank == 0 {
+            Score::zero()
+        } else if rank > -bound {
+            Score::cp(((-3).min(rank + (MAX_DTZ - 200)) * PAWN_VALUE as i32 / 200) as i16)
+        } else {
+            Score::tb_loss(0)
+        }
+    }
+}
+
+/// A table which is mapped the first time it is probed.
+#[derive(Default)]
+struct LazyTable {
+    path: Option<PathBuf>,
+    table: OnceLock<Option<Table>>,
+}
+
+impl LazyTable {
+    fn get(&self, kind: TableKind, material: Material) -> Option<&Table> {
+        let path = self.path.as_ref()?;
+        self.table
+            .get_or_init(|| match Table::open(path, kind, material) {
+                Ok(table) => Some(table),
+                Err(err) => {
+                    println!("info string couldn't open {}: {}", path.display(), err);
+                    None
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// The WDL and DTZ tables for one material signature.
+struct Entry {
+    /// The material as named by the files, with the stronger side as white.
+    material: Material,
+    wdl: LazyTable,
+    dtz: LazyTable,
+}
+
+/// A set of Syzygy tables found in one or more directories.
+#[derive(Default)]
+pub struct Tablebases {
+    entries: Vec<Entry>,
+    /// Index into `entries` by material key. Each entry is indexed under both colourings of its
+    /// material.
+    by_key: HashMap<u64, usize>,
+    /// The largest number of pieces in any WDL table found.
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// An empty set of tables, for which every probe fails.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find all the tables in `paths`, a list of directories separated as in the `PATH`
+    /// environment variable. Directories which can't be read are skipped.
+    pub fn open(paths: &str) -> Self {
+        let mut tb = Self::new();
+
+        for dir in std::env::split_paths(paths) {
+            let Ok(files) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for file in files.flatten() {
+                tb.add_file(&file.path());
+            }
+        }
+
+        tb
+    }
+
+    fn add_file(&mut self, path: &Path) {
+        let Some(kind) = [TableKind::Wdl, TableKind::Dtz]
+            .into_iter()
+            .find(|kind| path.extension() == Some(kind.extension().as_ref()))
+        else {
+            return;
+        };
+        let Some(material) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(Material::from_name)
+        else {
+            return;
+        };
+
+        let i = match self.by_key.get(&material.key()) {
+            Some(&i) => i,
+            None => {
+                self.entries.push(Entry {
+                    material,
+                    wdl: LazyTable::default(),
+                    dtz: LazyTable::default(),
+                });
+                let i = self.entries.len() - 1;
+                self.by_key.insert(material.key(), i);
+                self.by_key.insert(material.flipped().key(), i);
+                i
+            }
+        };
+
+        let entry = &mut self.entries[i];
+        match kind {
+            TableKind::Wdl => {
+                entry.wdl.path = Some(path.to_path_buf());
+                self.max_pieces = self.max_pieces.max(material.piece_count());
+            }
+            TableKind::Dtz => entry.dtz.path = Some(path.to_path_buf()),
+        }
+    }
+
+    /// The number of WDL tables found.
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|e| e.wdl.path.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The largest number of pieces, including kings, in a position we can probe.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Probe the WDL value of `pos`. Returns `None` if a table we need is missing or the position
+    /// has castling rights.
+    pub fn probe_wdl(&self, pos: &mut Position) -> Option<Wdl> {
+        if !pos.castling_rights().is_empty() {
+            return None;
+        }
+
+        self.search(pos, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Probe the DTZ value of `pos`, in plies. Positive for a win and negative for a loss, with
+    /// 100 added to the magnitude if the result is a cursed win or blessed loss, and zero for a
+    /// draw. A zeroing move counts as a DTZ of 1.
+    ///
+    /// Returns `None` if a table we need is missing or the position has castling rights.
+    pub fn probe_dtz(&self, pos: &mut Position) -> Option<i32> {
+        if !pos.castling_rights().is_empty() {
+            return None;
+        }
+
+        let (wdl, zeroing_best) = self.search(pos, true)?;
+
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+
+        // The table holds a "don't care" value when the best move zeroes the counter.
+        if zeroing_best {
+            return Some(wdl.dtz_before_zeroing());
+        }
+
+        if let Some(dtz) = self.probe_dtz_table(pos, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + 100 * cursed as i32) * wdl.signum());
+        }
+
+        // The table is stored for the other side to move, so do a 1-ply search and find the move
+        // which wins fastest, or loses slowest.
+        let mut min_dtz = i32::MAX;
+
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            let zeroing = mov.is_capture() || is_pawn_move(pos, mov);
+            let mut after = pos.play(mov);
+
+            // For zeroing moves we want the DTZ of the move before it is played, but we still
+            // search the position after it to find the sign, since we could be making a losing
+            // capture.
+            let mut dtz = if zeroing {
+                -self.search(&mut after, false)?.0.dtz_before_zeroing()
+            } else {
+                -self.probe_dtz(&mut after)?
+            };
+
+            // A mating move has a DTZ of 1.
+            if dtz == 1 && after.in_checkmate() {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // If there are no legal moves, we have been mated.
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// Rank the legal moves at the root. Uses DTZ tables where possible, so that the best ranked
+    /// moves both preserve the result and make progress towards it, and falls back to WDL tables.
+    ///
+    /// Returns `None` if the position can't be probed.
+    pub fn probe_root(&self, pos: &mut Position) -> Option<RootProbe> {
+        if !pos.castling_rights().is_empty() || pos.occupied().popcnt() as usize > self.max_pieces {
+            return None;
+        }
+
+        self.probe_root_dtz(pos)
+            .map(|moves| RootProbe { moves, dtz: true })
+            .or_else(|| {
+                self.probe_root_wdl(pos)
+                    .map(|moves| RootProbe { moves, dtz: false })
+            })
+    }
+
+    fn probe_root_dtz(&self, pos: &mut Position) -> Option<Vec<(Move, i32)>> {
+        let cnt50 = pos.half_move_clock() as i32;
+        let rep = has_repeated(pos);
+        let mut ranked = Vec::new();
+
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            let mut after = pos.play(mov);
+
+            let mut dtz = if after.half_move_clock() == 0 {
+                // A zeroing move, so the DTZ is one of -101, -1, 0, 1 or 101.
+                -self.search(&mut after, false)?.0.dtz_before_zeroing()
+            } else if after.in_threefold() || after.half_move_clock() >= 100 {
+                0
+            } else {
+                // Otherwise take the DTZ of the new position and correct by one ply.
+                let dtz = -self.probe_dtz(&mut after)?;
+                dtz + dtz.signum()
+            };
+
+            // A mating move has a DTZ of 1.
+            if dtz == 2 && after.in_checkmate() {
+                dtz = 1;
+            }
+
+            // Wins which can be forced within the 50-move rule rank equally, as do losses unless
+            // a 50-move draw is in sight.
+            let rank = if dtz > 0 {
+                if dtz + cnt50 <= 99 && !rep {
+                    MAX_DTZ
+                } else {
+                    MAX_DTZ - (dtz + cnt50)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + cnt50 < 100 {
+                    -MAX_DTZ
+                } else {
+                    -MAX_DTZ + (-dtz + cnt50)
+                }
+            } else {
+                0
+            };
+
+            ranked.push((*mov, rank));
+        }
+
+        Some(ranked)
+    }
+
+    fn probe_root_wdl(&self, pos: &mut Position) -> Option<Vec<(Move, i32)>> {
+        let mut ranked = Vec::new();
+
+        for mov in &pos.generate::<BasicMoveList, All, Legal>() {
+            let wdl = -self.probe_wdl(&mut pos.play(mov))?;
+            let rank = match wdl {
+                Wdl::Loss => -MAX_DTZ,
+                Wdl::BlessedLoss => -MAX_DTZ + 101,
+                Wdl::Draw => 0,
+                Wdl::CursedWin => MAX_DTZ - 101,
+                Wdl::Win => MAX_DTZ,
+            };
+
+            ranked.push((*mov, rank));
+        }
+
+        Some(ranked)
+    }
+
+    /// Resolve captures (and for DTZ, pawn moves) with a search before probing the table, since
+    /// the tables don't store positions where one of these is the best move. Returns the WDL value
+    /// and whether the best move is a zeroing move.
+    fn search(&self, pos: &mut Position, check_zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = pos.generate::<BasicMoveList, All, Legal>();
+        let mut best = Wdl::Loss;
+        let mut move_count = 0;
+
+        for mov in &moves {
+            if !mov.is_capture() && (!check_zeroing_moves || !is_pawn_move(pos, mov)) {
+                continue;
+            }
+
+            move_count += 1;
+            let value = -self.search(&mut pos.play(mov), false)?.0;
+
+            if value > best {
+                best = value;
+
+                if value >= Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // If every legal move was searched, the table value could be wrong (e.g. it is not
+        // stored for positions with an en passant capture), so we don't probe it.
+        let no_more_moves = move_count > 0 && move_count == moves.len();
+
+        let value = if no_more_moves {
+            best
+        } else {
+            self.probe_wdl_table(pos)?
+        };
+
+        if best >= value {
+            Some((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    fn entry(&self, material: &Material) -> Option<&Entry> {
+        self.by_key.get(&material.key()).map(|&i| &self.entries[i])
+    }
+
+    fn probe_wdl_table(&self, pos: &Position) -> Option<Wdl> {
+        let material = Material::from_position(pos);
+        if material.piece_count() == 2 {
+            return Some(Wdl::Draw);
+        }
+
+        let entry = self.entry(&material)?;
+        let table = entry.wdl.get(TableKind::Wdl, entry.material)?;
+        let (value, _) = probe_table(table, pos, &material)?.ok()?;
+
+        Wdl::from_value(value as i32 - 2)
+    }
+
+    /// Probe the DTZ table for `pos`, whose WDL value is `wdl`. Returns `Some(None)` if the table
+    /// is stored for the other side to move.
+    fn probe_dtz_table(&self, pos: &Position, wdl: Wdl) -> Option<Option<i32>> {
+        let material = Material::from_position(pos);
+        let entry = self.entry(&material)?;
+        let table = entry.dtz.get(TableKind::Dtz, entry.material)?;
+
+        match probe_table(table, pos, &material)? {
+            Ok((value, file)) => table.map_dtz(file, value, wdl as i8).map(Some),
+            Err(()) => Some(None),
+        }
+    }
+}
+
+/// Collect the pieces of `pos` in the orientation of `table` and look up the raw value stored.
+///
+/// Tables are stored with the stronger side as white, and tables with the same material on both
+/// sides only for white to move. Otherwise we swap the colours and mirror the board vertically.
+fn probe_table(
+    table: &Table,
+    pos: &Position,
+    material: &Material,
+) -> Option<Result<(usize, usize), ()>> {
+    let stored = table.material();
+    let symmetric_black_to_move = stored.is_symmetric() && pos.turn() == Player::BLACK;
+    let black_stronger = material.key() != stored.key();
+    let flip = symmetric_black_to_move || black_stronger;
+
+    let flip_colour = if flip { 8 } else { 0 };
+    let flip_squares = if flip { 56 } else { 0 };
+    let stm = flip as usize ^ pos.turn().is_black() as usize;
+
+    let mut pieces = [0u8; TB_PIECES];
+    let mut squares = [0u8; TB_PIECES];
+    let mut size = 0;
+    let mut lead_pawns = core::bb::Bitboard(0);
+
+    // Tables with pawns start with the pawns of the leading colour.
+    if stored.has_pawns() {
+        let lead = table.lead_piece();
+        let player = if (lead ^ flip_colour) & 8 == 0 {
+            Player::WHITE
+        } else {
+            Player::BLACK
+        };
+
+        lead_pawns = pos.piece_bb(player, PieceType::Pawn);
+        for sq in lead_pawns {
+            pieces[size] = lead;
+            squares[size] = sq.0 ^ flip_squares;
+            size += 1;
+        }
+    }
+    let lead_count = size;
+
+    for sq in pos.occupied() & !lead_pawns {
+        pieces[size] = tb_piece(pos.piece_at_sq(sq)) ^ flip_colour;
+        squares[size] = sq.0 ^ flip_squares;
+        size += 1;
+    }
+
+    table.probe(&mut pieces[..size], &mut squares[..size], lead_count, stm)
+}
+
+/// The encoding of a piece in table files: piece type, plus 8 for black.
+fn tb_piece(piece: Piece) -> u8 {
+    piece.type_of() as u8
+        + if piece.player() == Player::BLACK {
+            8
+        } else {
+            0
+        }
+}
+
+fn is_pawn_move(pos: &Position, mov: &Move) -> bool {
+    pos.piece_at_sq(mov.orig()).type_of() == PieceType::Pawn
+}
+
+/// Whether any position since the last zeroing move has occurred before.
+fn has_repeated(pos: &Position) -> bool {
+    let mut seen = vec![pos.zobrist()];
+
+    for m in pos.history().iter().rev() {
+        if seen.contains(&m.zobrist) {
+            return true;
+        }
+        seen.push(m.zobrist);
+
+        if m.ty.contains(MoveType::CAPTURE) || m.piece.type_of() == PieceType::Pawn {
+            break;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encoding::*;
+    use super::table::tests::check_encoding;
+    use super::*;
+
+    #[test]
+    fn encoding_tables() {
+        // There are 462 ways to place two kings, with the first in the a1-d1-d4 triangle.
+        let codes = MAP_KK.iter().flatten().filter(|&&c| c != 0).count();
+        assert_eq!(codes, 461);
+        assert_eq!(MAP_KK.iter().flatten().max(), Some(&461));
+
+        assert_eq!(MAP_A1D1D4[1], 0); // b1
+        assert_eq!(MAP_A1D1D4[0], 6); // a1
+        assert_eq!(MAP_A1D1D4[27], 9); // d4
+        assert_eq!(MAP_PAWNS[8], 47); // a2
+        assert_eq!(MAP_PAWNS[15], 46); // h2
+        assert_eq!(BINOMIAL[2][62], 62 * 61 / 2);
+        assert_eq!(LEAD_PAWNS_SIZE[1], [6, 6, 6, 6]);
+    }
+
+    #[test]
+    fn material_names() {
+        core::init::init_globals();
+
+        let material = Material::from_name("KRPvKR").unwrap();
+        assert_eq!(material.name(), "KRPvKR");
+        assert_eq!(material.piece_count(), 5);
+        assert!(material.has_pawns());
+        assert!(!material.is_symmetric());
+        assert_eq!(material.flipped().name(), "KRvKRP");
+
+        let pos = Position::from_fen("8/8/4k3/8/1r6/8/3PK3/5R2 b - - 0 1").unwrap();
+        assert_eq!(Material::from_position(&pos), material);
+        assert_ne!(material.key(), material.flipped().key());
+
+        assert!(Material::from_name("KRvR").is_none());
+        assert!(Material::from_name("KQQQQvKRRR").is_none());
+    }
+
+    #[test]
+    fn encoding_is_canonical() {
+        // Pieces as stored in table files: white pawn 1 to white king 6, black adds 8.
+        check_encoding("KQvK", &[6, 5, 14]);
+        check_encoding("KRvK", &[6, 14, 4]);
+        check_encoding("KPvK", &[1, 6, 14]);
+    }
+
+    #[test]
+    fn root_scores() {
+        assert_eq!(RootProbe::score(MAX_DTZ), Score::tb_win(0));
+        assert_eq!(RootProbe::score(-MAX_DTZ), Score::tb_loss(0));
+        assert_eq!(RootProbe::score(0), Score::zero());
+        assert!(RootProbe::score(MAX_DTZ - 101) > Score::zero());
+        assert!(RootProbe::score(MAX_DTZ - 101) < Score::cp(PAWN_VALUE));
+        assert!(RootProbe::score(-MAX_DTZ + 101) < Score::zero());
+    }
+
+    /// The 3-piece tables, from the directory in `SYZYGY_PATH` or else `syzygy` in the engine
+    /// crate. The tests which need them read `KQvK`, `KRvK`, `KBvK`, `KNvK` and `KPvK`, each as
+    /// both `.rtbw` and `.rtbz`. They are only checked against the real tables once these files
+    /// are committed to `engine/syzygy`; until then the tests are ignored, and can be run with
+    /// `cargo test -- --ignored` against a local copy.
+    fn tables() -> Tablebases {
+        let path = std::env::var("SYZYGY_PATH")
+            .unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/syzygy").to_string());
+        let tb = Tablebases::open(&path);
+
+        assert!(
+            tb.max_pieces() >= 3,
+            "no 3-piece tables found in {}; set SYZYGY_PATH",
+            path
+        );
+        tb
+    }
+
+    #[test]
+    #[ignore = "needs the 3-piece Syzygy tables in engine/syzygy"]
+    fn probe_wdl_3_piece() {
+        core::init::init_globals();
+        let tb = tables();
+
+        for (fen, wdl) in [
+            ("8/8/8/8/8/8/8/K1k5 w - - 0 1", Wdl::Draw),
+            ("8/8/8/4k3/8/8/8/KQ6 w - - 0 1", Wdl::Win),
+            ("8/8/8/4k3/8/8/8/KQ6 b - - 0 1", Wdl::Loss),
+            ("8/8/8/4k3/8/8/8/KR6 b - - 0 1", Wdl::Loss),
+            ("8/8/8/4k3/8/8/8/KB6 w - - 0 1", Wdl::Draw),
+            ("8/8/8/4k3/8/8/8/KN6 w - - 0 1", Wdl::Draw),
+            ("8/8/8/8/8/8/8/K1kq4 w - - 0 1", Wdl::Loss),
+            // Stalemate
+            ("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", Wdl::Draw),
+            // Black to move wins the queen
+            ("8/8/8/8/8/8/k7/1Q1K4 b - - 0 1", Wdl::Draw),
+            ("8/8/8/8/8/8/k7/2Q1K3 w - - 0 1", Wdl::Win),
+            ("8/8/8/8/8/k7/7P/K7 w - - 0 1", Wdl::Win),
+            // The black king is outside the square of the pawn
+            ("8/8/8/8/8/8/4k2P/7K w - - 0 1", Wdl::Win),
+            // White plays Kxa2
+            ("7k/8/8/8/8/8/p7/K7 b - - 0 1", Wdl::Draw),
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            assert_eq!(tb.probe_wdl(&mut pos), Some(wdl), "{}", fen);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the 3-piece Syzygy tables in engine/syzygy"]
+    fn probe_dtz_3_piece() {
+        core::init::init_globals();
+        let tb = tables();
+
+        // Mate in one: one ply to the zeroing "move" of being mated.
+        let mut pos = Position::from_fen("k7/8/1K6/8/8/8/8/7Q w - - 0 1").unwrap();
+        assert_eq!(tb.probe_dtz(&mut pos), Some(1));
+
+        // Wins need a positive DTZ; losses a negative one.
+        let mut pos = Position::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        assert!(tb.probe_dtz(&mut pos).unwrap() > 0);
+        let mut pos = Position::from_fen("8/8/8/4k3/8/8/8/KQ6 b - - 0 1").unwrap();
+        assert!(tb.probe_dtz(&mut pos).unwrap() < 0);
+
+        // Every best ranked root move must preserve the win.
+        let mut pos = Position::from_fen("8/8/8/4k3/8/8/8/KR6 w - - 0 1").unwrap();
+        let probe = tb.probe_root(&mut pos).unwrap();
+        assert!(probe.dtz);
+        for mov in probe.best_moves() {
+            let mut after = pos.play(&mov);
+            assert_eq!(tb.probe_wdl(&mut after), Some(Wdl::Loss));
+        }
+    }
+}
@@ -0,0 +1,1073 @@
+//! Reading a single memory-mapped Syzygy table file.
+//!
+//! A table is split into up to eight sub-tables (one per side to move, and for tables with pawns
+//! one per file of the leading pawn). Each sub-table is a sequence of values compressed with
+//! Recursive Pairing and then canonical Huffman coding, split into fixed size blocks, with a sparse
+//! index so that the block holding a given position can be found quickly.
+//!
+//! The layout here mirrors the reference probing code, so offsets and alignments follow it exactly.
+
+use super::encoding::*;
+use super::material::Material;
+
+use memmap2::Mmap;
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// The two kinds of table file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TableKind {
+    /// Win/draw/loss tables, `.rtbw`.
+    Wdl,
+    /// Distance-to-zero tables, `.rtbz`.
+    Dtz,
+}
+
+impl TableKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+
+    fn magic(&self) -> [u8; 4] {
+        match self {
+            TableKind::Wdl => [0x71, 0xe8, 0x23, 0x5d],
+            TableKind::Dtz => [0xd7, 0x66, 0x0c, 0xa5],
+        }
+    }
+}
+
+/// Flags stored in the first byte of each sub-table.
+mod flag {
+    /// DTZ tables: the side to move this sub-table is stored for.
+    pub const STM: u8 = 1;
+    /// DTZ tables: values are indices into the DTZ map.
+    pub const MAPPED: u8 = 2;
+    /// DTZ tables: winning values are stored in plies rather than moves.
+    pub const WIN_PLIES: u8 = 4;
+    /// DTZ tables: losing values are stored in plies rather than moves.
+    pub const LOSS_PLIES: u8 = 8;
+    /// DTZ tables: the DTZ map holds 16-bit values.
+    pub const WIDE: u8 = 16;
+    /// Every position in the sub-table has the same value.
+    pub const SINGLE_VALUE: u8 = 128;
+}
+
+/// One sub-table. Fields named as offsets point into the mapped file.
+#[derive(Debug, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    block_size: usize,
+    span: usize,
+    sparse_index_size: usize,
+    num_blocks: usize,
+    block_length_size: usize,
+    /// `lowest_sym[l]` is the symbol of length `l` with the lowest value.
+    lowest_sym: usize,
+    /// `btree[sym]` stores the left and right symbols that `sym` expands to.
+    btree: usize,
+    /// The number of values (minus one) stored in each block.
+    block_length: usize,
+    /// Partial indices into `block_length`.
+    sparse_index: usize,
+    /// Start of the Huffman compressed data.
+    data: usize,
+    /// `base64[l - min_sym_len]` is the lowest symbol of length `l`, padded to 64 bits.
+    base64: Vec<u64>,
+    /// The number of values (minus one) a symbol expands to.
+    symlen: Vec<u8>,
+    /// The pieces of the table, in the order which defines the groups.
+    pieces: [u8; TB_PIECES],
+    /// The start index used for the encoding of each group.
+    group_idx: [u64; TB_PIECES + 1],
+    /// The number of pieces in each group, zero terminated.
+    group_len: [usize; TB_PIECES + 1],
+    /// DTZ tables: start of the map for each of win, loss, cursed win and blessed loss.
+    map_idx: [usize; 4],
+}
+
+/// A memory-mapped table file.
+pub struct Table {
+    mmap: Mmap,
+    kind: TableKind,
+    /// The material this table is stored for, with the stronger side as white.
+    material: Material,
+    /// The number of sides stored, i.e. the number of sub-tables per file.
+    sides: usize,
+    /// Sub-tables, indexed by `file * sides + side`.
+    pairs: Vec<PairsData>,
+    /// DTZ tables: start of the DTZ map.
+    map: usize,
+}
+
+/// The first byte of a table file, after the magic.
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+impl Table {
+    /// Map and parse the table file at `path`.
+    pub fn open(path: &Path, kind: TableKind, material: Material) -> io::Result<Table> {
+        let file = File::open(path)?;
+
+        // Safety: the table files are read-only and we never write through the mapping. Like any
+        // other engine, we assume nobody modifies them while we are running.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() % 64 != 16 || mmap[..4] != kind.magic() {
+            return Err(invalid("corrupt tablebase file"));
+        }
+
+        let mut table = Table {
+            mmap,
+            kind,
+            material,
+            sides: 0,
+            pairs: Vec::new(),
+            map: 0,
+        };
+        table
+            .parse()
+            .ok_or_else(|| invalid("truncated tablebase file"))?;
+
+        Ok(table)
+    }
+
+    /// Parse the headers of all sub-tables. Returns `None` if the file is too short.
+    fn parse(&mut self) -> Option<()> {
+        let m = &self.material;
+        let mut data = 4;
+        let header = *self.mmap.get(data)?;
+
+        if (header & HAS_PAWNS != 0) != m.has_pawns() || (header & SPLIT != 0) == m.is_symmetric() {
+            return None;
+        }
+        data += 1;
+
+        self.sides = if self.kind == TableKind::Wdl && !m.is_symmetric() {
+            2
+        } else {
+            1
+        };
+        let files = if m.has_pawns() { 4 } else { 1 };
+        let both_pawns = m.has_pawns() && m.pawn_count(1) > 0;
+
+        self.pairs = (0..files * self.sides)
+            .map(|_| PairsData::default())
+            .collect();
+
+        for f in 0..files {
+            let b0 = *self.mmap.get(data)?;
+            let b1 = if both_pawns {
+                *self.mmap.get(data + 1)?
+            } else {
+                0xff
+            };
+            let order = [[b0 & 0xf, b1 & 0xf], [b0 >> 4, b1 >> 4]];
+            data += 1 + both_pawns as usize;
+
+            for k in 0..m.piece_count() {
+                let b = *self.mmap.get(data)?;
+                for side in 0..self.sides {
+                    self.pairs[f * self.sides + side].pieces[k] =
+                        if side == 0 { b & 0xf } else { b >> 4 };
+                }
+                data += 1;
+            }
+
+            for (side, order) in order.iter().enumerate().take(self.sides) {
+                let d = &mut self.pairs[f * self.sides + side];
+                set_groups(d, m, *order, f);
+            }
+        }
+
+        data += data & 1;
+
+        for i in 0..self.pairs.len() {
+            data = self.set_sizes(i, data)?;
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = data;
+            for f in 0..files {
+                let flags = self.pairs[f * self.sides].flags;
+                if flags & flag::MAPPED == 0 {
+                    continue;
+                }
+
+                if flags & flag::WIDE != 0 {
+                    data += data & 1;
+                    for i in 0..4 {
+                        self.pairs[f * self.sides].map_idx[i] = (data - self.map) / 2 + 1;
+                        data += 2 * self.read_u16_le(data)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        self.pairs[f * self.sides].map_idx[i] = data - self.map + 1;
+                        data += *self.mmap.get(data)? as usize + 1;
+                    }
+                }
+            }
+            data += data & 1;
+        }
+
+        for d in self.pairs.iter_mut() {
+            d.sparse_index = data;
+            data += d.sparse_index_size * 6;
+        }
+
+        for d in self.pairs.iter_mut() {
+            d.block_length = data;
+            data += d.block_length_size * 2;
+        }
+
+        for d in self.pairs.iter_mut() {
+            data = (data + 0x3f) & !0x3f;
+            d.data = data;
+            data += d.num_blocks * d.block_size;
+        }
+
+        if data > self.mmap.len() {
+            return None;
+        }
+
+        Some(())
+    }
+
+    /// Parse the sizes and the Huffman code of sub-table `i`, starting at `data`. Returns the
+    /// offset of the end of this header.
+    fn set_sizes(&mut self, i: usize, mut data: usize) -> Option<usize> {
+        let flags = *self.mmap.get(data)?;
+        data += 1;
+
+        if flags & flag::SINGLE_VALUE != 0 {
+            let d = &mut self.pairs[i];
+            d.flags = flags;
+            // The single value is stored in place of the minimum symbol length.
+            d.min_sym_len = *self.mmap.get(data)?;
+            return Some(data + 1);
+        }
+
+        let tb_size = {
+            let d = &self.pairs[i];
+            let groups = d.group_len.iter().position(|&l| l == 0)?;
+            d.group_idx[groups]
+        };
+
+        let block_size = 1usize << *self.mmap.get(data)?;
+        let span = 1usize << *self.mmap.get(data + 1)?;
+        let padding = *self.mmap.get(data + 2)? as usize;
+        let num_blocks = self.read_u32_le(data + 3)? as usize;
+        let max_sym_len = *self.mmap.get(data + 7)?;
+        let min_sym_len = *self.mmap.get(data + 8)?;
+        data += 9;
+
+        if min_sym_len > max_sym_len || max_sym_len > 32 {
+            return None;
+        }
+
+        let lowest_sym = data;
+        let sym_lens = (max_sym_len - min_sym_len) as usize + 1;
+
+        // The canonical code is ordered such that longer symbols have lower numeric value. From
+        // the lowest symbol of each length we compute the 64-bit padded lowest code of each length,
+        // so that a code can be identified by comparing the next 64 bits of input against these.
+        let mut base64 = vec![0u64; sym_lens];
+        for l in (0..sym_lens - 1).rev() {
+            let lowest = self.read_u16_le(lowest_sym + 2 * l)? as u64;
+            let next = self.read_u16_le(lowest_sym + 2 * (l + 1))? as u64;
+            base64[l] = (base64[l + 1] + lowest - next) / 2;
+        }
+        for (l, base) in base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl(64 - l as u32 - min_sym_len as u32)
+                .unwrap_or(0);
+        }
+        data += sym_lens * 2;
+
+        let num_syms = self.read_u16_le(data)? as usize;
+        data += 2;
+        let btree = data;
+
+        // Each symbol expands recursively into a sequence of values; compute how many.
+        let mut symlen = vec![0u8; num_syms];
+        let mut visited = vec![false; num_syms];
+        for sym in 0..num_syms {
+            if !visited[sym] {
+                symlen[sym] = self.set_symlen(btree, sym, &mut symlen, &mut visited)?;
+            }
+        }
+        data += num_syms * 3 + (num_syms & 1);
+
+        let d = &mut self.pairs[i];
+        d.flags = flags;
+        d.min_sym_len = min_sym_len;
+        d.block_size = block_size;
+        d.span = span;
+        d.sparse_index_size = tb_size.div_ceil(span as u64) as usize;
+        d.num_blocks = num_blocks;
+        d.block_length_size = num_blocks + padding;
+        d.lowest_sym = lowest_sym;
+        d.btree = btree;
+        d.base64 = base64;
+        d.symlen = symlen;
+
+        Some(data)
+    }
+
+    fn set_symlen(
+        &self,
+        btree: usize,
+        sym: usize,
+        symlen: &mut [u8],
+        visited: &mut [bool],
+    ) -> Option<u8> {
+        visited[sym] = true;
+
+        let (left, right) = self.pair(btree, sym)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+
+        if !*visited.get(left)? {
+            symlen[left] = self.set_symlen(btree, left, symlen, visited)?;
+        }
+        if !*visited.get(right)? {
+            symlen[right] = self.set_symlen(btree, right, symlen, visited)?;
+        }
+
+        Some(symlen[left].wrapping_add(symlen[right]).wrapping_add(1))
+    }
+
+    /// The left and right symbols which `sym` expands to, each stored in 12 bits.
+    #[inline]
+    fn pair(&self, btree: usize, sym: usize) -> Option<(usize, usize)> {
+        let lr = self.mmap.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+        let left = ((lr[1] as usize & 0xf) << 8) | lr[0] as usize;
+        let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    /// Decompress the value stored at index `idx` of sub-table `d`.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Option<usize> {
+        if d.flags & flag::SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as usize);
+        }
+
+        // Find the block holding `idx`. Sparse index entry `k` stores the block and the offset
+        // within it of the value with index `k * span + span / 2`, so start there and walk
+        // backwards or forwards to the right block.
+        let k = (idx / d.span as u64) as usize;
+        let entry = d.sparse_index + 6 * k;
+        let mut block = self.read_u32_le(entry)? as usize;
+        let mut offset = self.read_u16_le(entry + 4)? as i64;
+
+        offset += (idx % d.span as u64) as i64 - (d.span / 2) as i64;
+
+        let block_length =
+            |b: usize| -> Option<i64> { Some(self.read_u16_le(d.block_length + 2 * b)? as i64) };
+
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Decode symbols from the start of the block until we reach the one which covers our
+        // offset.
+        let mut ptr = d.data + block * d.block_size;
+        let mut buf64 = self.read_u64_be(ptr)?;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = d.min_sym_len as u32;
+
+        let mut sym = loop {
+            let mut len = 0;
+            while buf64 < *d.base64.get(len)? {
+                len += 1;
+            }
+
+            let sym = (buf64 - d.base64[len])
+                .checked_shr(64 - len as u32 - min_sym_len)
+                .unwrap_or(0) as usize
+                + self.read_u16_le(d.lowest_sym + 2 * len)? as usize;
+
+            let count = *d.symlen.get(sym)? as i64 + 1;
+            if offset < count {
+                break sym;
+            }
+
+            offset -= count;
+            let len = len as u32 + min_sym_len;
+            buf64 <<= len;
+            buf64_size -= len;
+
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (self.read_u32_be(ptr)? as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        };
+
+        // Our symbol expands into `symlen[sym] + 1` values. Walk down the pairs until we reach the
+        // single value we are after.
+        while d.symlen[sym] != 0 {
+            let (left, right) = self.pair(d.btree, sym)?;
+            let left_count = *d.symlen.get(left)? as i64 + 1;
+
+            if offset < left_count {
+                sym = left;
+            } else {
+                offset -= left_count;
+                sym = right;
+            }
+        }
+
+        Some(self.pair(d.btree, sym)?.0)
+    }
+
+    /// Look up the raw value stored for a position, given as a list of pieces (encoded as in the
+    /// files) and their squares, with white as the stronger side. `stm` is the side to move,
+    /// 0 for white. Returns the value along with the file of the leading pawn.
+    ///
+    /// For DTZ tables, which only store one side to move, returns `Err(())` if `stm` is not the
+    /// stored side.
+    pub fn probe(
+        &self,
+        pieces: &mut [u8],
+        squares: &mut [u8],
+        lead_pawns: usize,
+        stm: usize,
+    ) -> Option<Result<(usize, usize), ()>> {
+        let mut tb_file = 0;
+
+        if self.material.has_pawns() {
+            // The leading pawn is the one with the highest `MAP_PAWNS` value.
+            let lead = (0..lead_pawns).fold(0, |best, i| {
+                if MAP_PAWNS[squares[i] as usize] > MAP_PAWNS[squares[best] as usize] {
+                    i
+                } else {
+                    best
+                }
+            });
+            squares.swap(0, lead);
+            pieces.swap(0, lead);
+
+            let file = (squares[0] & 7) as usize;
+            tb_file = file.min(7 - file);
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.pairs[tb_file * self.sides].flags;
+            if (self.material.has_pawns() || !self.material.is_symmetric())
+                && (flags & flag::STM) as usize != stm
+            {
+                return Some(Err(()));
+            }
+        }
+
+        let d = &self.pairs[tb_file * self.sides + stm % self.sides];
+        let idx = encode(d, &self.material, pieces, squares, lead_pawns);
+        let value = self.decompress_pairs(d, idx)?;
+
+        Some(Ok((value, tb_file)))
+    }
+
+    /// Convert a raw DTZ table value into plies, given the WDL value of the position (as an index
+    /// into win, loss, cursed win, blessed loss) and the file of the leading pawn.
+    pub fn map_dtz(&self, file: usize, value: usize, wdl: i8) -> Option<i32> {
+        debug_assert_eq!(self.kind, TableKind::Dtz);
+
+        // Indices of the maps for Loss, BlessedLoss, Draw, CursedWin, Win.
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+        let d = &self.pairs[file * self.sides];
+        let mut value = value;
+
+        if d.flags & flag::MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl + 2) as usize]] + value;
+            value = if d.flags & flag::WIDE != 0 {
+                self.read_u16_le(self.map + 2 * idx)? as usize
+            } else {
+                *self.mmap.get(self.map + idx)? as usize
+            };
+        }
+
+        // DTZ tables store either moves or plies. We always want plies.
+        if (wdl == 2 && d.flags & flag::WIN_PLIES == 0)
+            || (wdl == -2 && d.flags & flag::LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1
+        {
+            value *= 2;
+        }
+
+        Some(value as i32 + 1)
+    }
+
+    /// The pieces stored for the first sub-table, which for tables with pawns starts with a pawn
+    /// of the leading colour.
+    pub fn lead_piece(&self) -> u8 {
+        self.pairs[0].pieces[0]
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    #[inline]
+    fn read_u16_le(&self, at: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(
+            self.mmap.get(at..at + 2)?.try_into().ok()?,
+        ))
+    }
+
+    #[inline]
+    fn read_u32_le(&self, at: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(
+            self.mmap.get(at..at + 4)?.try_into().ok()?,
+        ))
+    }
+
+    #[inline]
+    fn read_u32_be(&self, at: usize) -> Option<u32> {
+        Some(u32::from_be_bytes(
+            self.mmap.get(at..at + 4)?.try_into().ok()?,
+        ))
+    }
+
+    #[inline]
+    fn read_u64_be(&self, at: usize) -> Option<u64> {
+        Some(u64::from_be_bytes(
+            self.mmap.get(at..at + 8)?.try_into().ok()?,
+        ))
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Split the pieces of a sub-table into groups and compute the start index of each group.
+///
+/// The leading group is the kings plus one other unique piece if there is one (or the leading
+/// pawns, for tables with pawns), and each further group is a run of identical pieces. If the
+/// pieces of group `g` can be placed in `N(g)` ways, a position is encoded as
+/// `g1 * N(g2) * N(g3) + g2 * N(g3) + g3`, where the order of the groups is stored per table.
+fn set_groups(d: &mut PairsData, m: &Material, order: [u8; 2], file: usize) {
+    let mut n = 0;
+    let mut first_len: i32 = if m.has_pawns() {
+        0
+    } else if m.has_unique_pieces() {
+        3
+    } else {
+        2
+    };
+    d.group_len[n] = 1;
+
+    for i in 1..m.piece_count() {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let both_pawns = m.has_pawns() && m.pawn_count(1) > 0;
+    let mut next = if both_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            // Leading pawns or pieces
+            d.group_idx[0] = idx;
+            idx *= if m.has_pawns() {
+                LEAD_PAWNS_SIZE[d.group_len[0]][file]
+            } else if m.has_unique_pieces() {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] as usize {
+            // Remaining pawns
+            d.group_idx[1] = idx;
+            idx *= BINOMIAL[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            // Remaining pieces
+            d.group_idx[next] = idx;
+            idx *= BINOMIAL[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+
+    d.group_idx[n] = idx;
+}
+
+/// Map a position to its index in sub-table `d`.
+///
+/// `squares` and `pieces` list the pieces of the position, with the `lead_pawns` pawns of the
+/// leading colour first (and the leading pawn itself at the front). Both are reordered in place.
+fn encode(
+    d: &PairsData,
+    m: &Material,
+    pieces: &mut [u8],
+    squares: &mut [u8],
+    lead_pawns: usize,
+) -> u64 {
+    let size = m.piece_count();
+
+    // Reorder the pieces to the sequence stored in the table, which defines the groups.
+    for i in lead_pawns..size.saturating_sub(1) {
+        for j in i + 1..size {
+            if d.pieces[i] == pieces[j] {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+                break;
+            }
+        }
+    }
+
+    // Mirror so that the leading piece is on files a-d.
+    if squares[0] & 7 > 3 {
+        for sq in squares.iter_mut() {
+            *sq ^= 7;
+        }
+    }
+
+    let mut idx: u64;
+
+    if m.has_pawns() {
+        idx = LEAD_PAWN_IDX[lead_pawns][squares[0] as usize];
+
+        squares[1..lead_pawns].sort_by_key(|&sq| MAP_PAWNS[sq as usize]);
+        for i in 1..lead_pawns {
+            idx += BINOMIAL[i][MAP_PAWNS[squares[i] as usize] as usize];
+        }
+    } else {
+        // Without pawns, also mirror so that the leading piece is on ranks 1-4.
+        if squares[0] >> 3 > 3 {
+            for sq in squares.iter_mut() {
+                *sq ^= 56;
+            }
+        }
+
+        // Find the first piece of the leading group not on the a1-h8 diagonal, and mirror along
+        // the diagonal so that it is below it.
+        for i in 0..d.group_len[0] {
+            let off = off_a1h8(squares[i]);
+            if off == 0 {
+                continue;
+            }
+
+            if off > 0 {
+                for sq in squares[i..size].iter_mut() {
+                    *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                }
+            }
+            break;
+        }
+
+        if m.has_unique_pieces() {
+            let s = [squares[0] as u64, squares[1] as u64, squares[2] as u64];
+            let adjust1 = (s[1] > s[0]) as u64;
+            let adjust2 = (s[2] > s[0]) as u64 + (s[2] > s[1]) as u64;
+            let rank = |sq: u8| (sq >> 3) as u64;
+
+            idx = if off_a1h8(squares[0]) != 0 {
+                (MAP_A1D1D4[squares[0] as usize] as u64 * 63 + (s[1] - adjust1)) * 62 + s[2]
+                    - adjust2
+            } else if off_a1h8(squares[1]) != 0 {
+                (6 * 63 + rank(squares[0]) * 28 + MAP_B1H1H7[squares[1] as usize] as u64) * 62
+                    + s[2]
+                    - adjust2
+            } else if off_a1h8(squares[2]) != 0 {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + rank(squares[0]) * 7 * 28
+                    + (rank(squares[1]) - adjust1) * 28
+                    + MAP_B1H1H7[squares[2] as usize] as u64
+            } else {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + 4 * 7 * 28
+                    + rank(squares[0]) * 7 * 6
+                    + (rank(squares[1]) - adjust1) * 6
+                    + (rank(squares[2]) - adjust2)
+            };
+        } else {
+            idx = MAP_KK[MAP_A1D1D4[squares[0] as usize] as usize][squares[1] as usize] as u64;
+        }
+    }
+
+    idx *= d.group_idx[0];
+
+    // Encode the remaining groups, each in ascending order of square.
+    let mut remaining_pawns = m.has_pawns() && m.pawn_count(1) > 0;
+    let mut start = d.group_len[0];
+    let mut next = 1;
+
+    while d.group_len[next] != 0 {
+        let len = d.group_len[next];
+        squares[start..start + len].sort_unstable();
+
+        let mut n = 0;
+        for i in 0..len {
+            let sq = squares[start + i];
+            let adjust = squares[..start].iter().filter(|&&s| sq > s).count();
+            n += BINOMIAL[i + 1][sq as usize - adjust - 8 * remaining_pawns as usize];
+        }
+
+        remaining_pawns = false;
+        idx += n * d.group_idx[next];
+        start += len;
+        next += 1;
+    }
+
+    idx
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Build the header of a sub-table for `material` with pieces stored in the given order and
+    /// the default group order, without any backing file.
+    fn pairs_data(material: &Material, pieces: &[u8], file: usize) -> PairsData {
+        let mut d = PairsData::default();
+        d.pieces[..pieces.len()].copy_from_slice(pieces);
+        let both_pawns = material.has_pawns() && material.pawn_count(1) > 0;
+        set_groups(
+            &mut d,
+            material,
+            [0, if both_pawns { 1 } else { 0xf }],
+            file,
+        );
+        d
+    }
+
+    /// The size of a sub-table, i.e. one more than its highest index.
+    fn table_size(d: &PairsData) -> u64 {
+        d.group_idx[d.group_len.iter().position(|&l| l == 0).unwrap()]
+    }
+
+    /// Encode every placement of `pieces`, checking that all indices are in range, that placements
+    /// which are mirror images of each other share an index and that no other placements do.
+    /// Returns the number of distinct indices.
+    pub fn check_encoding(name: &str, pieces: &[u8]) -> usize {
+        let material = Material::from_name(name).unwrap();
+        let lead_pawns = if material.has_pawns() {
+            material.pawn_count(0)
+        } else {
+            0
+        };
+
+        // The canonical placement seen for each index, i.e. the least of its mirror images.
+        let mut seen = std::collections::HashMap::new();
+        let mut squares = vec![0u8; pieces.len()];
+
+        fn place(i: usize, squares: &mut Vec<u8>, pieces: &[u8], f: &mut dyn FnMut(&[u8])) {
+            if i == pieces.len() {
+                f(squares);
+                return;
+            }
+            for sq in 0..64u8 {
+                let is_pawn = pieces[i] & 7 == 1;
+                if squares[..i].contains(&sq) || (is_pawn && !(8..56).contains(&sq)) {
+                    continue;
+                }
+                squares[i] = sq;
+                place(i + 1, squares, pieces, f);
+            }
+        }
+
+        let mut check = |squares: &[u8]| {
+            let kings: Vec<u8> = (0..pieces.len())
+                .filter(|&i| pieces[i] & 7 == 6)
+                .map(|i| squares[i])
+                .collect();
+            let (a, b) = (kings[0], kings[1]);
+            if (a >> 3).abs_diff(b >> 3) <= 1 && (a & 7).abs_diff(b & 7) <= 1 {
+                return;
+            }
+
+            let index = |squares: &[u8]| {
+                let mut sq = squares.to_vec();
+                let mut pc = pieces.to_vec();
+
+                // Bring the leading pawn to the front, as the prober does.
+                let file = if material.has_pawns() {
+                    let lead = (0..lead_pawns)
+                        .max_by_key(|&i| MAP_PAWNS[sq[i] as usize])
+                        .unwrap();
+                    sq.swap(0, lead);
+                    pc.swap(0, lead);
+                    let file = (sq[0] & 7) as usize;
+                    file.min(7 - file)
+                } else {
+                    0
+                };
+
+                let d = pairs_data(&material, pieces, file);
+                let idx = encode(&d, &material, &mut pc, &mut sq, lead_pawns);
+                assert!(idx < table_size(&d), "index out of range for {:?}", squares);
+                (file, idx)
+            };
+
+            let idx = index(squares);
+            let mirrored: Vec<u8> = squares.iter().map(|s| s ^ 7).collect();
+            assert_eq!(
+                idx,
+                index(&mirrored),
+                "file mirror differs for {:?}",
+                squares
+            );
+
+            if !material.has_pawns() {
+                let flipped: Vec<u8> = squares.iter().map(|s| s ^ 56).collect();
+                assert_eq!(
+                    idx,
+                    index(&flipped),
+                    "rank mirror differs for {:?}",
+                    squares
+                );
+                let transposed: Vec<u8> =
+                    squares.iter().map(|s| ((s >> 3) | (s << 3)) & 63).collect();
+                assert_eq!(
+                    idx,
+                    index(&transposed),
+                    "diagonal mirror differs for {:?}",
+                    squares
+                );
+            }
+
+            let symmetries: &[fn(u8) -> u8] = if material.has_pawns() {
+                &[|s| s, |s| s ^ 7]
+            } else {
+                &[
+                    |s| s,
+                    |s| s ^ 7,
+                    |s| s ^ 56,
+                    |s| s ^ 63,
+                    |s| ((s >> 3) | (s << 3)) & 63,
+                    |s| (((s >> 3) | (s << 3)) & 63) ^ 7,
+                    |s| (((s >> 3) | (s << 3)) & 63) ^ 56,
+                    |s| (((s >> 3) | (s << 3)) & 63) ^ 63,
+                ]
+            };
+            let canonical = symmetries
+                .iter()
+                .map(|f| {
+                    let mut placement: Vec<(u8, u8)> = pieces
+                        .iter()
+                        .zip(squares)
+                        .map(|(&p, &s)| (p, f(s)))
+                        .collect();
+                    placement.sort_unstable();
+                    placement
+                })
+                .min()
+                .unwrap();
+
+            let prev = seen.entry(idx).or_insert_with(|| canonical.clone());
+            assert_eq!(*prev, canonical, "index {:?} is shared", idx);
+        };
+
+        place(0, &mut squares, pieces, &mut check);
+
+        seen.len()
+    }
+
+    /// Symbols of the synthetic table below, as stored in the file: a literal value has right
+    /// symbol 0xfff, and any other symbol expands to its left and then its right symbol.
+    const SYMBOLS: [(u16, u16); 10] = [
+        (0, 0xfff),
+        (1, 0xfff),
+        (2, 0xfff),
+        (3, 0xfff),
+        (4, 0xfff),
+        (0, 1),
+        (5, 2),
+        (6, 6),
+        (4, 4),
+        (3, 2),
+    ];
+
+    /// The Huffman code of each symbol and its length: symbols 0-3 have the 4-bit codes
+    /// 0000-0011 and the rest the 3-bit codes 010-111.
+    fn huffman_code(sym: usize) -> (u64, u32) {
+        if sym < 4 {
+            (sym as u64, 4)
+        } else {
+            (sym as u64 - 2, 3)
+        }
+    }
+
+    fn expand(sym: usize, values: &mut Vec<u8>) {
+        let (left, right) = SYMBOLS[sym];
+        if right == 0xfff {
+            values.push(left as u8);
+        } else {
+            expand(left as usize, values);
+            expand(right as usize, values);
+        }
+    }
+
+    /// Write a WDL table for KQvK, storing `values` for white to move and `single` for every
+    /// position with black to move.
+    fn write_table(path: &Path, values: &[u8], single: u8) {
+        const BLOCK_SIZE: usize = 32;
+        const SPAN: usize = 64;
+
+        let expansions: Vec<Vec<u8>> = (0..SYMBOLS.len())
+            .map(|sym| {
+                let mut values = Vec::new();
+                expand(sym, &mut values);
+                values
+            })
+            .collect();
+
+        // Greedily cover the values with the longest symbols which match, and pack the codes
+        // into blocks, recording the number of values in each.
+        let mut blocks: Vec<(Vec<bool>, usize)> = vec![(Vec::new(), 0)];
+        let mut i = 0;
+        while i < values.len() {
+            let sym = (0..SYMBOLS.len())
+                .filter(|&sym| values[i..].starts_with(&expansions[sym]))
+                .max_by_key(|&sym| expansions[sym].len())
+                .unwrap();
+            let (code, len) = huffman_code(sym);
+
+            if blocks.last().unwrap().0.len() + len as usize > 8 * BLOCK_SIZE {
+                blocks.push((Vec::new(), 0));
+            }
+            let (bits, count) = blocks.last_mut().unwrap();
+            bits.extend((0..len).rev().map(|bit| code >> bit & 1 == 1));
+            *count += expansions[sym].len();
+            i += expansions[sym].len();
+        }
+
+        let mut f = TableKind::Wdl.magic().to_vec();
+        f.push(SPLIT);
+        // Group order for both sides, then the pieces for both sides.
+        f.push(0);
+        f.extend([6u8, 5, 14].map(|p| p | p << 4));
+        f.push(0);
+
+        // White to move: a Huffman coded sub-table, with one entry of padding in the block
+        // lengths.
+        f.extend([
+            0,
+            BLOCK_SIZE.trailing_zeros() as u8,
+            SPAN.trailing_zeros() as u8,
+            1,
+        ]);
+        f.extend((blocks.len() as u32).to_le_bytes());
+        f.extend([4, 3]);
+        f.extend(4u16.to_le_bytes());
+        f.extend(0u16.to_le_bytes());
+        f.extend((SYMBOLS.len() as u16).to_le_bytes());
+        for (left, right) in SYMBOLS {
+            f.extend([
+                left as u8,
+                (left >> 8) as u8 | (right as u8 & 0xf) << 4,
+                (right >> 4) as u8,
+            ]);
+        }
+
+        // Black to move: a single value.
+        f.extend([flag::SINGLE_VALUE, single]);
+
+        // Each sparse index entry points at the value in the middle of its span.
+        let mut start = 0;
+        let starts: Vec<usize> = blocks
+            .iter()
+            .map(|(_, count)| {
+                start += count;
+                start - count
+            })
+            .collect();
+        for k in 0..values.len().div_ceil(SPAN) {
+            let idx = k * SPAN + SPAN / 2;
+            let block = starts.iter().rposition(|&s| s <= idx).unwrap();
+            f.extend((block as u32).to_le_bytes());
+            f.extend(((idx - starts[block]) as u16).to_le_bytes());
+        }
+
+        for (_, count) in &blocks {
+            f.extend((*count as u16 - 1).to_le_bytes());
+        }
+        f.extend([0, 0]);
+
+        f.resize(f.len().next_multiple_of(64), 0);
+        for (bits, _) in &blocks {
+            let mut block = [0u8; BLOCK_SIZE];
+            for (i, &bit) in bits.iter().enumerate() {
+                block[i / 8] |= (bit as u8) << (7 - i % 8);
+            }
+            f.extend(block);
+        }
+
+        // The decoder reads ahead of the last block, so leave some slack before padding the
+        // file to the expected length.
+        f.resize((f.len() + 8).next_multiple_of(64) + 16, 0);
+
+        std::fs::write(path, f).unwrap();
+    }
+
+    #[test]
+    fn decompress_synthetic_table() {
+        let material = Material::from_name("KQvK").unwrap();
+
+        // Concatenate the expansions of pseudo-random symbols, so that every symbol is used.
+        let mut values = Vec::new();
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        while values.len() < 31332 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            expand(seed as usize % SYMBOLS.len(), &mut values);
+        }
+        values.truncate(31332);
+
+        let path = std::env::temp_dir().join(format!("seaborg-{}-KQvK.rtbw", std::process::id()));
+        write_table(&path, &values, 3);
+        let table = Table::open(&path, TableKind::Wdl, material.clone());
+        std::fs::remove_file(&path).unwrap();
+        let table = table.unwrap();
+
+        assert_eq!(table_size(&table.pairs[0]), values.len() as u64);
+        for (idx, &value) in values.iter().enumerate() {
+            let decompressed = table.decompress_pairs(&table.pairs[0], idx as u64);
+            assert_eq!(decompressed, Some(value as usize), "index {}", idx);
+        }
+        assert_eq!(table.decompress_pairs(&table.pairs[1], 1234), Some(3));
+
+        // Probing finds the value stored at the index of the position.
+        let pieces = [6, 5, 14];
+        for squares in [[4, 3, 60], [62, 0, 27], [9, 50, 31]] {
+            let (mut pc, mut sq) = (pieces, squares);
+            let idx = encode(&table.pairs[0], &material, &mut pc, &mut sq, 0);
+            let value = values[idx as usize] as usize;
+
+            let (mut pc, mut sq) = (pieces, squares);
+            assert_eq!(table.probe(&mut pc, &mut sq, 0, 0), Some(Ok((value, 0))));
+            let (mut pc, mut sq) = (pieces, squares);
+            assert_eq!(table.probe(&mut pc, &mut sq, 0, 1), Some(Ok((3, 0))));
+        }
+    }
+}
@@ -11,6 +11,8 @@ pub enum TimingMode {
     Timed(TimeControl),
     MoveTime(usize),
     Depth(u8),
+    Nodes(usize),
+    Mate(u8),
     Infinite,
 }
 
@@ -2,6 +2,7 @@
 //! and nodes per second.
 
 use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 /// Object responsible for tracing data about the search.
@@ -20,6 +21,35 @@ pub struct Tracer {
     hash_collisions: usize,
     /// The number of times we had a hash clash (same table slot, different position).
     hash_clashes: usize,
+    /// The number of successful endgame tablebase probes.
+    tb_hits: usize,
+    /// The number of null move searches.
+    null_move_tries: usize,
+    /// The number of null move searches which produced a cutoff.
+    null_move_cutoffs: usize,
+    /// The number of captures tried by ProbCut.
+    probcut_tries: usize,
+    /// The number of ProbCut searches which produced a cutoff.
+    probcut_cutoffs: usize,
+    /// The number of moves searched with a late move reduction.
+    lmr_reductions: usize,
+    /// The number of reduced searches which had to be repeated at full depth.
+    lmr_researches: usize,
+    /// The number of quiet moves skipped by futility pruning.
+    futility_prunes: usize,
+    /// The number of captures skipped by delta pruning in quiescence search.
+    delta_prunes: usize,
+    /// The number of moves extended because they were singular.
+    singular_extensions: usize,
+    /// The number of nodes searched at reduced depth because the TT had no move for them.
+    iir_reductions: usize,
+    /// The greatest distance from the root reached by the search, including quiescence search.
+    seldepth: u8,
+    /// The number of nodes and tablebase hits already added to the `SharedStats`.
+    published_nodes: usize,
+    published_tb_hits: usize,
+    /// The time at which each iteration of iterative deepening completed, indexed by depth - 1.
+    iteration_times: Vec<Duration>,
     /// Records the duration between start and end of search. Only populated with `Some(duration)`
     /// when `end_search` is called.
     elapsed: Option<Duration>,
@@ -39,6 +69,21 @@ impl Tracer {
             hash_hits: 0,
             hash_collisions: 0,
             hash_clashes: 0,
+            tb_hits: 0,
+            null_move_tries: 0,
+            null_move_cutoffs: 0,
+            probcut_tries: 0,
+            probcut_cutoffs: 0,
+            lmr_reductions: 0,
+            lmr_researches: 0,
+            futility_prunes: 0,
+            delta_prunes: 0,
+            singular_extensions: 0,
+            iir_reductions: 0,
+            seldepth: 0,
+            published_nodes: 0,
+            published_tb_hits: 0,
+            iteration_times: Vec::new(),
             elapsed: None,
             killers_per_node: Averager::new(0),
             hash_found: Averager::new(0),
@@ -91,6 +136,102 @@ impl Tracer {
         self.hash_clashes += 1;
     }
 
+    /// Record a successful tablebase probe.
+    #[inline(always)]
+    pub fn tb_hit(&mut self) {
+        self.tb_hits += 1;
+    }
+
+    /// Record a null move search.
+    #[inline(always)]
+    pub fn null_move_try(&mut self) {
+        self.null_move_tries += 1;
+    }
+
+    /// Record a null move search which produced a cutoff.
+    #[inline(always)]
+    pub fn null_move_cutoff(&mut self) {
+        self.null_move_cutoffs += 1;
+    }
+
+    /// Record a capture tried by ProbCut.
+    #[inline(always)]
+    pub fn probcut_try(&mut self) {
+        self.probcut_tries += 1;
+    }
+
+    /// Record a ProbCut search which produced a cutoff.
+    #[inline(always)]
+    pub fn probcut_cutoff(&mut self) {
+        self.probcut_cutoffs += 1;
+    }
+
+    /// Record a move searched with a late move reduction.
+    #[inline(always)]
+    pub fn lmr_reduction(&mut self) {
+        self.lmr_reductions += 1;
+    }
+
+    /// Record a reduced search which had to be repeated at full depth.
+    #[inline(always)]
+    pub fn lmr_research(&mut self) {
+        self.lmr_researches += 1;
+    }
+
+    /// Record a quiet move skipped by futility pruning.
+    #[inline(always)]
+    pub fn futility_prune(&mut self) {
+        self.futility_prunes += 1;
+    }
+
+    /// Record a capture skipped by delta pruning.
+    #[inline(always)]
+    pub fn delta_prune(&mut self) {
+        self.delta_prunes += 1;
+    }
+
+    /// Record a singular extension.
+    #[inline(always)]
+    pub fn singular_extension(&mut self) {
+        self.singular_extensions += 1;
+    }
+
+    /// Record a node searched at reduced depth because the TT had no move for it.
+    #[inline(always)]
+    pub fn iir_reduction(&mut self) {
+        self.iir_reductions += 1;
+    }
+
+    /// Record a visit to a node `ply` plies from the root.
+    #[inline(always)]
+    pub fn reach_ply(&mut self, ply: u8) {
+        if ply > self.seldepth {
+            self.seldepth = ply;
+        }
+    }
+
+    /// Add the counts recorded since the last call to `shared`, which totals them across all the
+    /// search threads.
+    pub fn publish(&mut self, shared: &SharedStats) {
+        let nodes = self.all_nodes_visited();
+        shared
+            .nodes
+            .fetch_add(nodes - self.published_nodes, Ordering::Relaxed);
+        self.published_nodes = nodes;
+
+        shared
+            .tb_hits
+            .fetch_add(self.tb_hits - self.published_tb_hits, Ordering::Relaxed);
+        self.published_tb_hits = self.tb_hits;
+
+        shared.seldepth.fetch_max(self.seldepth, Ordering::Relaxed);
+    }
+
+    /// Record the completion of the next iteration of iterative deepening.
+    pub fn complete_iteration(&mut self) {
+        self.iteration_times.push(self.start_time.elapsed());
+    }
+
     /// The number of nodes skipped due to SEE check failures during search.
     pub fn see_skipped_nodes(&self) -> usize {
         self.see_skipped_nodes
@@ -117,6 +258,78 @@ impl Tracer {
         self.hash_hits + self.hash_collisions + self.hash_clashes
     }
 
+    /// The number of successful tablebase probes recorded during search.
+    pub fn tb_hits(&self) -> usize {
+        self.tb_hits
+    }
+
+    /// The number of null move searches recorded during search.
+    pub fn null_move_tries(&self) -> usize {
+        self.null_move_tries
+    }
+
+    /// The number of null move cutoffs recorded during search.
+    pub fn null_move_cutoffs(&self) -> usize {
+        self.null_move_cutoffs
+    }
+
+    /// The number of ProbCut captures tried during search.
+    pub fn probcut_tries(&self) -> usize {
+        self.probcut_tries
+    }
+
+    /// The number of ProbCut cutoffs recorded during search.
+    pub fn probcut_cutoffs(&self) -> usize {
+        self.probcut_cutoffs
+    }
+
+    /// The number of late move reductions recorded during search.
+    pub fn lmr_reductions(&self) -> usize {
+        self.lmr_reductions
+    }
+
+    /// The number of late move reduction re-searches recorded during search.
+    pub fn lmr_researches(&self) -> usize {
+        self.lmr_researches
+    }
+
+    /// The number of quiet moves skipped by futility pruning during search.
+    pub fn futility_prunes(&self) -> usize {
+        self.futility_prunes
+    }
+
+    /// The number of captures skipped by delta pruning during quiescence search.
+    pub fn delta_prunes(&self) -> usize {
+        self.delta_prunes
+    }
+
+    /// The number of singular extensions recorded during search.
+    pub fn singular_extensions(&self) -> usize {
+        self.singular_extensions
+    }
+
+    /// The number of internal iterative reductions recorded during search.
+    pub fn iir_reductions(&self) -> usize {
+        self.iir_reductions
+    }
+
+    /// The number of nodes visited since the last call to `publish`.
+    pub fn unpublished_nodes(&self) -> usize {
+        self.all_nodes_visited() - self.published_nodes
+    }
+
+    /// The greatest distance from the root reached during search.
+    pub fn seldepth(&self) -> u8 {
+        self.seldepth
+    }
+
+    /// The time taken to complete the iteration to `depth`, or `None` if it never completed.
+    pub fn time_to_depth(&self, depth: u8) -> Option<Duration> {
+        self.iteration_times
+            .get((depth as usize).checked_sub(1)?)
+            .copied()
+    }
+
     /// The number of nodes visited during main search.
     pub fn nodes_visited(&self) -> usize {
         self.nodes_visited
@@ -175,6 +388,46 @@ impl Tracer {
     }
 }
 
+/// Search stats totalled across all the threads of a search, so that the master thread can report
+/// them. Each thread's `Tracer` publishes its counts here every so often.
+#[derive(Debug, Default)]
+pub struct SharedStats {
+    /// The number of nodes (main search _and_ quiescence) visited by all threads.
+    nodes: AtomicUsize,
+    /// The number of successful tablebase probes by all threads.
+    tb_hits: AtomicUsize,
+    /// The greatest distance from the root reached by any thread.
+    seldepth: AtomicU8,
+}
+
+impl SharedStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset the stats, ready for a new search.
+    pub fn clear(&self) {
+        self.nodes.store(0, Ordering::Relaxed);
+        self.tb_hits.store(0, Ordering::Relaxed);
+        self.seldepth.store(0, Ordering::Relaxed);
+    }
+
+    /// The number of nodes published so far.
+    pub fn nodes(&self) -> usize {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    /// The number of tablebase hits published so far.
+    pub fn tb_hits(&self) -> usize {
+        self.tb_hits.load(Ordering::Relaxed)
+    }
+
+    /// The greatest selective depth published so far.
+    pub fn seldepth(&self) -> u8 {
+        self.seldepth.load(Ordering::Relaxed)
+    }
+}
+
 /// Used in Newton-Raphson iteration to calculate effective branching factor.
 ///
 /// Represents the numerator in f(x_i)/f'(x_i)
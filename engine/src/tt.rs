@@ -222,10 +222,21 @@ impl<'a> WritableEntry<'a> {
     }
 
     /// Write data to the entry.
+    ///
+    /// A null `mov` means the search found no best move, usually because it failed low. If the
+    /// entry already holds a move for this position, we keep it, since it remains our best guess
+    /// for move ordering.
     #[inline]
     pub fn write(&self, pos: &Position, score: Score, depth: u8, bound: Bound, mov: &Move) {
         let sig = (pos.zobrist().0 >> 48) as u16;
 
+        let old = self.read();
+        let mov = if mov.is_null() && !old.is_empty() && old.sig == sig {
+            old.mov.clone()
+        } else {
+            PackedMove::from_move(mov)
+        };
+
         // SAFETY: we know that the `'a` reference will be outlived by the table, so we can never
         // end up writing to a completely unrelated address. However, this may well be racy or
         // break mutability guarantees. All we know is that we are writing into the table, which is
@@ -236,7 +247,7 @@ impl<'a> WritableEntry<'a> {
                 depth,
                 gen_bound: GenBound::from_raw_parts(1, bound),
                 score,
-                mov: PackedMove::from_move(mov),
+                mov,
             }
         }
     }
@@ -507,4 +518,22 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn fail_low_keeps_move() {
+        core::init::init_globals();
+
+        let tt = Table::new(1);
+        let pos = Position::start_pos();
+        let mov = pos.move_from_san("e4").unwrap();
+
+        let entry = tt.probe(&pos).into_inner();
+        entry.write(&pos, Score::cp(30), 4, Bound::Exact, &mov);
+        entry.write(&pos, Score::cp(-50), 5, Bound::Upper, &Move::null());
+
+        let e = entry.read();
+        assert_eq!(e.depth, 5);
+        assert_eq!(e.bound(), Bound::Upper);
+        assert_eq!(e.mov.to_move(&pos), mov);
+    }
 }
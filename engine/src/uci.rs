@@ -1,5 +1,6 @@
-use super::options::EngineOpt;
+use super::options::{EngineOpt, MAX_MULTI_PV};
 use super::time::{TimeControl, TimingMode};
+use core::position::Variant;
 
 /// A UCI message sent by the GUI to the engine.
 #[derive(Clone, Debug)]
@@ -17,9 +18,12 @@ pub enum Command {
     /// Set an engine configuration option.
     SetOption(EngineOpt),
     /// Commence the search process.
-    Go(TimingMode),
+    Go(GoParams),
     /// Halt the search process, but don't quit the engine.
     Stop,
+    /// The opponent played the move we were pondering on, so the search should carry on as a
+    /// normal one.
+    PonderHit,
     /// Stop the search process and quit the engine.
     Quit,
     /// Display the board in ascii format.
@@ -43,6 +47,18 @@ pub enum Command {
     Perft(usize),
 }
 
+/// The parameters of a `go` command.
+#[derive(Clone, Debug)]
+pub struct GoParams {
+    /// When to stop searching.
+    pub timing: TimingMode,
+    /// The moves to restrict the root search to, as given in UCI notation. If empty, every move
+    /// is searched.
+    pub search_moves: Vec<String>,
+    /// Whether to search in ponder mode, ignoring the clock until `ponderhit`.
+    pub ponder: bool,
+}
+
 /// The reserved keywords which can be sent from the GUI to the engine.
 #[derive(PartialEq)]
 enum Keyword {
@@ -111,14 +127,14 @@ pub enum Error {
     UnexpectedToken,
     /// An attempt was made to set an option with an invalid name.
     InvalidOption,
+    /// An attempt was made to set an option to a value it does not accept.
+    InvalidOptionValue,
     /// No position was defined after the `position` keyword.
     NoPosition,
     /// The position provided was invalid.
     InvalidPosition(core::position::FenError),
     /// A move provided as part of setting the position is invalid.
     InvalidMove,
-    /// A go command was issued with an unsupported time control.
-    UnsupportedTimeControl,
     /// A go comannd was issued with a time control that was incomplete.
     IncompleteTimeControl,
 }
@@ -222,6 +238,7 @@ impl<'a> Parser<'a> {
                 Token::Kw(Keyword::Position) => self.parse_position_and_moves(),
                 Token::Kw(Keyword::Go) => self.parse_go(),
                 Token::Kw(Keyword::Stop) => self.parse_stop(),
+                Token::Kw(Keyword::PonderHit) => self.parse_ponderhit(),
                 Token::Kw(Keyword::Quit) => self.parse_quit(),
                 Token::Kw(Keyword::Display) => self.parse_display(),
                 Token::Kw(Keyword::DisplayLichess) => self.parse_display_lichess(),
@@ -315,8 +332,8 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_go(&mut self) -> PResult {
-        // The next token will be one of:
-        // - searchmoves
+        // The tokens which follow can be any of:
+        // - searchmoves <move1> ... <movei>
         // - ponder
         // - wtime, btime, winc, binc
         // - movestogo
@@ -326,101 +343,136 @@ impl<'a> Parser<'a> {
         // - movetime
         // - infinite
         //
-        // We only support time control, depth and infinite for now. We'll match on the next
-        // token, and handle the legitimate UCI commands with a panic saying that we don't support
-        // that time control (or we could just return `go infinite` and at least not crash).
-
-        match self.peek() {
-            Some(tok) => match *tok {
-                Token::Kw(Keyword::SearchMoves) => self.unsupported_time_control(),
-                Token::Kw(Keyword::Ponder) => self.unsupported_time_control(),
-                Token::Kw(Keyword::Wtime) => self.parse_time_control(),
-                Token::Kw(Keyword::Btime) => self.parse_time_control(),
-                Token::Kw(Keyword::Winc) => self.parse_time_control(),
-                Token::Kw(Keyword::Binc) => self.parse_time_control(),
-                Token::Kw(Keyword::MovesToGo) => self.parse_time_control(),
-                Token::Kw(Keyword::Depth) => self.parse_depth(),
-                Token::Kw(Keyword::Nodes) => self.unsupported_time_control(),
-                Token::Kw(Keyword::Mate) => self.unsupported_time_control(),
-                Token::Kw(Keyword::MoveTime) => self.parse_movetime(),
-                Token::Kw(Keyword::Infinite) => self.parse_infinite(),
-                Token::Kw(Keyword::PonderHit) => self.unsupported_time_control(),
-                _ => Err(Error::UnexpectedToken),
-            },
-            None => self.unexpected_end(),
+        // We also accept `go perft <depth>`, which is not part of UCI but is the form of the perft
+        // command used by other engines such as Stockfish.
+        if let Some(Token::Kw(Keyword::Perft)) = self.peek() {
+            self.advance();
+            return self.parse_perft();
         }
-    }
 
-    fn parse_stop(&mut self) -> PResult {
-        Ok(Command::Stop)
-    }
-
-    fn parse_quit(&mut self) -> PResult {
-        Ok(Command::Quit)
-    }
+        if self.peek().is_none() {
+            return self.unexpected_end();
+        }
 
-    fn parse_time_control(&mut self) -> PResult {
+        let mut search_moves = Vec::new();
+        let mut ponder = false;
+        let mut timed = false;
         let mut wtime: Option<usize> = None;
         let mut btime: Option<usize> = None;
         let mut winc: usize = 0;
         let mut binc: usize = 0;
         let mut moves_to_go: Option<usize> = None;
+        let mut depth: Option<u8> = None;
+        let mut nodes: Option<usize> = None;
+        let mut mate: Option<u8> = None;
+        let mut movetime: Option<usize> = None;
+        let mut infinite = false;
 
         while self.peek().is_some() {
             match self.advance().unwrap() {
+                Token::Kw(Keyword::SearchMoves) => {
+                    search_moves = self.parse_search_moves()?;
+                }
                 Token::Kw(Keyword::Wtime) => {
+                    timed = true;
                     wtime = Some(self.parse_integer()?);
                 }
                 Token::Kw(Keyword::Btime) => {
+                    timed = true;
                     btime = Some(self.parse_integer()?);
                 }
                 Token::Kw(Keyword::Winc) => {
+                    timed = true;
                     winc = self.parse_integer()?;
                 }
                 Token::Kw(Keyword::Binc) => {
+                    timed = true;
                     binc = self.parse_integer()?;
                 }
-                Token::Kw(Keyword::MovesToGo) => moves_to_go = Some(self.parse_integer()?),
+                Token::Kw(Keyword::MovesToGo) => {
+                    timed = true;
+                    moves_to_go = Some(self.parse_integer()?);
+                }
+                Token::Kw(Keyword::Depth) => {
+                    depth = Some(self.parse_integer()? as u8);
+                }
+                Token::Kw(Keyword::Nodes) => {
+                    nodes = Some(self.parse_integer()?);
+                }
+                Token::Kw(Keyword::Mate) => {
+                    let n = self.parse_integer()?;
+                    mate = Some(u8::try_from(n).map_err(|_| Error::ExpectedNumber)?);
+                }
+                Token::Kw(Keyword::MoveTime) => {
+                    movetime = Some(self.parse_integer()?);
+                }
+                Token::Kw(Keyword::Infinite) => {
+                    infinite = true;
+                }
+                Token::Kw(Keyword::Ponder) => {
+                    ponder = true;
+                }
                 _ => {
-                    return Err(Error::UnexpectedToken);
+                    return self.unexpected_token();
                 }
             }
         }
 
-        if wtime.is_none() || btime.is_none() {
-            return Err(Error::IncompleteTimeControl);
-        }
+        // A search has a single limit, so where several are given, `infinite` wins over `mate`,
+        // then `movetime`, the clock, `nodes` and finally `depth`.
+        let timing = if infinite {
+            TimingMode::Infinite
+        } else if let Some(n) = mate {
+            TimingMode::Mate(n)
+        } else if let Some(t) = movetime {
+            TimingMode::MoveTime(t)
+        } else if timed {
+            match (wtime, btime) {
+                (Some(wtime), Some(btime)) => {
+                    TimingMode::Timed(TimeControl::new(wtime, btime, winc, binc, moves_to_go))
+                }
+                _ => return Err(Error::IncompleteTimeControl),
+            }
+        } else if let Some(n) = nodes {
+            TimingMode::Nodes(n)
+        } else if let Some(d) = depth {
+            TimingMode::Depth(d)
+        } else {
+            TimingMode::Infinite
+        };
 
-        Ok(Command::Go(TimingMode::Timed(TimeControl::new(
-            wtime.expect("should not be None"),
-            btime.expect("should not be None"),
-            winc,
-            binc,
-            moves_to_go,
-        ))))
+        Ok(Command::Go(GoParams {
+            timing,
+            search_moves,
+            ponder,
+        }))
     }
 
-    fn parse_depth(&mut self) -> PResult {
-        self.advance().ok_or(Error::UnexpectedEnd)?;
+    /// Parse the moves following `searchmoves`, up to the next keyword.
+    fn parse_search_moves(&mut self) -> Result<Vec<String>, Error> {
+        let mut moves = Vec::new();
+        while let Some(Token::String(mov)) = self.peek() {
+            moves.push(mov.to_string());
+            self.advance();
+        }
 
-        let depth = self.parse_integer()? as u8;
-        Ok(Command::Go(TimingMode::Depth(depth)))
-    }
+        if moves.is_empty() {
+            return Err(Error::ExpectedString);
+        }
 
-    fn parse_infinite(&mut self) -> PResult {
-        self.advance().ok_or(Error::UnexpectedEnd)?;
-        self.expect_end(Ok(Command::Go(TimingMode::Infinite)))
+        Ok(moves)
     }
 
-    fn parse_movetime(&mut self) -> PResult {
-        self.advance().ok_or(Error::UnexpectedEnd)?;
+    fn parse_stop(&mut self) -> PResult {
+        Ok(Command::Stop)
+    }
 
-        let movetime = self.parse_integer()?;
-        self.expect_end(Ok(Command::Go(TimingMode::MoveTime(movetime))))
+    fn parse_ponderhit(&mut self) -> PResult {
+        self.expect_end(Ok(Command::PonderHit))
     }
 
-    fn unsupported_time_control(&mut self) -> PResult {
-        Err(Error::UnsupportedTimeControl)
+    fn parse_quit(&mut self) -> PResult {
+        Ok(Command::Quit)
     }
 
     fn parse_debug(&mut self) -> PResult {
@@ -437,6 +489,13 @@ impl<'a> Parser<'a> {
 
         match self.parse_string()? {
             "Hash" => self.parse_hash(),
+            "UCI_Variant" => self.parse_variant(),
+            "SyzygyPath" => self.parse_syzygy_path(),
+            "SyzygyProbeDepth" => self.parse_syzygy_probe_depth(),
+            "EgtbPath" => self.parse_egtb_path(),
+            "MultiPV" => self.parse_multi_pv(),
+            "UCI_ShowCurrLine" => self.parse_show_curr_line(),
+            "Ponder" => self.parse_ponder(),
             _ => Err(Error::InvalidOption),
         }
     }
@@ -449,6 +508,80 @@ impl<'a> Parser<'a> {
         Ok(Command::SetOption(EngineOpt::Hash(v)))
     }
 
+    fn parse_variant(&mut self) -> PResult {
+        self.expect_kw(Keyword::Value)?;
+
+        let v = Variant::from_uci_name(self.parse_string()?).ok_or(Error::InvalidOptionValue)?;
+
+        self.expect_end(Ok(Command::SetOption(EngineOpt::Variant(v))))
+    }
+
+    fn parse_syzygy_path(&mut self) -> PResult {
+        let path = self.parse_path()?;
+
+        Ok(Command::SetOption(EngineOpt::SyzygyPath(path)))
+    }
+
+    fn parse_egtb_path(&mut self) -> PResult {
+        let path = self.parse_path()?;
+
+        Ok(Command::SetOption(EngineOpt::EgtbPath(path)))
+    }
+
+    /// Parse the value of an option holding a path. `<empty>` or no path gives `None`.
+    fn parse_path(&mut self) -> Result<Option<String>, Error> {
+        self.expect_kw(Keyword::Value)?;
+
+        // Paths may contain spaces, so take the rest of the raw input rather than one token.
+        let path = match self.raw.split_once(" value ") {
+            Some((_, path)) => path.trim(),
+            None => return Err(Error::UnexpectedEnd),
+        };
+        self.cursor = self.toks.len();
+
+        if path.is_empty() || path == "<empty>" {
+            Ok(None)
+        } else {
+            Ok(Some(path.to_string()))
+        }
+    }
+
+    fn parse_syzygy_probe_depth(&mut self) -> PResult {
+        self.expect_kw(Keyword::Value)?;
+
+        let v = self.parse_integer()?;
+        let v = u8::try_from(v).map_err(|_| Error::InvalidOptionValue)?;
+
+        self.expect_end(Ok(Command::SetOption(EngineOpt::SyzygyProbeDepth(v))))
+    }
+
+    fn parse_multi_pv(&mut self) -> PResult {
+        self.expect_kw(Keyword::Value)?;
+
+        let v = self.parse_integer()?;
+        if !(1..=MAX_MULTI_PV).contains(&v) {
+            return Err(Error::InvalidOptionValue);
+        }
+
+        self.expect_end(Ok(Command::SetOption(EngineOpt::MultiPv(v))))
+    }
+
+    fn parse_show_curr_line(&mut self) -> PResult {
+        self.expect_kw(Keyword::Value)?;
+
+        let b = self.parse_bool()?;
+
+        self.expect_end(Ok(Command::SetOption(EngineOpt::ShowCurrLine(b))))
+    }
+
+    fn parse_ponder(&mut self) -> PResult {
+        self.expect_kw(Keyword::Value)?;
+
+        let b = self.parse_bool()?;
+
+        self.expect_end(Ok(Command::SetOption(EngineOpt::Ponder(b))))
+    }
+
     fn parse_display(&mut self) -> PResult {
         if self.peek().is_some() {
             match self.advance().unwrap() {
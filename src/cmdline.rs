@@ -1,5 +1,8 @@
 use crate::dev::dev;
+use crate::egtb::{egtb, EgtbArgs};
 use crate::perft::{perft, PerftArgs};
+use crate::perft_diff::{perft_diff, PerftDiffArgs};
+use crate::solve::{solve, SolveArgs};
 use clap::{Parser, Subcommand};
 use engine::engine;
 
@@ -21,6 +24,9 @@ pub struct Args {
 #[derive(Debug, Subcommand)]
 enum Commands {
     Perft(PerftArgs),
+    PerftDiff(PerftDiffArgs),
+    Egtb(EgtbArgs),
+    Solve(SolveArgs),
 }
 
 pub fn cmdline() {
@@ -38,6 +44,15 @@ pub fn cmdline() {
             Some(Commands::Perft(perft_args)) => {
                 perft(perft_args);
             }
+            Some(Commands::PerftDiff(perft_diff_args)) => {
+                perft_diff(perft_diff_args);
+            }
+            Some(Commands::Egtb(egtb_args)) => {
+                egtb(egtb_args);
+            }
+            Some(Commands::Solve(solve_args)) => {
+                solve(solve_args);
+            }
             None => {}
         }
     }
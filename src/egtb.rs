@@ -0,0 +1,75 @@
+use core::init::init_globals;
+use engine::egtb::EndgameTables;
+use engine::syzygy::Material;
+
+use separator::Separatable;
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Generate DTM endgame tables, and the tables they depend on
+#[derive(Debug, clap::Args)]
+pub struct EgtbArgs {
+    /// Directory to save the tables to, and to load existing tables from
+    #[clap(short, long, default_value = ".")]
+    out: PathBuf,
+    /// Material sets to generate, e.g. KQvK or KRPvK
+    #[clap(required = true)]
+    materials: Vec<String>,
+}
+
+pub fn egtb(args: &EgtbArgs) {
+    init_globals();
+
+    let mut tables = match EndgameTables::load_dir(&args.out) {
+        Ok(tables) => tables,
+        Err(err) => {
+            println!("cannot read {}: {}", args.out.display(), err);
+            return;
+        }
+    };
+
+    for name in &args.materials {
+        let Some(material) = Material::from_name(name) else {
+            println!("invalid material: {}", name);
+            continue;
+        };
+
+        if material.piece_count() > engine::egtb::MAX_PIECES {
+            println!(
+                "{} has too many pieces; at most {} are supported",
+                name,
+                engine::egtb::MAX_PIECES
+            );
+            continue;
+        }
+
+        let now = Instant::now();
+        let generated = tables.generate(material);
+        if generated.is_empty() {
+            println!("{} already exists", name);
+            continue;
+        }
+
+        for material in generated {
+            let table = tables.get(&material).unwrap();
+            let (wins, draws, losses) = table.counts();
+
+            if let Err(err) = table.save(&args.out) {
+                println!("cannot save {}: {}", material.name(), err);
+                return;
+            }
+
+            println!(
+                "{}: {} wins, {} draws, {} losses, longest mate {} plies",
+                material.name(),
+                wins.separated_string(),
+                draws.separated_string(),
+                losses.separated_string(),
+                table.longest_mate()
+            );
+        }
+
+        println!("{}ms to generate {}", now.elapsed().as_millis(), name);
+    }
+}
@@ -1,6 +1,9 @@
 mod cmdline;
 mod dev;
+mod egtb;
 mod perft;
+mod perft_diff;
+mod solve;
 
 use log::{info, LevelFilter};
 use simple_logger::SimpleLogger;
@@ -0,0 +1,336 @@
+use core::init::init_globals;
+use core::position::Position;
+use engine::perft::Perft;
+
+use separator::Separatable;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Compare perft divide output against a reference UCI engine, bisecting into the first subtree
+/// where the node counts differ
+#[derive(Debug, clap::Args)]
+pub struct PerftDiffArgs {
+    /// Path to the reference engine binary (e.g. a snapshot produced by `snapshot.sh`)
+    #[clap(short, long)]
+    engine: String,
+    /// Arguments to pass to the reference engine (e.g. `--uci` for a Seaborg snapshot)
+    #[clap(long = "engine-arg")]
+    engine_args: Vec<String>,
+    /// Command used to request divide output from the reference engine; the depth is appended
+    #[clap(short, long, default_value_t = String::from("go perft"))]
+    command: String,
+    /// Depth to search
+    #[clap(short = 'n', long, action, default_value_t = 1)]
+    depth: u8,
+    /// FEN string to run perft on; default to start position
+    #[clap(default_value_t = String::from(core::position::START_POSITION))]
+    fen: String,
+}
+
+pub fn perft_diff(args: &PerftDiffArgs) {
+    init_globals();
+
+    let mut pos = match Position::from_fen(&args.fen) {
+        Ok(pos) => pos,
+        Err(fen_error) => {
+            println!("{}", fen_error.msg);
+            return;
+        }
+    };
+
+    let mut reference = match UciEngine::launch(&args.engine, &args.engine_args) {
+        Ok(reference) => reference,
+        Err(err) => {
+            println!("couldn't launch {}: {}", args.engine, err);
+            return;
+        }
+    };
+
+    let mut moves = Vec::new();
+    let result = bisect(
+        &mut pos,
+        &mut reference,
+        &args.fen,
+        &args.command,
+        &mut moves,
+        args.depth as usize,
+        None,
+    );
+
+    match result {
+        Ok(Some(diff)) => diff.report(&pos, &args.fen, &moves),
+        Ok(None) => println!("no difference found at depth {}", args.depth),
+        Err(err) => println!("error communicating with {}: {}", args.engine, err),
+    }
+
+    reference.quit();
+}
+
+/// The first point at which our divide output disagrees with the reference engine.
+enum Diff {
+    /// A move we generate which the reference engine does not.
+    Extra(String),
+    /// A move the reference engine generates which we do not.
+    Missing(String),
+    /// Both engines generate the move, but the subtree counts differ at depth 1. This can only
+    /// happen if the reference engine reports something other than 1 for a leaf.
+    Count(String, usize, usize),
+}
+
+impl Diff {
+    fn report(&self, pos: &Position, root_fen: &str, moves: &[String]) {
+        println!("divide output differs");
+        println!("fen:   {}", pos.to_fen());
+        println!("root:  {}", root_fen);
+        println!("moves: {}", moves.join(" "));
+
+        match self {
+            Diff::Extra(mov) => println!("{} is generated here, but not by the reference", mov),
+            Diff::Missing(mov) => println!("{} is generated by the reference, but not here", mov),
+            Diff::Count(mov, ours, theirs) => println!(
+                "{}: {} here, {} from the reference",
+                mov,
+                ours.separated_string(),
+                theirs.separated_string()
+            ),
+        }
+    }
+}
+
+/// Recursively compare divide output at `depth`, descending into the first move whose subtree
+/// count differs. On return, `moves` holds the move list leading from the root to the position
+/// where the difference was found, and `pos` is left in that position.
+///
+/// `expected` is the count the reference engine reported for the move leading to `pos`, or
+/// `None` at the root.
+fn bisect(
+    pos: &mut Position,
+    reference: &mut UciEngine,
+    root_fen: &str,
+    command: &str,
+    moves: &mut Vec<String>,
+    depth: usize,
+    expected: Option<usize>,
+) -> io::Result<Option<Diff>> {
+    let ours = Perft::divide_counts(pos, depth);
+    let theirs = reference.divide(root_fen, moves, command, depth)?;
+
+    // An empty divide from the reference is only believable if it has already told us that
+    // this position is terminal. Otherwise it most likely didn't understand the command, and
+    // reporting every one of our moves as extra would be a false diagnosis.
+    if theirs.is_empty() && !ours.is_empty() && expected != Some(0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "reference engine printed no divide output for `{} {}`",
+                command, depth
+            ),
+        ));
+    }
+
+    for (mov, _) in &ours {
+        if !theirs.contains_key(&mov.to_uci_string()) {
+            return Ok(Some(Diff::Extra(mov.to_uci_string())));
+        }
+    }
+
+    let mut missing = theirs
+        .keys()
+        .filter(|m| !ours.iter().any(|(mov, _)| mov.to_uci_string() == **m))
+        .collect::<Vec<_>>();
+    missing.sort();
+    if let Some(mov) = missing.first() {
+        return Ok(Some(Diff::Missing(mov.to_string())));
+    }
+
+    for (mov, count) in &ours {
+        let uci = mov.to_uci_string();
+        let reference_count = theirs[&uci];
+
+        if *count != reference_count {
+            if depth == 1 {
+                return Ok(Some(Diff::Count(uci, *count, reference_count)));
+            }
+
+            println!(
+                "{}: {} here, {} from the reference; descending",
+                uci,
+                count.separated_string(),
+                reference_count.separated_string()
+            );
+
+            pos.make_move(mov);
+            moves.push(uci);
+            return bisect(
+                pos,
+                reference,
+                root_fen,
+                command,
+                moves,
+                depth - 1,
+                Some(reference_count),
+            );
+        }
+    }
+
+    Ok(None)
+}
+
+/// A UCI engine running as a subprocess.
+struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciEngine {
+    /// Launch the engine binary at `path` and wait for it to complete the UCI handshake.
+    fn launch(path: &str, args: &[String]) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        let mut engine = Self {
+            child,
+            stdin,
+            stdout,
+        };
+
+        engine.send("uci")?;
+        engine.read_until("uciok")?;
+
+        Ok(engine)
+    }
+
+    fn send(&mut self, cmd: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", cmd)?;
+        self.stdin.flush()
+    }
+
+    /// Read lines from the engine until one equal to `terminator` is found, returning the lines
+    /// read before it.
+    fn read_until(&mut self, terminator: &str) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        let mut buf = String::new();
+
+        loop {
+            buf.clear();
+            if self.stdout.read_line(&mut buf)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("engine exited before sending `{}`", terminator),
+                ));
+            }
+
+            let line = buf.trim();
+            if line == terminator {
+                return Ok(lines);
+            }
+            lines.push(line.to_string());
+        }
+    }
+
+    /// Request divide output for the position reached by playing `moves` from `fen`.
+    ///
+    /// We follow the perft command with `isready`, since engines answer commands in order and so
+    /// `readyok` tells us that all of the divide output has been printed, however many summary
+    /// lines the engine adds after it.
+    fn divide(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        command: &str,
+        depth: usize,
+    ) -> io::Result<HashMap<String, usize>> {
+        if moves.is_empty() {
+            self.send(&format!("position fen {}", fen))?;
+        } else {
+            self.send(&format!("position fen {} moves {}", fen, moves.join(" ")))?;
+        }
+        self.send(&format!("{} {}", command, depth))?;
+        self.send("isready")?;
+
+        Ok(self
+            .read_until("readyok")?
+            .iter()
+            .filter_map(|line| parse_divide_line(line))
+            .collect())
+    }
+
+    fn quit(mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+/// Parse a line of divide output of the form `e2e4: 1,234`, ignoring thousands separators.
+/// Returns `None` for any other line.
+fn parse_divide_line(line: &str) -> Option<(String, usize)> {
+    let (mov, count) = line.split_once(':')?;
+    let mov = mov.trim();
+    let bytes = mov.as_bytes();
+
+    let is_square =
+        |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+    let valid = match bytes.len() {
+        4 => true,
+        5 => b"qrbn".contains(&bytes[4]),
+        _ => false,
+    } && is_square(bytes[0], bytes[1])
+        && is_square(bytes[2], bytes[3]);
+
+    if !valid {
+        return None;
+    }
+
+    let count = count.trim().replace(',', "").parse::<usize>().ok()?;
+    Some((mov.to_string(), count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_divide_lines() {
+        assert_eq!(
+            parse_divide_line("e2e4: 20"),
+            Some(("e2e4".to_string(), 20))
+        );
+        assert_eq!(
+            parse_divide_line("  g1f3 :  1,234,567 "),
+            Some(("g1f3".to_string(), 1_234_567))
+        );
+        assert_eq!(
+            parse_divide_line("a7a8q: 0"),
+            Some(("a7a8q".to_string(), 0))
+        );
+        assert_eq!(
+            parse_divide_line("h2h1n: 3"),
+            Some(("h2h1n".to_string(), 3))
+        );
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(parse_divide_line(""), None);
+        assert_eq!(parse_divide_line("readyok"), None);
+        assert_eq!(parse_divide_line("Nodes searched: 197281"), None);
+        assert_eq!(parse_divide_line("info string depth: 4"), None);
+        assert_eq!(parse_divide_line("e2e4 20"), None);
+        assert_eq!(parse_divide_line("e2e4:"), None);
+        assert_eq!(parse_divide_line("e2e4: lots"), None);
+        assert_eq!(parse_divide_line("e2e9: 20"), None);
+        assert_eq!(parse_divide_line("i2e4: 20"), None);
+        assert_eq!(parse_divide_line("a7a8k: 20"), None);
+        assert_eq!(parse_divide_line("e2e4e5: 20"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use core::init::init_globals;
+use core::position::Position;
+use engine::dfpn::{MateSolver, Solution, MAX_MATE_MOVES};
+
+use separator::Separatable;
+
+use std::time::Instant;
+
+/// Prove the shortest forced mate for the side to move with a proof-number search
+#[derive(Debug, clap::Args)]
+pub struct SolveArgs {
+    /// Longest mate to look for, in moves
+    #[clap(
+        short = 'm',
+        long,
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u8).range(1..=MAX_MATE_MOVES as i64)
+    )]
+    moves: u8,
+    /// Only consider checking moves for the attacker
+    #[clap(short, long, action, default_value_t = false)]
+    checks: bool,
+    /// Give up after expanding this many nodes
+    #[clap(short, long)]
+    nodes: Option<usize>,
+    /// FEN string of the position to solve
+    fen: String,
+}
+
+pub fn solve(args: &SolveArgs) {
+    init_globals();
+
+    let mut pos = match Position::from_fen(&args.fen) {
+        Ok(pos) => pos,
+        Err(fen_error) => {
+            println!("{}", fen_error.msg);
+            return;
+        }
+    };
+
+    let mut solver = MateSolver::new();
+    solver.set_checks_only(args.checks);
+    if let Some(nodes) = args.nodes {
+        solver.set_node_limit(nodes);
+    }
+
+    let now = Instant::now();
+    match solver.solve(&mut pos, args.moves) {
+        Solution::Mate { moves, line } => println!(
+            "mate in {}: {}",
+            moves,
+            line.iter()
+                .map(|m| m.to_uci_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        ),
+        Solution::NoMate => println!("no mate in {} moves", args.moves),
+        Solution::Unknown => println!("unknown; node limit reached"),
+    }
+
+    println!(
+        "{} nodes in {}ms",
+        solver.nodes().separated_string(),
+        now.elapsed().as_millis()
+    );
+}